@@ -1,32 +1,61 @@
+use ark_std::rand::{rngs::StdRng, SeedableRng};
 use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
-use pdqhash::regime_a::{client_submit, server_verify_and_decide, RegimeAParams, TtpSetup};
+use pdqhash::regime_a::{
+    batch::server_verify_batch, client_submit, server_verify_and_decide, ClientId, RegimeAParams,
+    SubmissionHeader, TtpSetup,
+};
+
+/// Fast, non-cryptographic generator for synthetic benchmark inputs. Never
+/// use it for setup randomness: its output is predictable from the seed.
+struct XorShift64 {
+    state: u64,
+}
+
+impl XorShift64 {
+    fn new(seed: u64) -> Self {
+        let seed = if seed == 0 { 0x9e3779b97f4a7c15 } else { seed };
+        Self { state: seed }
+    }
+
+    fn next_bit(&mut self) -> u8 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.state = x;
+        (x >> 63) as u8
+    }
+
+    fn bits(&mut self, lambda: usize) -> Vec<u8> {
+        (0..lambda).map(|_| self.next_bit()).collect()
+    }
+}
 
 fn synth_db(n: usize, lambda: usize) -> Vec<Vec<u8>> {
-    (0..n)
-        .map(|i| {
-            (0..lambda)
-                .map(|j| ((i * 131 + j * 17 + 3) % 2) as u8)
-                .collect::<Vec<_>>()
-        })
-        .collect()
+    let mut rng = XorShift64::new(n as u64);
+    (0..n).map(|_| rng.bits(lambda)).collect()
 }
 
 fn synth_query(lambda: usize) -> Vec<u8> {
-    (0..lambda).map(|i| ((i * 7 + 11) % 2) as u8).collect()
+    XorShift64::new(7).bits(lambda)
 }
 
 fn regime_a_microbenchmark(c: &mut Criterion) {
     let mut group = c.benchmark_group("regime_a_micro");
+    // Setup and client cost are dominated by Groth16 key generation and
+    // proving, which take seconds each.
+    group.sample_size(10);
+    let mut rng = StdRng::seed_from_u64(77);
 
-    for n in [32usize, 128, 512] {
-        let params = RegimeAParams::new(16, 16, 6);
+    for n in [32usize, 128, 512, 2048] {
+        let params = RegimeAParams::new(8, 8, 3);
         let lambda = params.lambda();
         let db = synth_db(n, lambda);
         let query = synth_query(lambda);
 
         group.bench_with_input(BenchmarkId::new("ttp_setup", n), &n, |b, _| {
             b.iter(|| {
-                black_box(TtpSetup::setup(
+                black_box(TtpSetup::setup_seeded(
                     black_box(db.clone()),
                     black_box(params.clone()),
                     black_box(12345),
@@ -34,31 +63,114 @@ fn regime_a_microbenchmark(c: &mut Criterion) {
             })
         });
 
-        let setup = TtpSetup::setup(db, params, 12345);
+        let TtpSetup {
+            client_key,
+            server_key,
+            ..
+        } = TtpSetup::setup_seeded(db, params, 12345);
+
+        // The table lookups alone, without proving: should be flat in `n`.
+        group.bench_with_input(BenchmarkId::new("client_lookup", n), &n, |b, _| {
+            b.iter(|| black_box(client_key.masked_sum(black_box(&query))))
+        });
 
         group.bench_with_input(BenchmarkId::new("client_submit", n), &n, |b, _| {
             b.iter(|| {
                 black_box(client_submit(
-                    black_box(&setup),
+                    black_box(&client_key),
                     black_box(query.clone()),
-                    black_box(77),
+                    black_box(SubmissionHeader::now(ClientId([0; 32]), 77)),
+                    &mut rng,
                 ))
             })
         });
 
-        let submission = client_submit(&setup, query, 77);
+        let submission = client_submit(
+            &client_key,
+            query,
+            SubmissionHeader::now(ClientId([0; 32]), 77),
+            &mut rng,
+        );
         group.bench_with_input(BenchmarkId::new("server_verify", n), &n, |b, _| {
             b.iter(|| {
                 black_box(server_verify_and_decide(
-                    black_box(&setup),
+                    black_box(&server_key),
                     black_box(&submission),
                 ))
             })
         });
     }
 
+    // Client lookups grow with the number of chunks instead.
+    for b_chunks in [4usize, 8, 16] {
+        let params = RegimeAParams::new(8, b_chunks, 3);
+        let lambda = params.lambda();
+        let query = synth_query(lambda);
+        let setup = TtpSetup::setup_seeded(synth_db(32, lambda), params, 12345);
+        group.bench_with_input(
+            BenchmarkId::new("client_lookup_by_chunks", b_chunks),
+            &b_chunks,
+            |b, _| b.iter(|| black_box(setup.client_key.masked_sum(black_box(&query)))),
+        );
+    }
+
+    group.finish();
+}
+
+/// Server cost at realistic database sizes, for single submissions and for
+/// batches of `BATCH` verified together.
+fn regime_a_server_scaling(c: &mut Criterion) {
+    const BATCH: usize = 8;
+    let mut group = c.benchmark_group("regime_a_server");
+    group.sample_size(10);
+    let mut rng = StdRng::seed_from_u64(78);
+
+    for n in [10_000usize, 100_000] {
+        let params = RegimeAParams::new(8, 8, 3);
+        let lambda = params.lambda();
+        let setup = TtpSetup::setup_seeded(synth_db(n, lambda), params, 12345);
+        let submissions = (0..BATCH as u64)
+            .map(|msgid| {
+                client_submit(
+                    &setup.client_key,
+                    synth_query(lambda),
+                    SubmissionHeader::now(ClientId([0; 32]), msgid),
+                    &mut rng,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        group.bench_with_input(BenchmarkId::new("server_verify", n), &n, |b, _| {
+            b.iter(|| {
+                black_box(server_verify_and_decide(
+                    black_box(&setup.server_key),
+                    black_box(&submissions[0]),
+                ))
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("server_verify_each", n), &n, |b, _| {
+            b.iter(|| {
+                for submission in &submissions {
+                    black_box(server_verify_and_decide(
+                        black_box(&setup.server_key),
+                        black_box(submission),
+                    ))
+                    .unwrap();
+                }
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("server_verify_batch", n), &n, |b, _| {
+            b.iter(|| {
+                black_box(server_verify_batch(
+                    black_box(&setup.server_key),
+                    black_box(&submissions),
+                ))
+            })
+        });
+    }
+
     group.finish();
 }
 
-criterion_group!(benches, regime_a_microbenchmark);
+criterion_group!(benches, regime_a_microbenchmark, regime_a_server_scaling);
 criterion_main!(benches);
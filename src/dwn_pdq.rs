@@ -298,13 +298,10 @@ const HASH_LENGTH: usize = PDQ_HASH_LENGTH;
 #[derive(Debug, Clone)]
 pub(crate) struct PDQPrecomputed {
     /// Downsampled 64x64 grayscale buffer after filtering.
+    #[cfg_attr(not(feature = "snark"), allow(dead_code))]
     pub(crate) buffer64: [[f32; BUFFER_W_H]; BUFFER_W_H],
-    /// Float DCT coefficients for the top-left 16x16 block.
-    pub(crate) dct16: [f32; DCT_OUTPUT_MATRIX_SIZE],
     /// PDQ quality score derived from the 64x64 buffer.
     pub(crate) quality: f32,
-    /// Median of the float DCT coefficients.
-    pub(crate) median: f32,
     /// Final PDQ hash bytes.
     pub(crate) hash: [u8; HASH_LENGTH],
 }
@@ -409,7 +406,16 @@ pub fn generate_pdq_full_size(image: &image::DynamicImage) -> ([u8; HASH_LENGTH]
 
 /// Compute the PDQ transformation buffers for a full-size image.
 pub(crate) fn compute_pdq_state(image: &image::DynamicImage) -> PDQPrecomputed {
-    let (num_cols, num_rows, mut image) = to_luma_image(image);
+    let (num_cols, num_rows, luma) = to_luma_image(image);
+    compute_pdq_state_from_luma(num_cols, num_rows, luma)
+}
+
+/// Compute the PDQ transformation buffers from a row-major luminance buffer.
+pub(crate) fn compute_pdq_state_from_luma(
+    num_cols: usize,
+    num_rows: usize,
+    mut image: Vec<f32>,
+) -> PDQPrecomputed {
     let window_size_along_rows = compute_jarosz_filter_window_size(num_cols, BUFFER_W_H);
     let window_size_along_cols = compute_jarosz_filter_window_size(num_rows, BUFFER_W_H);
 
@@ -424,17 +430,20 @@ pub(crate) fn compute_pdq_state(image: &image::DynamicImage) -> PDQPrecomputed {
 
     let buffer64x64 =
         decimate_float::<BUFFER_W_H, BUFFER_W_H>(image.as_slice(), num_rows, num_cols);
+    compute_pdq_state_from_buffer64(buffer64x64)
+}
 
+/// Compute the PDQ hash and quality from an already filtered 64x64 buffer.
+pub(crate) fn compute_pdq_state_from_buffer64(
+    buffer64x64: [[f32; BUFFER_W_H]; BUFFER_W_H],
+) -> PDQPrecomputed {
     let buffer16x16 = dct64_to_16(&buffer64x64);
     let quality = pdq_image_domain_quality_metric(&buffer64x64);
     let hash = pdq_buffer16x16_to_bits(&buffer16x16);
-    let median = torben_median(&buffer16x16).expect("non-empty DCT buffer");
 
     PDQPrecomputed {
         buffer64: buffer64x64,
-        dct16: buffer16x16,
         quality,
-        median,
         hash,
     }
 }
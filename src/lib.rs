@@ -64,6 +64,11 @@ use std::ops::Deref;
 pub mod dwn_pdq;
 
 /// Regime A masked threshold protocol implementation.
+///
+/// This module is only available when the `regime-a` feature is enabled.
+/// Add `pdqhash = { version = "*", features = ["regime-a"] }` to your `Cargo.toml`
+/// to enable it.
+#[cfg(feature = "regime-a")]
 pub mod regime_a;
 
 /// SNARK-based zero-knowledge proof system for PDQ hashes.
@@ -74,6 +79,12 @@ pub mod regime_a;
 #[cfg(feature = "snark")]
 pub mod snark;
 
+/// Self-describing proving jobs for detached SNARK workers.
+///
+/// Only available with the `snark` feature.
+#[cfg(feature = "snark")]
+pub mod prove_job;
+
 // Re-export commonly used items
 pub use dwn_pdq::generate_pdq;
 pub use dwn_pdq::PDQ_HASH_LENGTH;
@@ -95,6 +106,9 @@ pub use ark_groth16::Proof;
 #[cfg(feature = "snark")]
 pub use snark::PDQHashCircuit;
 
+#[cfg(feature = "snark")]
+pub use snark::PdqWitness;
+
 const LUMA_FROM_R_COEFF: f32 = 0.299;
 const LUMA_FROM_G_COEFF: f32 = 0.587;
 const LUMA_FROM_B_COEFF: f32 = 0.114;
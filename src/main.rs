@@ -14,11 +14,32 @@ use std::path::PathBuf;
 #[cfg(feature = "snark")]
 use {
     ark_bls12_381::Fr as BlsFr,
-    ark_groth16::{Proof, VerifyingKey},
+    ark_groth16::{Proof, ProvingKey, VerifyingKey},
     ark_serialize::{CanonicalDeserialize, CanonicalSerialize},
     ark_std::rand::rngs::StdRng,
     ark_std::rand::SeedableRng,
-    pdqhash::{Bls12_381, PDQSnark},
+    pdqhash::prove_job::{JobOutputs, ProveJob},
+    pdqhash::{Bls12_381, PDQHashCircuit, PDQSnark, PdqWitness},
+};
+
+#[cfg(feature = "regime-a")]
+use {
+    ark_std::rand::{
+        rngs::{OsRng, StdRng as RegimeARng},
+        RngCore,
+    },
+    pdqhash::regime_a::{
+        audit::{verify_log, AuditKey, AuditLog, AuditPublicKey},
+        client_submit, net,
+        pdq::PdqLayout,
+        session::ServerSession,
+        simulate::{Perturbation, Simulation},
+        wire::Wire,
+        ClientId, ClientKey, ClientSubmission, RegimeAParams, ServerKey, SubmissionHeader,
+        TtpSetup,
+    },
+    pdqhash::PDQ_HASH_LENGTH,
+    std::time::Duration,
 };
 
 /// Command-line interface for the PDQ Hash tool
@@ -82,6 +103,238 @@ enum Commands {
         #[clap(long)]
         verifying_key: PathBuf,
     },
+
+    /// Report circuit size per phase and prover timings (requires 'snark' feature)
+    #[cfg(feature = "snark")]
+    Profile {
+        /// Image to prove when measuring prover timings (sizes only if omitted)
+        #[clap(short, long)]
+        input: Option<PathBuf>,
+
+        /// Print the report as JSON
+        #[clap(long)]
+        json: bool,
+    },
+
+    /// Generate a proving/verifying key pair for prove workers (requires 'snark' feature)
+    #[cfg(feature = "snark")]
+    Setup {
+        /// Output file for the proving key (default: proving_key.bin)
+        #[clap(long, default_value = "proving_key.bin")]
+        proving_key: PathBuf,
+
+        /// Output file for the verifying key (default: verifying_key.bin)
+        #[clap(long, default_value = "verifying_key.bin")]
+        verifying_key: PathBuf,
+    },
+
+    /// Turn an image into a detached prove job (requires 'snark' feature)
+    #[cfg(feature = "snark")]
+    MakeJob {
+        /// Path to the input image file
+        #[clap(short, long)]
+        input: PathBuf,
+
+        /// Verifying key of the key pair the job will be proven under
+        #[clap(long)]
+        verifying_key: PathBuf,
+
+        /// Output file for the job
+        #[clap(short, long)]
+        output: PathBuf,
+    },
+
+    /// Prove detached jobs without access to the original images (requires 'snark' feature)
+    #[cfg(feature = "snark")]
+    ProveWorker {
+        /// Path to the proving key file
+        #[clap(long)]
+        proving_key: PathBuf,
+
+        /// Directory of `*.job` files (default: read jobs from stdin)
+        #[clap(short, long)]
+        jobs: Option<PathBuf>,
+
+        /// Directory for proofs and public inputs
+        #[clap(short, long)]
+        output: PathBuf,
+    },
+
+    /// Regime A masked threshold matching (requires 'regime-a' feature)
+    #[cfg(feature = "regime-a")]
+    RegimeA {
+        /// Regime A subcommand to execute
+        #[clap(subcommand)]
+        command: RegimeACommand,
+    },
+}
+
+#[cfg(feature = "regime-a")]
+#[derive(clap::Subcommand, Debug)]
+enum RegimeACommand {
+    /// Build client and server keys for a database of PDQ hashes
+    Setup {
+        /// File of hex PDQ hashes, one per line
+        #[clap(long)]
+        hashes: PathBuf,
+
+        /// How hash bits are assigned to chunks
+        #[clap(long, value_enum, default_value = "frequency", conflicts_with = "ell")]
+        layout: PdqLayoutArg,
+
+        /// Chunk length in bits, with chunks in hash order (default: the
+        /// chosen layout)
+        #[clap(long, requires = "epsilon")]
+        ell: Option<usize>,
+
+        /// Chunk distance at which a chunk stops matching
+        #[clap(long, requires = "ell")]
+        epsilon: Option<usize>,
+
+        /// Output file for the client key (default: client_key.bin)
+        #[clap(long, default_value = "client_key.bin")]
+        client_key: PathBuf,
+
+        /// Output file for the server key (default: server_key.bin)
+        #[clap(long, default_value = "server_key.bin")]
+        server_key: PathBuf,
+
+        /// Output file for the TTP secret, needed for later database updates
+        #[clap(long)]
+        secret: Option<PathBuf>,
+    },
+
+    /// Accept submissions on a socket and answer with decisions
+    Serve {
+        /// Path to the server key file
+        #[clap(long)]
+        server_key: PathBuf,
+
+        #[clap(flatten)]
+        endpoint: Endpoint,
+
+        /// Seconds a submission stays valid after it is issued
+        #[clap(long, default_value_t = 300)]
+        ttl: u64,
+
+        /// Seconds a connection may stall in a read or write before it is dropped
+        #[clap(long, default_value_t = 30, value_parser = clap::value_parser!(u64).range(1..))]
+        io_timeout: u64,
+
+        /// Append a signed receipt of every decision to this log
+        #[clap(long, requires = "audit_key")]
+        audit_log: Option<PathBuf>,
+
+        /// Path to the audit key receipts are signed with
+        #[clap(long, requires = "audit_log")]
+        audit_key: Option<PathBuf>,
+    },
+
+    /// Generate a key pair for signing decision receipts
+    AuditKeygen {
+        /// Output file for the secret audit key (default: audit_key.bin)
+        #[clap(long, default_value = "audit_key.bin")]
+        key: PathBuf,
+
+        /// Output file for the public audit key (default: audit_public_key.bin)
+        #[clap(long, default_value = "audit_public_key.bin")]
+        public_key: PathBuf,
+    },
+
+    /// Check the signatures and hash chain of a receipt log
+    AuditVerify {
+        /// Path to the receipt log
+        #[clap(long)]
+        log: PathBuf,
+
+        /// Path to the public audit key
+        #[clap(long)]
+        public_key: PathBuf,
+
+        /// Print every receipt as a line of JSON
+        #[clap(long)]
+        receipts: bool,
+    },
+
+    /// Hash images, submit them to a server and print the decisions
+    Submit {
+        /// Path to the client key file
+        #[clap(long)]
+        client_key: PathBuf,
+
+        /// Image to check; repeat to submit several over one connection
+        #[clap(short, long, required = true)]
+        image: Vec<PathBuf>,
+
+        #[clap(flatten)]
+        endpoint: Endpoint,
+
+        /// Client identity as 64 hex digits (default: random)
+        #[clap(long)]
+        client_id: Option<String>,
+
+        /// Message id of the first image, counting up for the rest (default: random)
+        #[clap(long)]
+        msgid: Option<u64>,
+    },
+
+    /// Enrol a directory of images and report how often perturbed copies match
+    Simulate {
+        /// Directory of original images to enrol
+        #[clap(long)]
+        images: PathBuf,
+
+        /// Directory of unrelated images (default: hold out the second half
+        /// of --images, by file name)
+        #[clap(long)]
+        unrelated: Option<PathBuf>,
+
+        /// Perturbation to apply before submitting: none, jpeg:Q, resize:PCT,
+        /// crop:PCT or brighten:N; repeat for several (default: a standard set)
+        #[clap(short, long)]
+        perturbation: Vec<Perturbation>,
+
+        /// How hash bits are assigned to chunks
+        #[clap(long, value_enum, default_value = "frequency")]
+        layout: PdqLayoutArg,
+
+        /// Also enrol the rotations and reflections of each original
+        #[clap(long)]
+        dihedral: bool,
+
+        /// Evaluate decisions directly instead of proving every submission
+        #[clap(long)]
+        in_clear: bool,
+
+        /// Print the report as JSON
+        #[clap(long)]
+        json: bool,
+    },
+}
+
+/// Where a Regime A server listens.
+#[cfg(feature = "regime-a")]
+#[derive(clap::Args, Debug)]
+#[group(required = true, multiple = false)]
+struct Endpoint {
+    /// TCP address, such as 127.0.0.1:7878
+    #[clap(long)]
+    tcp: Option<String>,
+
+    /// Unix domain socket path
+    #[cfg(unix)]
+    #[clap(long)]
+    unix: Option<PathBuf>,
+}
+
+/// Assignment of PDQ hash bits to Regime A chunks.
+#[cfg(feature = "regime-a")]
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum PdqLayoutArg {
+    /// One DCT row per chunk, in hash order
+    Rows,
+    /// Chunks in order of increasing frequency (recommended)
+    Frequency,
 }
 
 fn main() -> anyhow::Result<()> {
@@ -128,14 +381,12 @@ fn main() -> anyhow::Result<()> {
                 .with_context(|| format!("Failed to open image: {}", input.display()))?
                 .decode()
                 .with_context(|| format!("Failed to decode image: {}", input.display()))?;
-            let (hash, _quality) =
-                generate_pdq(&img).with_context(|| "Failed to generate PDQ hash")?;
-            println!("Generating SNARK parameters...");
+            let witness = PdqWitness::from_image(&img);
+            info!("Generating SNARK parameters...");
             let mut rng = StdRng::from_entropy();
             let snark = PDQSnark::setup(&mut rng)?;
-            let image_data = std::fs::read(&input)?;
-            println!("Generating proof...");
-            let (proof, pub_inputs) = snark.create_proof(&image_data, hash, &mut rng)?;
+            info!("Generating proof...");
+            let (proof, pub_inputs) = snark.prove_witness(&witness, &mut rng)?;
             let mut proof_bytes = Vec::new();
             proof.serialize_compressed(&mut proof_bytes)?;
             std::fs::write(&output, proof_bytes)?;
@@ -148,7 +399,7 @@ fn main() -> anyhow::Result<()> {
             snark.verifying_key.serialize_compressed(&mut vk_bytes)?;
             std::fs::write(&verifying_key, vk_bytes)?;
 
-            println!("Proof, public inputs, and verifying key written.");
+            info!("Proof, public inputs, and verifying key written.");
         }
         #[cfg(feature = "snark")]
         Commands::Verify {
@@ -175,6 +426,435 @@ fn main() -> anyhow::Result<()> {
                 println!("✗ Proof is invalid!");
             }
         }
+        #[cfg(feature = "snark")]
+        Commands::Profile { input, json } => {
+            info!("Synthesizing PDQ circuit in setup mode");
+            let profile = PDQHashCircuit::<BlsFr>::constraint_profile()?;
+            let timings = match input {
+                Some(input) => {
+                    info!("Profiling proof generation for {:?}", input);
+                    let image_data = std::fs::read(&input)
+                        .with_context(|| format!("Failed to read image: {}", input.display()))?;
+                    let mut rng = StdRng::from_entropy();
+                    let snark = PDQSnark::setup(&mut rng)?;
+                    Some(snark.profile_proof(&image_data, &mut rng)?)
+                }
+                None => None,
+            };
+
+            if json {
+                let phases = profile
+                    .phases
+                    .iter()
+                    .chain(std::iter::once(&profile.total()))
+                    .map(|phase| {
+                        serde_json::json!({
+                            "name": phase.name,
+                            "constraints": phase.constraints,
+                            "witness_variables": phase.witness_variables,
+                            "public_inputs": phase.public_inputs,
+                            "linear_combinations": phase.linear_combinations,
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                let timings = timings.map(|t| {
+                    serde_json::json!({
+                        "witness_generation_ms": t.witness_generation.as_secs_f64() * 1e3,
+                        "synthesis_ms": t.synthesis.as_secs_f64() * 1e3,
+                        "proving_ms": t.proving.as_secs_f64() * 1e3,
+                        "verification_ms": t.verification.as_secs_f64() * 1e3,
+                    })
+                });
+                let report = serde_json::json!({ "phases": phases, "timings": timings });
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                print!("{}", profile);
+                if let Some(timings) = timings {
+                    println!();
+                    print!("{}", timings);
+                }
+            }
+        }
+        #[cfg(feature = "snark")]
+        Commands::Setup {
+            proving_key,
+            verifying_key,
+        } => {
+            info!("Generating SNARK parameters...");
+            let mut rng = StdRng::from_entropy();
+            let snark = PDQSnark::setup(&mut rng)?;
+
+            let mut pk_bytes = Vec::new();
+            snark.proving_key.serialize_compressed(&mut pk_bytes)?;
+            std::fs::write(&proving_key, pk_bytes)?;
+
+            let mut vk_bytes = Vec::new();
+            snark.verifying_key.serialize_compressed(&mut vk_bytes)?;
+            std::fs::write(&verifying_key, vk_bytes)?;
+
+            println!("{}", hex::encode(snark.key_id()));
+        }
+        #[cfg(feature = "snark")]
+        Commands::MakeJob {
+            input,
+            verifying_key,
+            output,
+        } => {
+            info!("Creating prove job for {:?}", input);
+            let img = ImageReader::open(&input)
+                .with_context(|| format!("Failed to open image: {}", input.display()))?
+                .decode()
+                .with_context(|| format!("Failed to decode image: {}", input.display()))?;
+            let vk_bytes = std::fs::read(&verifying_key)?;
+            let verifying_key =
+                VerifyingKey::<Bls12_381>::deserialize_compressed_unchecked(&*vk_bytes)?;
+
+            let job = ProveJob::new(
+                pdqhash::snark::key_id(&verifying_key),
+                PdqWitness::from_image(&img),
+            );
+            std::fs::write(&output, job.to_bytes())?;
+            println!("{}", job.id());
+        }
+        #[cfg(feature = "snark")]
+        Commands::ProveWorker {
+            proving_key,
+            jobs,
+            output,
+        } => {
+            info!("Loading proving key {:?}", proving_key);
+            let pk_bytes = std::fs::read(&proving_key)?;
+            let proving_key =
+                ProvingKey::<Bls12_381>::deserialize_compressed_unchecked(&*pk_bytes)?;
+            let snark = PDQSnark::from_proving_key(proving_key);
+            let outputs = JobOutputs::new(output)?;
+            let mut rng = StdRng::from_entropy();
+
+            let results = match jobs {
+                Some(dir) => outputs.run_directory(&snark, &dir, &mut rng)?,
+                None => outputs.run_stream(&snark, std::io::stdin().lock(), &mut rng)?,
+            };
+            for (id, status) in results {
+                println!("{} {:?}", id, status);
+            }
+        }
+        #[cfg(feature = "regime-a")]
+        Commands::RegimeA { command } => regime_a(command)?,
+    }
+    Ok(())
+}
+
+#[cfg(feature = "regime-a")]
+fn regime_a(command: RegimeACommand) -> anyhow::Result<()> {
+    match command {
+        RegimeACommand::Setup {
+            hashes,
+            layout,
+            ell,
+            epsilon,
+            client_key,
+            server_key,
+            secret,
+        } => {
+            let text = std::fs::read_to_string(&hashes)
+                .with_context(|| format!("Failed to read hashes: {}", hashes.display()))?;
+            let db = text
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .map(|line| {
+                    let mut hash = [0u8; PDQ_HASH_LENGTH];
+                    hex::decode_to_slice(line, &mut hash)
+                        .with_context(|| format!("Invalid PDQ hash: {}", line))?;
+                    Ok(hash)
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            anyhow::ensure!(!db.is_empty(), "No hashes in {}", hashes.display());
+            let layout = match (ell, epsilon) {
+                (Some(ell), Some(epsilon)) => {
+                    let bits = PDQ_HASH_LENGTH * 8;
+                    anyhow::ensure!(
+                        (1..=bits).contains(&ell)
+                            && bits.is_multiple_of(ell)
+                            && (1..=ell).contains(&epsilon),
+                        "ell must divide {} and epsilon must be in 1..=ell",
+                        bits
+                    );
+                    PdqLayout::in_hash_order(RegimeAParams::new(ell, bits / ell, epsilon))
+                }
+                _ => match layout {
+                    PdqLayoutArg::Rows => PdqLayout::rows(),
+                    PdqLayoutArg::Frequency => PdqLayout::frequency(),
+                },
+            };
+
+            info!("Building Regime A keys for {} hashes", db.len());
+            let setup = TtpSetup::setup(
+                layout.database_to_bits(&db),
+                layout.params().clone(),
+                &mut OsRng,
+            );
+            std::fs::write(&client_key, setup.client_key.to_bytes())?;
+            std::fs::write(&server_key, setup.server_key.to_bytes())?;
+            if let Some(secret) = secret {
+                std::fs::write(&secret, setup.secret.to_bytes())?;
+            }
+            info!("Keys written.");
+        }
+        RegimeACommand::Serve {
+            server_key,
+            endpoint,
+            ttl,
+            io_timeout,
+            audit_log,
+            audit_key,
+        } => {
+            let io_timeout = Duration::from_secs(io_timeout);
+            let bytes = std::fs::read(&server_key)
+                .with_context(|| format!("Failed to read server key: {}", server_key.display()))?;
+            let key = ServerKey::from_bytes(&bytes)?;
+            let mut session = ServerSession::new(key, Duration::from_secs(ttl));
+            if let (Some(log), Some(audit_key)) = (audit_log, audit_key) {
+                let bytes = std::fs::read(&audit_key).with_context(|| {
+                    format!("Failed to read audit key: {}", audit_key.display())
+                })?;
+                let log = AuditLog::open(&log, AuditKey::from_bytes(&bytes)?)
+                    .with_context(|| format!("Failed to open audit log: {}", log.display()))?;
+                info!("Recording receipts after {} existing", log.summary().len);
+                session = session.with_audit_log(log);
+            }
+            if let Some(addr) = endpoint.tcp {
+                let listener = std::net::TcpListener::bind(&addr)
+                    .with_context(|| format!("Failed to listen on {}", addr))?;
+                println!("listening on {}", listener.local_addr()?);
+                net::serve_tcp(&listener, &mut session, io_timeout)?;
+            }
+            #[cfg(unix)]
+            if let Some(path) = endpoint.unix {
+                let listener = std::os::unix::net::UnixListener::bind(&path)
+                    .with_context(|| format!("Failed to listen on {}", path.display()))?;
+                println!("listening on {}", path.display());
+                net::serve_unix(&listener, &mut session, io_timeout)?;
+            }
+        }
+        RegimeACommand::AuditKeygen { key, public_key } => {
+            let audit_key = AuditKey::generate(&mut OsRng);
+            std::fs::write(&key, audit_key.to_bytes())?;
+            std::fs::write(&public_key, audit_key.public_key().to_bytes())?;
+            info!("Audit keys written.");
+        }
+        RegimeACommand::AuditVerify {
+            log,
+            public_key,
+            receipts,
+        } => {
+            let bytes = std::fs::read(&public_key).with_context(|| {
+                format!("Failed to read audit public key: {}", public_key.display())
+            })?;
+            let key = AuditPublicKey::from_bytes(&bytes)?;
+            let file = std::fs::File::open(&log)
+                .with_context(|| format!("Failed to open audit log: {}", log.display()))?;
+            let summary = verify_log(std::io::BufReader::new(file), &key, |receipt| {
+                if receipts {
+                    println!(
+                        "{}",
+                        serde_json::to_string(receipt).expect("receipts serialize to JSON")
+                    );
+                }
+            })
+            .with_context(|| format!("Audit log {} failed verification", log.display()))?;
+            println!(
+                "{} receipts verified, head {}",
+                summary.len,
+                hex::encode(summary.head)
+            );
+        }
+        RegimeACommand::Submit {
+            client_key,
+            image,
+            endpoint,
+            client_id,
+            msgid,
+        } => {
+            let mut rng: RegimeARng = ark_std::rand::SeedableRng::from_entropy();
+            let client_id = match client_id {
+                Some(id) => {
+                    let mut bytes = [0u8; 32];
+                    hex::decode_to_slice(&id, &mut bytes)
+                        .with_context(|| format!("Invalid client id: {}", id))?;
+                    ClientId(bytes)
+                }
+                None => {
+                    let mut bytes = [0u8; 32];
+                    rng.fill_bytes(&mut bytes);
+                    ClientId(bytes)
+                }
+            };
+            let msgid = msgid.unwrap_or_else(|| rng.next_u64());
+
+            let hashes = image
+                .iter()
+                .map(|path| {
+                    let img = ImageReader::open(path)
+                        .with_context(|| format!("Failed to open image: {}", path.display()))?
+                        .decode()
+                        .with_context(|| format!("Failed to decode image: {}", path.display()))?;
+                    let (hash, _quality) =
+                        generate_pdq(&img).with_context(|| "Failed to generate PDQ hash")?;
+                    Ok(hash)
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            let bytes = std::fs::read(&client_key)
+                .with_context(|| format!("Failed to read client key: {}", client_key.display()))?;
+            let key = ClientKey::from_bytes(&bytes)?;
+            let layout =
+                PdqLayout::for_params(&key.params).context("Client key is not for PDQ hashes")?;
+
+            let msgids = (0..hashes.len() as u64)
+                .map(|i| msgid.checked_add(i))
+                .collect::<Option<Vec<_>>>()
+                .with_context(|| {
+                    format!(
+                        "Message ids from {} overflow for {} images",
+                        msgid,
+                        hashes.len()
+                    )
+                })?;
+            let submissions = hashes.iter().zip(msgids).map(|(hash, msgid)| {
+                info!("Proving submission {}", msgid);
+                client_submit(
+                    &key,
+                    layout.hash_to_bits(hash),
+                    SubmissionHeader::now(client_id, msgid),
+                    &mut rng,
+                )
+            });
+            if let Some(addr) = endpoint.tcp {
+                let stream = std::net::TcpStream::connect(&addr)
+                    .with_context(|| format!("Failed to connect to {}", addr))?;
+                submit_all(stream, submissions)?;
+            } else {
+                #[cfg(unix)]
+                {
+                    let path = endpoint.unix.expect("clap requires an endpoint");
+                    let stream = std::os::unix::net::UnixStream::connect(&path)
+                        .with_context(|| format!("Failed to connect to {}", path.display()))?;
+                    submit_all(stream, submissions)?;
+                }
+                #[cfg(not(unix))]
+                anyhow::bail!("unix sockets unsupported on this platform");
+            }
+        }
+        RegimeACommand::Simulate {
+            images,
+            unrelated,
+            perturbation,
+            layout,
+            dihedral,
+            in_clear,
+            json,
+        } => {
+            let mut originals = load_images(&images)?;
+            let unrelated = match unrelated {
+                Some(dir) => load_images(&dir)?,
+                None => originals.split_off(originals.len().div_ceil(2)),
+            };
+            anyhow::ensure!(!originals.is_empty(), "No images in {}", images.display());
+            let simulation = Simulation {
+                layout: match layout {
+                    PdqLayoutArg::Rows => PdqLayout::rows(),
+                    PdqLayoutArg::Frequency => PdqLayout::frequency(),
+                },
+                perturbations: if perturbation.is_empty() {
+                    Perturbation::standard()
+                } else {
+                    perturbation
+                },
+                dihedral,
+                in_clear,
+            };
+
+            info!(
+                "Simulating {} originals and {} unrelated images",
+                originals.len(),
+                unrelated.len()
+            );
+            let report = simulation.run(&originals, &unrelated, &mut OsRng)?;
+            if json {
+                let rows = report
+                    .perturbations
+                    .iter()
+                    .map(|row| {
+                        serde_json::json!({
+                            "perturbation": row.perturbation.to_string(),
+                            "true_positives": row.true_positives,
+                            "positives": row.positives,
+                            "tpr": row.true_positive_rate(),
+                            "false_positives": row.false_positives,
+                            "negatives": row.negatives,
+                            "fpr": row.false_positive_rate(),
+                            "skipped": row.skipped,
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                let report = serde_json::json!({
+                    "database_entries": report.database_entries,
+                    "predicted_fpr": report.predicted_false_positive_rate,
+                    "perturbations": rows,
+                });
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                print!("{}", report);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Decode every image in `dir`, in file name order, skipping other files.
+#[cfg(feature = "regime-a")]
+fn load_images(dir: &std::path::Path) -> anyhow::Result<Vec<image::DynamicImage>> {
+    let mut paths = std::fs::read_dir(dir)
+        .with_context(|| format!("Failed to read image directory: {}", dir.display()))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.is_file())
+        .collect::<Vec<_>>();
+    paths.sort();
+
+    let mut images = Vec::with_capacity(paths.len());
+    for path in paths {
+        match ImageReader::open(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|reader| {
+                reader
+                    .with_guessed_format()?
+                    .decode()
+                    .map_err(anyhow::Error::from)
+            }) {
+            Ok(image) => images.push(image),
+            Err(e) => log::warn!("skipping {}: {:#}", path.display(), e),
+        }
+    }
+    Ok(images)
+}
+
+/// Send each submission as soon as it is proven and print the decisions.
+#[cfg(feature = "regime-a")]
+fn submit_all<S: std::io::Read + std::io::Write>(
+    mut stream: S,
+    submissions: impl Iterator<Item = ClientSubmission>,
+) -> anyhow::Result<()> {
+    let mut rejected = 0;
+    for submission in submissions {
+        match net::submit(&mut stream, &submission) {
+            Ok(decision) => println!("{:?}", decision),
+            Err(net::NetError::Rejected(reason)) => {
+                println!("Rejected: {}", reason);
+                rejected += 1;
+            }
+            Err(e) => return Err(e.into()),
+        }
     }
+    anyhow::ensure!(rejected == 0, "{} submissions were rejected", rejected);
     Ok(())
 }
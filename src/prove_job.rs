@@ -0,0 +1,381 @@
+//! Detached proving jobs for SNARK worker pools.
+//!
+//! A [`ProveJob`] bundles everything a worker needs to produce a PDQ proof
+//! without access to the original upload: the [`PdqWitness`], the identifier
+//! of the proving key it must be proven under and the hash the submitter
+//! expects. Jobs are identified by a hash of their encoding, so resubmitting
+//! or reprocessing a job is idempotent.
+//!
+//! ## Wire format
+//!
+//! ```text
+//! magic        6 bytes   "PDQJOB"
+//! version      1 byte    JOB_FORMAT_VERSION
+//! key_id      32 bytes   BLAKE3 of the compressed verifying key
+//! target_hash 32 bytes   expected PDQ hash
+//! witness      ...       PdqWitness, ark-serialize compressed
+//! ```
+//!
+//! Workers write `<job id>.inputs` (the public inputs) and then
+//! `<job id>.proof` (the Groth16 proof), both ark-serialize compressed, so the
+//! outputs can be checked directly with `pdqhash verify`. A job whose proof
+//! file already exists is not proven again.
+
+use crate::dwn_pdq::PDQ_HASH_LENGTH;
+use crate::snark::{PDQSnark, PdqWitness};
+use anyhow::{anyhow, Context};
+use ark_bls12_381::Bls12_381;
+use ark_groth16::Proof;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use ark_std::rand::{CryptoRng, RngCore};
+use std::fmt;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Leading bytes of every encoded job.
+const JOB_MAGIC: &[u8; 6] = b"PDQJOB";
+/// Current version of the job encoding.
+pub const JOB_FORMAT_VERSION: u8 = 1;
+/// File extension for jobs picked up from a directory.
+pub const JOB_EXTENSION: &str = "job";
+/// BLAKE3 key-derivation context for job identifiers.
+const JOB_ID_CONTEXT: &str = "pdqhash 2024 prove-job id";
+
+/// Content-derived identifier of a [`ProveJob`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct JobId(pub [u8; 32]);
+
+impl fmt::Display for JobId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.0 {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+/// A self-describing request to prove a PDQ hash.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProveJob {
+    /// Identifier of the key pair the proof must be produced under.
+    pub key_id: [u8; 32],
+    /// Hash the submitter expects the proof to attest to.
+    pub target_hash: [u8; PDQ_HASH_LENGTH],
+    /// Private witness to prove.
+    pub witness: PdqWitness,
+}
+
+impl ProveJob {
+    /// Create a job proving the witness's own hash under `key_id`.
+    pub fn new(key_id: [u8; 32], witness: PdqWitness) -> Self {
+        Self {
+            key_id,
+            target_hash: witness.hash(),
+            witness,
+        }
+    }
+
+    /// Encode the job in the versioned wire format.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(JOB_MAGIC);
+        bytes.push(JOB_FORMAT_VERSION);
+        bytes.extend_from_slice(&self.key_id);
+        bytes.extend_from_slice(&self.target_hash);
+        self.witness
+            .serialize_compressed(&mut bytes)
+            .expect("serializing into a Vec cannot fail");
+        bytes
+    }
+
+    /// Decode one job from `reader`, leaving any trailing bytes unread.
+    pub fn read_from<R: Read>(mut reader: R) -> anyhow::Result<Self> {
+        let mut magic = [0u8; JOB_MAGIC.len()];
+        reader
+            .read_exact(&mut magic)
+            .context("truncated job header")?;
+        if &magic != JOB_MAGIC {
+            return Err(anyhow!("not a PDQ prove job"));
+        }
+        let mut version = [0u8; 1];
+        reader.read_exact(&mut version)?;
+        if version[0] != JOB_FORMAT_VERSION {
+            return Err(anyhow!("unsupported job format version {}", version[0]));
+        }
+        let mut key_id = [0u8; 32];
+        reader.read_exact(&mut key_id)?;
+        let mut target_hash = [0u8; PDQ_HASH_LENGTH];
+        reader.read_exact(&mut target_hash)?;
+        let witness = PdqWitness::deserialize_compressed(&mut reader)
+            .map_err(|e| anyhow!("invalid job witness: {}", e))?;
+
+        Ok(Self {
+            key_id,
+            target_hash,
+            witness,
+        })
+    }
+
+    /// Decode a job that occupies all of `bytes`.
+    pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        let mut reader = bytes;
+        let job = Self::read_from(&mut reader)?;
+        if !reader.is_empty() {
+            return Err(anyhow!("{} trailing bytes after job", reader.len()));
+        }
+        Ok(job)
+    }
+
+    /// Identifier derived from the job's encoding.
+    pub fn id(&self) -> JobId {
+        JobId(blake3::derive_key(JOB_ID_CONTEXT, &self.to_bytes()))
+    }
+
+    /// Prove the job, checking it targets this key pair and hash.
+    pub fn prove<R: RngCore + CryptoRng>(
+        &self,
+        snark: &PDQSnark,
+        rng: &mut R,
+    ) -> anyhow::Result<(Proof<Bls12_381>, Vec<ark_bls12_381::Fr>)> {
+        if self.key_id != snark.key_id() {
+            return Err(anyhow!("job was issued for a different proving key"));
+        }
+        if self.target_hash != self.witness.hash() {
+            return Err(anyhow!("witness does not hash to the job's target hash"));
+        }
+        snark.prove_witness(&self.witness, rng)
+    }
+}
+
+/// Outcome of handing a job to a worker.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JobStatus {
+    /// A proof was generated and written.
+    Proved,
+    /// A proof for this job was already present; nothing was done.
+    AlreadyProved,
+}
+
+/// Directory where a worker writes its proofs and public inputs.
+#[derive(Clone, Debug)]
+pub struct JobOutputs {
+    dir: PathBuf,
+}
+
+impl JobOutputs {
+    /// Use `dir` (created if missing) for job outputs.
+    pub fn new(dir: impl Into<PathBuf>) -> anyhow::Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("failed to create output directory {}", dir.display()))?;
+        Ok(Self { dir })
+    }
+
+    /// Path of the proof written for `id`.
+    pub fn proof_path(&self, id: JobId) -> PathBuf {
+        self.dir.join(format!("{}.proof", id))
+    }
+
+    /// Path of the public inputs written for `id`.
+    pub fn inputs_path(&self, id: JobId) -> PathBuf {
+        self.dir.join(format!("{}.inputs", id))
+    }
+
+    /// Whether `id` has already been proven. The proof is written last, so
+    /// its presence means both files are complete.
+    pub fn is_done(&self, id: JobId) -> bool {
+        self.proof_path(id).exists()
+    }
+
+    /// Prove `job` unless its outputs already exist.
+    pub fn run<R: RngCore + CryptoRng>(
+        &self,
+        snark: &PDQSnark,
+        job: &ProveJob,
+        rng: &mut R,
+    ) -> anyhow::Result<(JobId, JobStatus)> {
+        let id = job.id();
+        if self.is_done(id) {
+            log::debug!("job {} already proved", id);
+            return Ok((id, JobStatus::AlreadyProved));
+        }
+
+        log::info!("proving job {}", id);
+        let (proof, public_inputs) = job.prove(snark, rng)?;
+
+        let mut inputs_bytes = Vec::new();
+        public_inputs.serialize_compressed(&mut inputs_bytes)?;
+        write_atomically(&self.inputs_path(id), &inputs_bytes)?;
+
+        let mut proof_bytes = Vec::new();
+        proof.serialize_compressed(&mut proof_bytes)?;
+        write_atomically(&self.proof_path(id), &proof_bytes)?;
+
+        Ok((id, JobStatus::Proved))
+    }
+
+    /// Prove every `*.job` file in `jobs_dir`, in file-name order.
+    ///
+    /// A job that fails is logged and skipped so one bad upload does not stall
+    /// the worker; several workers may share a directory, at worst proving the
+    /// same job twice.
+    pub fn run_directory<R: RngCore + CryptoRng>(
+        &self,
+        snark: &PDQSnark,
+        jobs_dir: &Path,
+        rng: &mut R,
+    ) -> anyhow::Result<Vec<(JobId, JobStatus)>> {
+        let mut paths = std::fs::read_dir(jobs_dir)
+            .with_context(|| format!("failed to read job directory {}", jobs_dir.display()))?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == JOB_EXTENSION))
+            .collect::<Vec<_>>();
+        paths.sort();
+
+        let mut results = Vec::with_capacity(paths.len());
+        for path in paths {
+            let outcome = std::fs::read(&path)
+                .map_err(anyhow::Error::from)
+                .and_then(|bytes| ProveJob::from_bytes(&bytes))
+                .and_then(|job| self.run(snark, &job, rng));
+            match outcome {
+                Ok(result) => results.push(result),
+                Err(e) => log::error!("skipping job {}: {:#}", path.display(), e),
+            }
+        }
+        Ok(results)
+    }
+
+    /// Prove a concatenation of encoded jobs read from `reader` until EOF.
+    ///
+    /// Jobs are decoded and proved one at a time. A job that fails to prove
+    /// is logged and skipped like in [`run_directory`](Self::run_directory);
+    /// one that fails to decode ends the stream, since the next job's start is
+    /// then unknown.
+    pub fn run_stream<R: RngCore + CryptoRng, S: Read>(
+        &self,
+        snark: &PDQSnark,
+        reader: S,
+        rng: &mut R,
+    ) -> anyhow::Result<Vec<(JobId, JobStatus)>> {
+        let mut reader = BufReader::new(reader);
+        let mut results = Vec::new();
+        while !reader.fill_buf()?.is_empty() {
+            let job = match ProveJob::read_from(&mut reader) {
+                Ok(job) => job,
+                Err(e) => {
+                    log::error!("stopping job stream after {} jobs: {:#}", results.len(), e);
+                    break;
+                }
+            };
+            match self.run(snark, &job, rng) {
+                Ok(result) => results.push(result),
+                Err(e) => log::error!("skipping job {}: {:#}", job.id(), e),
+            }
+        }
+        Ok(results)
+    }
+}
+
+/// Write via a temporary file and rename, so readers never see partial output.
+///
+/// The temporary name is unique to the file, the process and the call, so
+/// workers sharing a directory never rename each other's partial output.
+fn write_atomically(path: &Path, bytes: &[u8]) -> anyhow::Result<()> {
+    static WRITES: AtomicU64 = AtomicU64::new(0);
+    let mut name = path
+        .file_name()
+        .expect("output paths name a file")
+        .to_owned();
+    name.push(format!(
+        ".{}.{}.tmp",
+        std::process::id(),
+        WRITES.fetch_add(1, Ordering::Relaxed)
+    ));
+    let tmp = path.with_file_name(name);
+    std::fs::write(&tmp, bytes).with_context(|| format!("failed to write {}", tmp.display()))?;
+    std::fs::rename(&tmp, path).with_context(|| format!("failed to write {}", path.display()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_bls12_381::Fr as BlsFr;
+    use ark_std::rand::{rngs::StdRng, SeedableRng};
+
+    fn bridge_witness() -> PdqWitness {
+        let bytes = include_bytes!("test_data/bridge-1-original.jpg");
+        PdqWitness::from_image(&image::load_from_memory(bytes).unwrap())
+    }
+
+    #[test]
+    fn job_encoding_roundtrip() {
+        let job = ProveJob::new([7u8; 32], bridge_witness());
+        let bytes = job.to_bytes();
+        let decoded = ProveJob::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded, job);
+        assert_eq!(decoded.id(), job.id());
+
+        let mut other = job.clone();
+        other.key_id = [8u8; 32];
+        assert_ne!(other.id(), job.id());
+
+        let mut bad_version = bytes.clone();
+        bad_version[JOB_MAGIC.len()] = JOB_FORMAT_VERSION + 1;
+        assert!(ProveJob::from_bytes(&bad_version).is_err());
+        assert!(ProveJob::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn outputs_of_one_job_use_separate_temporaries() {
+        let dir = tempfile::tempdir().unwrap();
+        let inputs = dir.path().join("job.inputs");
+        let proof = dir.path().join("job.proof");
+        write_atomically(&inputs, b"inputs").unwrap();
+        write_atomically(&proof, b"proof").unwrap();
+        assert_eq!(std::fs::read(&inputs).unwrap(), b"inputs");
+        assert_eq!(std::fs::read(&proof).unwrap(), b"proof");
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn worker_proves_each_job_once() {
+        let mut rng = StdRng::from_seed([3u8; 32]);
+        let snark = PDQSnark::setup(&mut rng).unwrap();
+        let job = ProveJob::new(snark.key_id(), bridge_witness());
+
+        let jobs_dir = tempfile::tempdir().unwrap();
+        let out_dir = tempfile::tempdir().unwrap();
+        std::fs::write(jobs_dir.path().join("a.job"), job.to_bytes()).unwrap();
+        std::fs::write(jobs_dir.path().join("b.job"), b"garbage").unwrap();
+        let outputs = JobOutputs::new(out_dir.path()).unwrap();
+
+        let first = outputs
+            .run_directory(&snark, jobs_dir.path(), &mut rng)
+            .unwrap();
+        assert_eq!(first, vec![(job.id(), JobStatus::Proved)]);
+
+        let proof_bytes = std::fs::read(outputs.proof_path(job.id())).unwrap();
+        let inputs_bytes = std::fs::read(outputs.inputs_path(job.id())).unwrap();
+        let proof = Proof::<Bls12_381>::deserialize_compressed(&*proof_bytes).unwrap();
+        let inputs = Vec::<BlsFr>::deserialize_compressed(&*inputs_bytes).unwrap();
+        assert!(snark.verify_proof(&proof, &inputs).unwrap());
+
+        let foreign = ProveJob::new([0u8; 32], job.witness.clone());
+        assert!(foreign.prove(&snark, &mut rng).is_err());
+
+        let mut stream = foreign.to_bytes();
+        stream.extend(job.to_bytes());
+        stream.extend(b"garbage");
+        let again = outputs
+            .run_stream(&snark, stream.as_slice(), &mut rng)
+            .unwrap();
+        assert_eq!(again, vec![(job.id(), JobStatus::AlreadyProved)]);
+        assert_eq!(
+            std::fs::read(outputs.proof_path(job.id())).unwrap(),
+            proof_bytes
+        );
+    }
+}
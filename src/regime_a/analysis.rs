@@ -0,0 +1,260 @@
+//! Error rates of Regime A parameters, and choosing parameters for a target
+//! Hamming threshold.
+//!
+//! The server answers Yes exactly when some chunk `b` of the query lies within
+//! distance `epsilon_b - 1` of the same chunk of some database entry, except
+//! when the random weights `gamma_ib` make the near terms cancel in `Fr`.
+//! Each entry has its own weight in each chunk and every near term is a
+//! nonzero multiple of its weight (see [`scores`](super::scores)), so by
+//! Schwartz–Zippel that happens with probability at most `1 / |Fr|`, and
+//! only turns a Yes into a No.
+//!
+//! A global Hamming threshold `T` instead matches any entry within `T` bits.
+//! The two rules disagree when the differing bits of a close pair are spread
+//! so that every chunk `b` has at least `epsilon_b` of them, or when a far or
+//! unrelated entry happens to agree on one chunk. The probabilities below
+//! assume the differing positions of a pair are uniformly distributed, and
+//! unrelated hashes uniformly random.
+
+use super::{ChunkParams, RegimeAParams, MAX_CHUNK_BITS};
+use ark_ed_on_bls12_381::Fr;
+use ark_ff::PrimeField;
+
+/// Error rates of a parameter set against a global Hamming threshold.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Analysis {
+    /// `match_probability[t]` is the probability of a Yes against a single
+    /// entry at Hamming distance `t`, for `t = 0..=lambda`.
+    pub match_probability: Vec<f64>,
+    /// Largest probability of a No for a query within the threshold of some
+    /// entry, including weight cancellation.
+    pub false_negative: f64,
+    /// Probability of a Yes for a random query against `db_size` unrelated
+    /// entries.
+    pub false_positive: f64,
+    /// Upper bound on the probability that weights cancel.
+    pub cancellation: f64,
+}
+
+/// `n choose k` as an `f64` approximation; it stops being exact once the
+/// products exceed 2^53.
+fn binomial(n: usize, k: usize) -> f64 {
+    if k > n {
+        return 0.0;
+    }
+    (0..k.min(n - k)).fold(1.0, |acc, i| acc * (n - i) as f64 / (i + 1) as f64)
+}
+
+impl RegimeAParams {
+    /// Probability that chunk `b` of a uniformly random query lies within
+    /// distance `epsilon_b - 1` of a fixed chunk.
+    fn chunk_match_probability(&self, b: usize) -> f64 {
+        let ChunkParams { len, epsilon } = self.chunks[b];
+        let near = (0..epsilon).map(|k| binomial(len, k)).sum::<f64>();
+        near / (1u64 << len) as f64
+    }
+
+    /// Error rates against `db_size` entries and a global Hamming
+    /// `threshold`.
+    pub fn analyze(&self, db_size: usize, threshold: usize) -> Analysis {
+        let lambda = self.lambda();
+
+        // Coefficient t of prod_b (sum_{k >= eps_b} C(len_b, k) x^k) counts the
+        // ways to place t differing bits with every chunk at distance >= eps_b.
+        let mut all_far = vec![1.0];
+        for &ChunkParams { len, epsilon } in &self.chunks {
+            let mut next = vec![0.0; all_far.len() + len];
+            for (t, ways) in all_far.iter().enumerate() {
+                for k in epsilon..=len {
+                    next[t + k] += ways * binomial(len, k);
+                }
+            }
+            all_far = next;
+        }
+        let match_probability = (0..=lambda)
+            .map(|t| 1.0 - all_far[t] / binomial(lambda, t))
+            .collect::<Vec<_>>();
+
+        let cancellation = 2f64.powi(1 - Fr::MODULUS_BIT_SIZE as i32);
+        let false_negative = match_probability[..=threshold.min(lambda)]
+            .iter()
+            .map(|p| 1.0 - p)
+            .fold(0.0, f64::max)
+            + cancellation;
+
+        let entry_miss = (0..self.b_chunks)
+            .map(|b| 1.0 - self.chunk_match_probability(b))
+            .product::<f64>();
+        let false_positive = 1.0 - entry_miss.powf(db_size as f64);
+
+        Analysis {
+            match_probability,
+            false_negative: false_negative.min(1.0),
+            false_positive,
+            cancellation,
+        }
+    }
+
+    /// Parameters for `lambda`-bit hashes that best approximate a global
+    /// Hamming `threshold` against `db_size` entries.
+    ///
+    /// Among chunkings with a false-positive rate of at most
+    /// `max_false_positive`, picks the lowest false-negative rate, breaking
+    /// ties by the smallest tables. Returns None if no chunking qualifies.
+    pub fn select(
+        lambda: usize,
+        threshold: usize,
+        db_size: usize,
+        max_false_positive: f64,
+    ) -> Option<Self> {
+        (1..=MAX_CHUNK_BITS.min(lambda))
+            .filter(|ell| lambda.is_multiple_of(*ell))
+            .flat_map(|ell| (1..=ell).map(move |epsilon| Self::new(ell, lambda / ell, epsilon)))
+            .map(|params| {
+                let analysis = params.analyze(db_size, threshold);
+                (params, analysis)
+            })
+            .filter(|(_, analysis)| analysis.false_positive <= max_false_positive)
+            .min_by(|(a, a_analysis), (b, b_analysis)| {
+                a_analysis
+                    .false_negative
+                    .total_cmp(&b_analysis.false_negative)
+                    .then((a.b_chunks << a.ell).cmp(&(b.b_chunks << b.ell)))
+            })
+            .map(|(params, _)| params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::regime_a::{ServerDecision, TtpSecret};
+    use ark_ff::Zero;
+    use ark_std::rand::{rngs::StdRng, Rng, SeedableRng};
+    use ark_std::UniformRand;
+
+    const TRIALS: usize = 4000;
+
+    /// The server's zero test evaluated directly on the unmasked sum.
+    fn decide(secret: &TtpSecret, query: &[u8]) -> ServerDecision {
+        secret
+            .params
+            .decide_in_clear(&secret.db, &secret.gamma, query)
+    }
+
+    fn random_secret(params: &RegimeAParams, db_size: usize, rng: &mut StdRng) -> TtpSecret {
+        TtpSecret {
+            params: params.clone(),
+            db: (0..db_size)
+                .map(|_| (0..params.lambda()).map(|_| rng.gen_range(0..2)).collect())
+                .collect(),
+            gamma: (0..db_size)
+                .map(|_| (0..params.b_chunks).map(|_| Fr::rand(rng)).collect())
+                .collect(),
+            r_masks: vec![Fr::zero(); params.b_chunks],
+        }
+    }
+
+    /// Whether `observed` successes in `TRIALS` are consistent with `p`.
+    fn assert_close(observed: usize, p: f64) {
+        let expected = p * TRIALS as f64;
+        let sigma = (TRIALS as f64 * p * (1.0 - p)).sqrt().max(1.0);
+        assert!(
+            (observed as f64 - expected).abs() <= 5.0 * sigma,
+            "observed {observed}, expected {expected:.1}"
+        );
+    }
+
+    #[test]
+    fn analysis_matches_simple_cases() {
+        let params = RegimeAParams::new(4, 4, 2);
+        let analysis = params.analyze(1, 7);
+        assert_eq!(analysis.match_probability[0], 1.0);
+        // Seven differing bits cannot put two in each of four chunks.
+        assert_eq!(analysis.match_probability[7], 1.0);
+        assert!(analysis.match_probability[8] < 1.0);
+        assert!(analysis.false_negative < 1e-70);
+        let chunk = 5.0 / 16.0;
+        let expected_fp = 1.0 - (1.0f64 - chunk).powi(4);
+        assert!((analysis.false_positive - expected_fp).abs() < 1e-12);
+    }
+
+    #[test]
+    fn analysis_handles_mixed_chunks() {
+        let params = RegimeAParams::with_chunks(vec![
+            ChunkParams { len: 3, epsilon: 2 },
+            ChunkParams { len: 5, epsilon: 3 },
+        ]);
+        let analysis = params.analyze(1, 4);
+        // Five differing bits are needed to reach both thresholds.
+        assert_eq!(analysis.match_probability[4], 1.0);
+        // Of the C(8, 5) placements, only two in the first chunk and three
+        // in the second miss.
+        let expected = 1.0 - 3.0 * 10.0 / 56.0;
+        assert!((analysis.match_probability[5] - expected).abs() < 1e-12);
+        // Chunk match probabilities are 4/8 and 16/32.
+        assert!((analysis.false_positive - 0.75).abs() < 1e-12);
+
+        // The server agrees: every query within four bits is a Yes.
+        let mut rng = StdRng::seed_from_u64(3);
+        let secret = random_secret(&params, 1, &mut rng);
+        for flips in 0..1u32 << 8 {
+            let query = (0..8)
+                .map(|i| secret.db[0][i] ^ (flips >> i & 1) as u8)
+                .collect::<Vec<_>>();
+            if flips.count_ones() <= 4 {
+                assert_eq!(decide(&secret, &query), ServerDecision::Yes);
+            }
+        }
+    }
+
+    #[test]
+    fn monte_carlo_match_probability() {
+        let params = RegimeAParams::new(4, 4, 2);
+        let analysis = params.analyze(1, 0);
+        let mut rng = StdRng::seed_from_u64(1);
+        for t in [6, 8, 10, 12] {
+            let mut matches = 0;
+            for _ in 0..TRIALS {
+                let secret = random_secret(&params, 1, &mut rng);
+                let mut query = secret.db[0].clone();
+                // Partial Fisher–Yates: flip `t` distinct random positions.
+                let mut positions = (0..params.lambda()).collect::<Vec<_>>();
+                for i in 0..t {
+                    positions.swap(i, rng.gen_range(i..params.lambda()));
+                    query[positions[i]] ^= 1;
+                }
+                matches += (decide(&secret, &query) == ServerDecision::Yes) as usize;
+            }
+            assert_close(matches, analysis.match_probability[t]);
+        }
+    }
+
+    #[test]
+    fn monte_carlo_false_positive() {
+        let params = RegimeAParams::new(8, 2, 2);
+        let db_size = 5;
+        let analysis = params.analyze(db_size, 0);
+        let mut rng = StdRng::seed_from_u64(2);
+        let mut matches = 0;
+        for _ in 0..TRIALS {
+            let secret = random_secret(&params, db_size, &mut rng);
+            let query = (0..params.lambda())
+                .map(|_| rng.gen_range(0..2))
+                .collect::<Vec<_>>();
+            matches += (decide(&secret, &query) == ServerDecision::Yes) as usize;
+        }
+        assert_close(matches, analysis.false_positive);
+    }
+
+    #[test]
+    fn selector_recovers_pdq_parameters() {
+        assert_eq!(
+            RegimeAParams::select(256, 31, 1, 0.01),
+            Some(RegimeAParams::pdq())
+        );
+        let params = RegimeAParams::select(64, 5, 100, 0.05).unwrap();
+        assert!(params.analyze(100, 5).false_positive <= 0.05);
+        assert!(RegimeAParams::select(256, 31, 1, 0.0).is_none());
+    }
+}
@@ -0,0 +1,635 @@
+//! Signed, hash-chained receipts for Regime A decisions.
+//!
+//! A server with an [`AuditLog`] writes one [`Receipt`] for every decision it
+//! returns. A receipt names the submission by its
+//! [`transcript_hash`](ClientSubmission::transcript_hash), the database epoch
+//! it was checked against, the decision and the time it was made, and carries
+//! the digest of the receipt before it. Each receipt is signed with the
+//! server's [`AuditKey`], a Schnorr key on Jubjub, so anyone holding the
+//! [`AuditPublicKey`] can check a log offline with [`verify_log`]: a receipt
+//! that was altered, dropped, reordered or inserted breaks either a
+//! signature or the chain. Truncating the log is only caught by comparing its
+//! head against a digest recorded elsewhere.
+//!
+//! The log file is a sequence of frames `u32_le(len) ‖ Receipt wire encoding`,
+//! as used by [`net`](super::net).
+
+use super::net::{read_frame, write_frame, NetError};
+use super::transcript::Transcript;
+use super::wire::Wire;
+use super::{ClientId, ClientSubmission, ServerDecision};
+use ark_ec::{AffineRepr, CurveGroup};
+use ark_ed_on_bls12_381::{EdwardsAffine, Fr};
+use ark_ff::Zero;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use ark_std::rand::{CryptoRng, RngCore};
+use ark_std::UniformRand;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read};
+use std::path::Path;
+
+/// Digest the first receipt of a log chains to.
+pub const GENESIS: [u8; 32] = [0; 32];
+
+/// Why a log could not be read, written or verified.
+#[derive(Debug, thiserror::Error)]
+pub enum AuditError {
+    /// The log file could not be opened or written.
+    #[error("i/o error: {0}")]
+    Io(#[from] io::Error),
+    /// A record was truncated or did not hold a receipt.
+    #[error("bad record: {0}")]
+    Record(#[from] NetError),
+    /// A receipt is out of sequence.
+    #[error("expected receipt {expected}, found {found}")]
+    BadSequence {
+        /// Sequence number that should come next.
+        expected: u64,
+        /// Sequence number of the receipt found.
+        found: u64,
+    },
+    /// A receipt does not chain to the one before it.
+    #[error("receipt {0} does not follow the previous receipt")]
+    BrokenChain(u64),
+    /// A receipt was not signed by the expected key.
+    #[error("receipt {0} has an invalid signature")]
+    BadSignature(u64),
+    /// The log does not end in whole receipts or the start of one.
+    #[error("{0} trailing bytes do not start a receipt")]
+    BadTail(usize),
+    /// An append failed and could not be undone, so the log takes no more
+    /// receipts.
+    #[error("a failed append could not be undone")]
+    Poisoned,
+}
+
+/// Secret key a server signs its receipts with. Its `Debug` output shows
+/// only the public key.
+#[derive(Clone, PartialEq, CanonicalSerialize, CanonicalDeserialize)]
+pub struct AuditKey {
+    pub(super) secret: Fr,
+}
+
+impl fmt::Debug for AuditKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AuditKey")
+            .field("public_key", &self.public_key())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Public key receipts are checked against.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, CanonicalSerialize, CanonicalDeserialize)]
+pub struct AuditPublicKey(pub EdwardsAffine);
+
+/// Schnorr signature `(R, s)` with `s·G = R + c·P`.
+#[derive(Clone, Debug, PartialEq, Eq, CanonicalSerialize, CanonicalDeserialize)]
+pub struct Signature {
+    r: EdwardsAffine,
+    s: Fr,
+}
+
+impl AuditKey {
+    /// Fresh random key.
+    pub fn generate<R: RngCore + CryptoRng>(rng: &mut R) -> Self {
+        loop {
+            let secret = Fr::rand(rng);
+            if !secret.is_zero() {
+                return Self { secret };
+            }
+        }
+    }
+
+    /// The matching public key.
+    pub fn public_key(&self) -> AuditPublicKey {
+        AuditPublicKey((EdwardsAffine::generator() * self.secret).into_affine())
+    }
+
+    /// Sign the 32-byte `digest`. The nonce is derived from the key and the
+    /// digest, so signing needs no randomness.
+    pub fn sign(&self, digest: &[u8; 32]) -> Signature {
+        let mut nonce = Transcript::new(b"regime-a receipt nonce");
+        nonce.append_serialized(b"secret", &self.secret);
+        nonce.append_message(b"digest", digest);
+        let k = nonce.challenge_field::<Fr>(b"k");
+        let r = (EdwardsAffine::generator() * k).into_affine();
+        let c = challenge(&self.public_key(), &r, digest);
+        Signature {
+            r,
+            s: k + c * self.secret,
+        }
+    }
+}
+
+impl AuditPublicKey {
+    /// Whether `signature` is a signature on `digest` under this key.
+    pub fn verify(&self, digest: &[u8; 32], signature: &Signature) -> bool {
+        let c = challenge(self, &signature.r, digest);
+        EdwardsAffine::generator() * signature.s == signature.r + self.0 * c
+    }
+}
+
+fn challenge(public: &AuditPublicKey, r: &EdwardsAffine, digest: &[u8; 32]) -> Fr {
+    let mut transcript = Transcript::new(b"regime-a receipt signature");
+    transcript.append_serialized(b"public_key", &public.0);
+    transcript.append_serialized(b"r", r);
+    transcript.append_message(b"digest", digest);
+    transcript.challenge_field(b"c")
+}
+
+/// Signed record of one decision.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Receipt {
+    /// Position in the log, from 0.
+    pub seq: u64,
+    /// Digest of the previous receipt, or [`GENESIS`].
+    pub prev: [u8; 32],
+    /// Client the submission was attributed to.
+    pub client_id: ClientId,
+    /// Message id of the submission.
+    pub msgid: u64,
+    /// [`ClientSubmission::transcript_hash`] of the submission.
+    pub submission: [u8; 32],
+    /// Database epoch the submission was checked against.
+    pub epoch: u64,
+    /// The decision returned.
+    pub decision: ServerDecision,
+    /// Unix time in seconds at which the decision was made.
+    pub timestamp: u64,
+    /// Signature on [`digest`](Self::digest).
+    pub signature: Signature,
+}
+
+impl Receipt {
+    /// Digest of every field but the signature; the next receipt chains to
+    /// it.
+    pub fn digest(&self) -> [u8; 32] {
+        let mut transcript = Transcript::new(b"regime-a receipt");
+        transcript.append_u64(b"seq", self.seq);
+        transcript.append_message(b"prev", &self.prev);
+        transcript.append_message(b"client_id", &self.client_id.0);
+        transcript.append_u64(b"msgid", self.msgid);
+        transcript.append_message(b"submission", &self.submission);
+        transcript.append_u64(b"epoch", self.epoch);
+        transcript.append_serialized(b"decision", &self.decision);
+        transcript.append_u64(b"timestamp", self.timestamp);
+        let mut digest = [0u8; 32];
+        transcript.challenge_bytes(b"receipt", &mut digest);
+        digest
+    }
+
+    /// Whether this receipt records `submission`.
+    pub fn covers(&self, submission: &ClientSubmission) -> bool {
+        self.submission == submission.transcript_hash()
+    }
+}
+
+/// Length and head of a verified log.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LogSummary {
+    /// Number of receipts.
+    pub len: u64,
+    /// Digest of the last receipt, or [`GENESIS`] for an empty log.
+    pub head: [u8; 32],
+}
+
+/// Check every receipt in `log` against `key` and the chain, calling `each`
+/// on them in order.
+pub fn verify_log<R: Read>(
+    mut log: R,
+    key: &AuditPublicKey,
+    mut each: impl FnMut(&Receipt),
+) -> Result<LogSummary, AuditError> {
+    let mut summary = LogSummary {
+        len: 0,
+        head: GENESIS,
+    };
+    while let Some(frame) = read_frame(&mut log)? {
+        let receipt = Receipt::from_bytes(&frame).map_err(NetError::from)?;
+        if receipt.seq != summary.len {
+            return Err(AuditError::BadSequence {
+                expected: summary.len,
+                found: receipt.seq,
+            });
+        }
+        if receipt.prev != summary.head {
+            return Err(AuditError::BrokenChain(receipt.seq));
+        }
+        let digest = receipt.digest();
+        if !key.verify(&digest, &receipt.signature) {
+            return Err(AuditError::BadSignature(receipt.seq));
+        }
+        each(&receipt);
+        summary = LogSummary {
+            len: summary.len + 1,
+            head: digest,
+        };
+    }
+    Ok(summary)
+}
+
+/// Length of every receipt frame; receipts have no variable-size fields.
+fn receipt_frame_len() -> usize {
+    let receipt = Receipt {
+        seq: 0,
+        prev: GENESIS,
+        client_id: ClientId([0; 32]),
+        msgid: 0,
+        submission: [0; 32],
+        epoch: 0,
+        decision: ServerDecision::No,
+        timestamp: 0,
+        signature: Signature {
+            r: EdwardsAffine::zero(),
+            s: Fr::zero(),
+        },
+    };
+    4 + receipt.to_bytes().len()
+}
+
+/// File a log appends to, so tests can make appends fail.
+trait LogFile: io::Write {
+    fn len(&self) -> io::Result<u64>;
+    fn set_len(&mut self, len: u64) -> io::Result<()>;
+    fn sync_data(&self) -> io::Result<()>;
+}
+
+impl LogFile for File {
+    fn len(&self) -> io::Result<u64> {
+        Ok(self.metadata()?.len())
+    }
+
+    fn set_len(&mut self, len: u64) -> io::Result<()> {
+        File::set_len(self, len)
+    }
+
+    fn sync_data(&self) -> io::Result<()> {
+        File::sync_data(self)
+    }
+}
+
+/// Append `payload` as one frame and sync it. If that fails, the file is cut
+/// back to its old length; the error says whether that worked.
+fn append_frame<F: LogFile>(file: &mut F, payload: &[u8]) -> Result<(), (AuditError, bool)> {
+    let start = file.len().map_err(|e| (e.into(), true))?;
+    let appended = write_frame(&mut *file, payload)
+        .map_err(AuditError::from)
+        .and_then(|()| Ok(file.sync_data()?));
+    appended.map_err(|e| {
+        // The next append that syncs makes the cut durable.
+        let undone = file.set_len(start).is_ok();
+        (e, undone)
+    })
+}
+
+/// Append-only log file of receipts.
+#[derive(Debug)]
+pub struct AuditLog {
+    file: File,
+    key: AuditKey,
+    summary: LogSummary,
+    poisoned: bool,
+}
+
+impl AuditLog {
+    /// Open or create the log at `path`. Existing receipts are verified
+    /// first, so a damaged log, or one signed with another key, is never
+    /// extended.
+    ///
+    /// A crash while appending can leave the log ending in part of a
+    /// receipt frame. Such a fragment never held a receipt anyone was given,
+    /// so it is cut off with a warning once the receipts before it verify.
+    /// Anything else that is not a whole receipt is an error and leaves the
+    /// file untouched.
+    pub fn open<P: AsRef<Path>>(path: P, key: AuditKey) -> Result<Self, AuditError> {
+        let path = path.as_ref();
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)?;
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+        let frame_len = receipt_frame_len();
+        let complete = bytes.len() - bytes.len() % frame_len;
+        let summary = verify_log(&bytes[..complete], &key.public_key(), |_| {})?;
+        let header = ((frame_len - 4) as u32).to_le_bytes();
+        let tail = &bytes[complete..];
+        if !header.starts_with(&tail[..tail.len().min(4)]) {
+            return Err(AuditError::BadTail(tail.len()));
+        }
+        if !tail.is_empty() {
+            log::warn!(
+                "dropping {} bytes of an incomplete receipt at the end of {}",
+                bytes.len() - complete,
+                path.display()
+            );
+            file.set_len(complete as u64)?;
+            file.sync_data()?;
+        }
+        Ok(Self {
+            file,
+            key,
+            summary,
+            poisoned: false,
+        })
+    }
+
+    /// Number of receipts and digest of the last one.
+    pub fn summary(&self) -> LogSummary {
+        self.summary
+    }
+
+    /// Sign and append the receipt for `decision` on `submission`, made at
+    /// Unix time `timestamp`. Returns once the receipt is on disk.
+    ///
+    /// A failed append is cut off again, so the receipt can be retried. If
+    /// that fails too, the log is poisoned and refuses every later receipt
+    /// rather than break its chain.
+    pub fn record(
+        &mut self,
+        submission: &ClientSubmission,
+        decision: ServerDecision,
+        timestamp: u64,
+    ) -> Result<Receipt, AuditError> {
+        if self.poisoned {
+            return Err(AuditError::Poisoned);
+        }
+        let mut receipt = Receipt {
+            seq: self.summary.len,
+            prev: self.summary.head,
+            client_id: submission.header.client_id,
+            msgid: submission.header.msgid,
+            submission: submission.transcript_hash(),
+            epoch: submission.epoch,
+            decision,
+            timestamp,
+            signature: Signature {
+                r: EdwardsAffine::zero(),
+                s: Fr::zero(),
+            },
+        };
+        let digest = receipt.digest();
+        receipt.signature = self.key.sign(&digest);
+        if let Err((e, undone)) = append_frame(&mut self.file, &receipt.to_bytes()) {
+            if !undone {
+                log::error!("audit log poisoned: a failed append could not be undone");
+                self.poisoned = true;
+            }
+            return Err(e);
+        }
+        self.summary = LogSummary {
+            len: self.summary.len + 1,
+            head: digest,
+        };
+        Ok(receipt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::regime_a::{client_submit, RegimeAParams, SubmissionHeader, TtpSetup};
+    use ark_std::rand::{rngs::StdRng, SeedableRng};
+    use std::io::Write;
+
+    #[test]
+    fn signatures_bind_key_and_digest() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let key = AuditKey::generate(&mut rng);
+        let other = AuditKey::generate(&mut rng).public_key();
+        let signature = key.sign(&[1; 32]);
+        assert!(key.public_key().verify(&[1; 32], &signature));
+        assert!(!key.public_key().verify(&[2; 32], &signature));
+        assert!(!other.verify(&[1; 32], &signature));
+    }
+
+    #[test]
+    fn debug_output_hides_the_secret() {
+        let key = AuditKey::generate(&mut StdRng::seed_from_u64(1));
+        let secret = format!("{:?}", key.secret);
+        assert!(!format!("{:?}", key).contains(&secret));
+    }
+
+    #[test]
+    fn log_chains_receipts_and_detects_tampering() {
+        let params = RegimeAParams::new(4, 2, 2);
+        let setup = TtpSetup::setup_seeded(vec![vec![0; params.lambda()]], params.clone(), 10);
+        let mut rng = StdRng::seed_from_u64(0);
+        let submissions = (1..=3)
+            .map(|msgid| {
+                let header = SubmissionHeader {
+                    client_id: ClientId([5; 32]),
+                    msgid,
+                    issued_at: 1_700_000_000,
+                };
+                client_submit(
+                    &setup.client_key,
+                    vec![0; params.lambda()],
+                    header,
+                    &mut rng,
+                )
+            })
+            .collect::<Vec<_>>();
+        let key = AuditKey::generate(&mut rng);
+        let public = key.public_key();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.log");
+
+        let mut log = AuditLog::open(&path, key.clone()).unwrap();
+        let first = log
+            .record(&submissions[0], ServerDecision::Yes, 1_700_000_001)
+            .unwrap();
+        assert!(first.covers(&submissions[0]));
+        drop(log);
+        // Reopening picks up the chain where it ended.
+        let mut log = AuditLog::open(&path, key.clone()).unwrap();
+        assert_eq!(log.summary().len, 1);
+        for submission in &submissions[1..] {
+            log.record(submission, ServerDecision::Yes, 1_700_000_002)
+                .unwrap();
+        }
+        let head = log.summary().head;
+        drop(log);
+
+        // A crash mid-append leaves part of a frame, which reopening drops.
+        let bytes = std::fs::read(&path).unwrap();
+        let frame_len = receipt_frame_len();
+        assert_eq!(bytes.len(), 3 * frame_len);
+        let append = |tail: &[u8]| {
+            let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+            file.write_all(tail).unwrap();
+        };
+        for torn in [2, 4, frame_len - 1] {
+            append(&bytes[..torn]);
+            let log = AuditLog::open(&path, key.clone()).unwrap();
+            assert_eq!(log.summary(), LogSummary { len: 3, head });
+            assert_eq!(std::fs::read(&path).unwrap(), bytes);
+        }
+
+        // Anything else is left in place for inspection.
+        append(&[7, 0]);
+        assert!(matches!(
+            AuditLog::open(&path, key.clone()),
+            Err(AuditError::BadTail(2))
+        ));
+        assert_eq!(std::fs::read(&path).unwrap().len(), bytes.len() + 2);
+        let mut damaged = bytes.clone();
+        damaged[frame_len] ^= 1;
+        std::fs::write(&path, &damaged).unwrap();
+        assert!(AuditLog::open(&path, key.clone()).is_err());
+        assert_eq!(std::fs::read(&path).unwrap(), damaged);
+        std::fs::write(&path, &bytes).unwrap();
+
+        let mut receipts = Vec::new();
+        let summary = verify_log(bytes.as_slice(), &public, |r| receipts.push(r.clone())).unwrap();
+        assert_eq!(summary, LogSummary { len: 3, head });
+        assert_eq!(receipts[0], first);
+        assert_eq!(receipts[2].prev, receipts[1].digest());
+        assert!(receipts[2].covers(&submissions[2]));
+        assert_eq!(receipts[1].epoch, 0);
+
+        let other = AuditKey::generate(&mut rng);
+        assert!(matches!(
+            verify_log(bytes.as_slice(), &other.public_key(), |_| {}),
+            Err(AuditError::BadSignature(0))
+        ));
+        assert!(AuditLog::open(&path, other).is_err());
+
+        let log_of = |receipts: &[Receipt]| {
+            let mut bytes = Vec::new();
+            for receipt in receipts {
+                write_frame(&mut bytes, &receipt.to_bytes()).unwrap();
+            }
+            verify_log(bytes.as_slice(), &public, |_| {})
+        };
+        let mut altered = receipts.clone();
+        altered[1].decision = ServerDecision::No;
+        assert!(matches!(log_of(&altered), Err(AuditError::BadSignature(1))));
+        assert!(matches!(
+            log_of(&[receipts[0].clone(), receipts[2].clone()]),
+            Err(AuditError::BadSequence {
+                expected: 1,
+                found: 2
+            })
+        ));
+        let mut resequenced = receipts[2].clone();
+        resequenced.seq = 1;
+        resequenced.signature = key.sign(&resequenced.digest());
+        assert!(matches!(
+            log_of(&[receipts[0].clone(), resequenced]),
+            Err(AuditError::BrokenChain(1))
+        ));
+        assert!(matches!(
+            verify_log(&bytes[..bytes.len() - 1], &public, |_| {}),
+            Err(AuditError::Record(NetError::Io(_)))
+        ));
+    }
+
+    /// Log file that fails after `room` bytes, or on sync or truncation.
+    #[derive(Default)]
+    struct FailingFile {
+        bytes: Vec<u8>,
+        room: usize,
+        fail_sync: bool,
+        fail_set_len: bool,
+    }
+
+    impl Write for FailingFile {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.room == 0 {
+                return Err(io::ErrorKind::StorageFull.into());
+            }
+            let n = buf.len().min(self.room);
+            self.bytes.extend_from_slice(&buf[..n]);
+            self.room -= n;
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl LogFile for FailingFile {
+        fn len(&self) -> io::Result<u64> {
+            Ok(self.bytes.len() as u64)
+        }
+
+        fn set_len(&mut self, len: u64) -> io::Result<()> {
+            if self.fail_set_len {
+                return Err(io::ErrorKind::Other.into());
+            }
+            self.bytes.truncate(len as usize);
+            Ok(())
+        }
+
+        fn sync_data(&self) -> io::Result<()> {
+            if self.fail_sync {
+                return Err(io::ErrorKind::Other.into());
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn failed_appends_are_undone_or_poison_the_log() {
+        let frame = |file: &mut FailingFile| append_frame(file, &[9; 20]);
+        let mut file = FailingFile {
+            bytes: vec![1; 24],
+            room: 30,
+            ..Default::default()
+        };
+        assert!(frame(&mut file).is_ok());
+        assert_eq!(file.bytes.len(), 48);
+        // A partial write, or a full one that does not sync, is cut back.
+        assert!(matches!(
+            frame(&mut file),
+            Err((AuditError::Record(_), true))
+        ));
+        assert_eq!(file.bytes.len(), 48);
+        file.room = 100;
+        file.fail_sync = true;
+        assert!(matches!(frame(&mut file), Err((AuditError::Io(_), true))));
+        assert_eq!(file.bytes.len(), 48);
+        file.fail_set_len = true;
+        assert!(matches!(frame(&mut file), Err((AuditError::Io(_), false))));
+
+        // A log whose file cannot be written or cut back refuses to go on.
+        let params = RegimeAParams::new(4, 2, 2);
+        let setup = TtpSetup::setup_seeded(vec![vec![0; params.lambda()]], params.clone(), 11);
+        let mut rng = StdRng::seed_from_u64(0);
+        let header = SubmissionHeader {
+            client_id: ClientId([5; 32]),
+            msgid: 1,
+            issued_at: 1_700_000_000,
+        };
+        let submission = client_submit(
+            &setup.client_key,
+            vec![0; params.lambda()],
+            header,
+            &mut rng,
+        );
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.log");
+        drop(AuditLog::open(&path, AuditKey::generate(&mut rng)).unwrap());
+        let mut log = AuditLog {
+            file: File::open(&path).unwrap(),
+            key: AuditKey::generate(&mut rng),
+            summary: LogSummary {
+                len: 0,
+                head: GENESIS,
+            },
+            poisoned: false,
+        };
+        assert!(matches!(
+            log.record(&submission, ServerDecision::No, 1_700_000_001),
+            Err(AuditError::Record(_))
+        ));
+        assert!(matches!(
+            log.record(&submission, ServerDecision::No, 1_700_000_001),
+            Err(AuditError::Poisoned)
+        ));
+    }
+}
@@ -0,0 +1,200 @@
+//! Verifying many Regime A submissions at once.
+//!
+//! [`server_verify_batch`] runs the structural checks of every submission in
+//! parallel, then checks all the Groth16 proofs that pass with a single
+//! randomized pairing equation. With random `rho_i`, every proof verifies
+//! exactly when, except with negligible probability,
+//!
+//! ```text
+//! prod_i e(rho_i A_i, B_i) · e(sum_i rho_i IC_i, -gamma) · e(sum_i rho_i C_i, -delta)
+//!     = e(alpha, beta)^(sum_i rho_i)
+//! ```
+//!
+//! which costs one Miller loop per proof plus two, and one final
+//! exponentiation, instead of three Miller loops and a final exponentiation
+//! per proof. The `rho_i` are derived from a transcript of the whole batch, so
+//! they are fixed only after every proof is. If the batch equation fails,
+//! each proof is verified on its own, in parallel, to find the bad ones.
+//!
+//! The server's work does not depend on the database: the tables already fold
+//! every entry into each chunk's lookup, so there is nothing per chunk or per
+//! entry left to spread over threads.
+
+use super::transcript::Transcript;
+use super::{
+    checked_public_inputs, zero_test, ClientSubmission, ServerDecision, ServerKey, VerifyError,
+};
+use ark_bls12_381::{Bls12_381, Fr as BlsFr, G1Projective};
+use ark_ec::pairing::Pairing;
+use ark_ec::CurveGroup;
+use ark_ff::{Field, PrimeField};
+use ark_groth16::{prepare_verifying_key, Groth16, PreparedVerifyingKey};
+use rayon::prelude::*;
+
+/// Verify and decide every submission, in order.
+///
+/// Gives the same results as calling
+/// [`server_verify_and_decide`](super::server_verify_and_decide) on each.
+pub fn server_verify_batch(
+    key: &ServerKey,
+    submissions: &[ClientSubmission],
+) -> Vec<Result<ServerDecision, VerifyError>> {
+    let checked = submissions
+        .par_iter()
+        .map(|submission| checked_public_inputs(key, submission))
+        .collect::<Vec<_>>();
+    let pvk = prepare_verifying_key(&key.verifying_key);
+    let prepared = checked
+        .par_iter()
+        .map(|inputs| {
+            inputs.as_ref().ok().map(|inputs| {
+                Groth16::<Bls12_381>::prepare_inputs(&pvk, inputs)
+                    .expect("the verifying key matches the circuit")
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let candidates = submissions
+        .iter()
+        .zip(&prepared)
+        .filter_map(|(submission, inputs)| inputs.map(|inputs| (submission, inputs)))
+        .collect::<Vec<_>>();
+    let all_valid = batch_equation_holds(&pvk, &candidates);
+
+    let g_r_sum = key.g_r_sum();
+    submissions
+        .par_iter()
+        .zip(checked)
+        .zip(prepared)
+        .map(|((submission, checked), inputs)| {
+            checked?;
+            let inputs = inputs.expect("checked submissions have prepared inputs");
+            let valid = all_valid
+                || Groth16::<Bls12_381>::verify_proof_with_prepared_inputs(
+                    &pvk,
+                    &submission.proof,
+                    &inputs,
+                )
+                .unwrap_or(false);
+            if !valid {
+                return Err(VerifyError::ProofRejected);
+            }
+            Ok(zero_test(&g_r_sum, &submission.res_total))
+        })
+        .collect()
+}
+
+/// Whether every proof satisfies its verification equation, checked as one
+/// random linear combination.
+fn batch_equation_holds(
+    pvk: &PreparedVerifyingKey<Bls12_381>,
+    batch: &[(&ClientSubmission, G1Projective)],
+) -> bool {
+    if batch.is_empty() {
+        return true;
+    }
+    let mut transcript = Transcript::new(b"regime-a batch verification");
+    for (submission, _) in batch {
+        transcript.append_message(b"submission", &submission.transcript_hash());
+    }
+    let rho = batch
+        .iter()
+        .map(|_| transcript.challenge_field::<BlsFr>(b"rho"))
+        .collect::<Vec<_>>();
+
+    let scaled_a = batch
+        .par_iter()
+        .zip(&rho)
+        .map(|((submission, _), rho)| submission.proof.a * rho)
+        .collect::<Vec<_>>();
+    let (inputs, c) = batch
+        .par_iter()
+        .zip(&rho)
+        .map(|((submission, inputs), rho)| (*inputs * rho, submission.proof.c * rho))
+        .reduce(
+            || (G1Projective::default(), G1Projective::default()),
+            |(i1, c1), (i2, c2)| (i1 + i2, c1 + c2),
+        );
+
+    let g1 = G1Projective::normalize_batch(&scaled_a)
+        .into_iter()
+        .chain([inputs.into_affine(), c.into_affine()])
+        .map(<Bls12_381 as Pairing>::G1Prepared::from)
+        .collect::<Vec<_>>();
+    let g2 = batch
+        .iter()
+        .map(|(submission, _)| submission.proof.b.into())
+        .chain([pvk.gamma_g2_neg_pc.clone(), pvk.delta_g2_neg_pc.clone()])
+        .collect::<Vec<<Bls12_381 as Pairing>::G2Prepared>>();
+    let Some(result) = Bls12_381::final_exponentiation(Bls12_381::multi_miller_loop(g1, g2)) else {
+        return false;
+    };
+    let rho_sum = rho.iter().sum::<BlsFr>();
+    result.0 == pvk.alpha_g1_beta_g2.pow(rho_sum.into_bigint())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::regime_a::{
+        client_submit, server_verify_and_decide, ClientId, RegimeAParams, SubmissionHeader,
+        TtpSetup,
+    };
+    use ark_std::rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn batch_matches_single_verification() {
+        let params = RegimeAParams::new(4, 2, 2);
+        let setup = TtpSetup::setup_seeded(vec![vec![0; params.lambda()]], params.clone(), 8);
+        let mut rng = StdRng::seed_from_u64(0);
+        let queries = [
+            vec![0, 0, 0, 1, 1, 1, 1, 1],
+            vec![1; 8],
+            vec![1, 0, 0, 0, 1, 1, 1, 1],
+            vec![0, 1, 1, 1, 1, 0, 1, 1],
+        ];
+        let mut submissions = queries
+            .iter()
+            .zip(1..)
+            .map(|(query, msgid)| {
+                let header = SubmissionHeader {
+                    client_id: ClientId([2; 32]),
+                    msgid,
+                    issued_at: 1_700_000_000,
+                };
+                client_submit(&setup.client_key, query.clone(), header, &mut rng)
+            })
+            .collect::<Vec<_>>();
+        let decide_each = |submissions: &[ClientSubmission]| {
+            submissions
+                .iter()
+                .map(|s| server_verify_and_decide(&setup.server_key, s))
+                .collect::<Vec<_>>()
+        };
+
+        let results = server_verify_batch(&setup.server_key, &submissions);
+        assert_eq!(
+            results,
+            [
+                Ok(ServerDecision::Yes),
+                Ok(ServerDecision::No),
+                Ok(ServerDecision::Yes),
+                Ok(ServerDecision::No),
+            ]
+        );
+        assert_eq!(results, decide_each(&submissions));
+        assert!(server_verify_batch(&setup.server_key, &[]).is_empty());
+
+        // A proof moved to another header fails the batch equation, and only
+        // that submission is rejected.
+        submissions[1].header.msgid = 99;
+        submissions[3].epoch = 5;
+        let results = server_verify_batch(&setup.server_key, &submissions);
+        assert_eq!(results[1], Err(VerifyError::ProofRejected));
+        assert!(matches!(
+            results[3],
+            Err(VerifyError::EpochMismatch { found: 5, .. })
+        ));
+        assert_eq!(results, decide_each(&submissions));
+    }
+}
@@ -0,0 +1,420 @@
+//! Zero-knowledge proof of a well-formed Regime A submission.
+//!
+//! The TTP publishes, for every chunk `b` and chunk value `x`, the point
+//! `T_b[x] = g^{s_b(x) + r_b}` and commits to all of them in a Poseidon Merkle
+//! tree whose leaf `b * 2^ell + x` is `H(T_b[x])`. A client proves, in
+//! Groth16 over BLS12-381, that for its private bits `d`:
+//!
+//! * `c_d = rho * H_0 + sum_i d_i * H_{i+1}` (a hiding Pedersen commitment),
+//! * for every chunk the leaf at the index spelled by that chunk's bits is a
+//!   point `P_b` under the published root, and
+//! * `res_total = sum_b P_b`.
+//!
+//! The group is the prime-order subgroup of Jubjub, whose base field is the
+//! BLS12-381 scalar field, so all point arithmetic is native to the circuit.
+
+use ark_crypto_primitives::sponge::constraints::CryptographicSpongeVar;
+use ark_crypto_primitives::sponge::poseidon::constraints::PoseidonSpongeVar;
+use ark_crypto_primitives::sponge::poseidon::{
+    find_poseidon_ark_and_mds, PoseidonConfig, PoseidonSponge,
+};
+use ark_crypto_primitives::sponge::CryptographicSponge;
+use ark_ec::{AffineRepr, CurveGroup, Group};
+use ark_ed_on_bls12_381::constraints::EdwardsVar;
+use ark_ed_on_bls12_381::{EdwardsAffine, EdwardsProjective, Fq, Fr};
+use ark_ff::{BigInteger, PrimeField, Zero};
+use ark_r1cs_std::{alloc::AllocVar, boolean::Boolean, fields::fp::FpVar, prelude::*};
+use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// Poseidon width-3 parameters for the BLS12-381 scalar field.
+const POSEIDON_FULL_ROUNDS: usize = 8;
+const POSEIDON_PARTIAL_ROUNDS: usize = 57;
+const POSEIDON_ALPHA: u64 = 5;
+/// BLAKE3 key-derivation context for the Pedersen generators.
+const PEDERSEN_CONTEXT: &str = "pdqhash 2024 regime-a pedersen generators";
+
+fn poseidon_config() -> &'static PoseidonConfig<Fq> {
+    static CONFIG: OnceLock<PoseidonConfig<Fq>> = OnceLock::new();
+    CONFIG.get_or_init(|| {
+        let (ark, mds) = find_poseidon_ark_and_mds::<Fq>(
+            Fq::MODULUS_BIT_SIZE as u64,
+            2,
+            POSEIDON_FULL_ROUNDS as u64,
+            POSEIDON_PARTIAL_ROUNDS as u64,
+            0,
+        );
+        PoseidonConfig::new(
+            POSEIDON_FULL_ROUNDS,
+            POSEIDON_PARTIAL_ROUNDS,
+            POSEIDON_ALPHA,
+            mds,
+            ark,
+            2,
+            1,
+        )
+    })
+}
+
+/// Two-to-one Poseidon hash used for leaves and inner nodes.
+pub(crate) fn hash_pair(left: Fq, right: Fq) -> Fq {
+    let mut sponge = PoseidonSponge::new(poseidon_config());
+    sponge.absorb(&left);
+    sponge.absorb(&right);
+    sponge.squeeze_field_elements::<Fq>(1)[0]
+}
+
+fn hash_pair_var(left: &FpVar<Fq>, right: &FpVar<Fq>) -> Result<FpVar<Fq>, SynthesisError> {
+    let cs = left.cs().or(right.cs());
+    let mut sponge = PoseidonSpongeVar::new(cs, poseidon_config());
+    sponge.absorb(left)?;
+    sponge.absorb(right)?;
+    Ok(sponge.squeeze_field_elements(1)?.remove(0))
+}
+
+/// Merkle leaf committing to a table point.
+pub(crate) fn leaf_hash(point: &EdwardsAffine) -> Fq {
+    hash_pair(point.x, point.y)
+}
+
+/// Poseidon Merkle tree over the TTP's per-chunk tables.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct TableTree {
+    /// `levels[0]` are the leaves, the last level is the root.
+    levels: Vec<Vec<Fq>>,
+}
+
+impl TableTree {
+    /// Build a tree over `leaves`, padded with zeros to a power of two.
+    ///
+    /// Tables are mostly copies of one masked point, so repeated pairs are
+    /// hashed once per level.
+    pub(crate) fn new(mut leaves: Vec<Fq>) -> Self {
+        leaves.resize(leaves.len().next_power_of_two().max(2), Fq::zero());
+        let mut levels = vec![leaves];
+        while levels.last().map_or(0, Vec::len) > 1 {
+            let mut cache = HashMap::new();
+            let next = levels
+                .last()
+                .unwrap()
+                .chunks(2)
+                .map(|pair| {
+                    *cache
+                        .entry((pair[0], pair[1]))
+                        .or_insert_with(|| hash_pair(pair[0], pair[1]))
+                })
+                .collect();
+            levels.push(next);
+        }
+        Self { levels }
+    }
+
+    pub(crate) fn root(&self) -> Fq {
+        self.levels.last().unwrap()[0]
+    }
+
+    /// Number of levels between a leaf and the root.
+    pub(crate) fn depth(&self) -> usize {
+        self.levels.len() - 1
+    }
+
+    /// Sibling hashes from the leaf at `index` up to the root.
+    pub(crate) fn path(&self, mut index: usize) -> Vec<Fq> {
+        let mut siblings = Vec::with_capacity(self.depth());
+        for level in &self.levels[..self.depth()] {
+            siblings.push(level[index ^ 1]);
+            index >>= 1;
+        }
+        siblings
+    }
+}
+
+/// Depth of the table tree for the given chunk layout.
+pub(crate) fn tree_depth(ell: usize, b_chunks: usize) -> usize {
+    (b_chunks << ell)
+        .next_power_of_two()
+        .max(2)
+        .trailing_zeros() as usize
+}
+
+/// Hash `(index, counter)` onto Jubjub until a prime-order point is found, so
+/// nobody knows discrete logs between generators.
+fn derive_generator(index: u64) -> EdwardsProjective {
+    (0u64..)
+        .find_map(|counter| {
+            let mut hasher = blake3::Hasher::new_derive_key(PEDERSEN_CONTEXT);
+            hasher.update(&index.to_le_bytes());
+            hasher.update(&counter.to_le_bytes());
+            let y = Fq::from_le_bytes_mod_order(hasher.finalize().as_bytes());
+            EdwardsAffine::get_point_from_y_unchecked(y, false)
+                .map(|point| point.mul_by_cofactor_to_group())
+                .filter(|point| !point.is_zero())
+        })
+        .expect("an unbounded search always finds a point")
+}
+
+/// Pedersen generators for committing to `lambda` bits.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct CommitmentKey {
+    /// `2^j * H_0` for every bit `j` of the blinding scalar.
+    blinding_multiples: Vec<EdwardsProjective>,
+    /// `H_1 .. H_lambda`, one per committed bit.
+    bit_generators: Vec<EdwardsProjective>,
+}
+
+impl CommitmentKey {
+    pub(crate) fn new(lambda: usize) -> Self {
+        let mut base = derive_generator(0);
+        let blinding_multiples = (0..Fr::MODULUS_BIT_SIZE)
+            .map(|_| {
+                let current = base;
+                base.double_in_place();
+                current
+            })
+            .collect();
+        let bit_generators = (1..=lambda as u64).map(derive_generator).collect();
+        Self {
+            blinding_multiples,
+            bit_generators,
+        }
+    }
+
+    pub(crate) fn commit(&self, bits: &[u8], blinding: &Fr) -> EdwardsAffine {
+        let blinding_bits = blinding.into_bigint().to_bits_le();
+        bits.iter()
+            .map(|bit| *bit == 1)
+            .zip(&self.bit_generators)
+            .chain(blinding_bits.into_iter().zip(&self.blinding_multiples))
+            .filter(|(bit, _)| *bit)
+            .map(|(_, generator)| *generator)
+            .sum::<EdwardsProjective>()
+            .into_affine()
+    }
+}
+
+/// Public inputs of [`SubmissionCircuit`], in allocation order.
+pub(crate) fn public_inputs(
+    statement: Fq,
+    root: Fq,
+    c_d: &EdwardsAffine,
+    res_total: &EdwardsAffine,
+) -> Vec<Fq> {
+    vec![statement, root, c_d.x, c_d.y, res_total.x, res_total.y]
+}
+
+/// Private inputs of a submission proof.
+#[derive(Clone, Debug)]
+pub(crate) struct SubmissionWitness {
+    pub(crate) bits: Vec<u8>,
+    pub(crate) blinding: Fr,
+    /// Table point selected by each chunk.
+    pub(crate) points: Vec<EdwardsAffine>,
+    /// Merkle path of each selected point.
+    pub(crate) paths: Vec<Vec<Fq>>,
+}
+
+/// Groth16 circuit for a Regime A submission; see the module docs.
+#[derive(Clone, Debug)]
+pub(crate) struct SubmissionCircuit<'a> {
+    pub(crate) ell: usize,
+    /// Query bits in each chunk, at most `ell`.
+    pub(crate) chunk_lens: Vec<usize>,
+    pub(crate) key: &'a CommitmentKey,
+    /// Transcript digest binding the proof to its message and parameters.
+    pub(crate) statement: Fq,
+    pub(crate) root: Fq,
+    pub(crate) c_d: EdwardsAffine,
+    pub(crate) res_total: EdwardsAffine,
+    /// `None` during key generation.
+    pub(crate) witness: Option<SubmissionWitness>,
+}
+
+impl SubmissionCircuit<'_> {
+    /// Circuit shape for key generation; every value is a placeholder.
+    pub(crate) fn blank(
+        ell: usize,
+        chunk_lens: Vec<usize>,
+        key: &CommitmentKey,
+    ) -> SubmissionCircuit<'_> {
+        SubmissionCircuit {
+            ell,
+            chunk_lens,
+            key,
+            statement: Fq::zero(),
+            root: Fq::zero(),
+            c_d: EdwardsAffine::zero(),
+            res_total: EdwardsAffine::zero(),
+            witness: None,
+        }
+    }
+}
+
+fn point_input(
+    cs: &ConstraintSystemRef<Fq>,
+    point: &EdwardsAffine,
+) -> Result<EdwardsVar, SynthesisError> {
+    Ok(EdwardsVar::new(
+        FpVar::new_input(cs.clone(), || Ok(point.x))?,
+        FpVar::new_input(cs.clone(), || Ok(point.y))?,
+    ))
+}
+
+impl ConstraintSynthesizer<Fq> for SubmissionCircuit<'_> {
+    fn generate_constraints(self, cs: ConstraintSystemRef<Fq>) -> Result<(), SynthesisError> {
+        let b_chunks = self.chunk_lens.len();
+        let lambda = self.chunk_lens.iter().sum::<usize>();
+        let depth = tree_depth(self.ell, b_chunks);
+        let witness = self.witness.as_ref();
+        let missing = || SynthesisError::AssignmentMissing;
+
+        // Public inputs, in the order of `public_inputs`. The statement digest
+        // is only bound, never used.
+        let _statement = FpVar::new_input(cs.clone(), || Ok(self.statement))?;
+        let root = FpVar::new_input(cs.clone(), || Ok(self.root))?;
+        let c_d = point_input(&cs, &self.c_d)?;
+        let res_total = point_input(&cs, &self.res_total)?;
+
+        let bits = (0..lambda)
+            .map(|i| {
+                Boolean::new_witness(cs.clone(), || {
+                    witness.map(|w| w.bits[i] == 1).ok_or_else(missing)
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let blinding_bits = (0..Fr::MODULUS_BIT_SIZE as usize)
+            .map(|j| {
+                Boolean::new_witness(cs.clone(), || {
+                    witness
+                        .map(|w| w.blinding.into_bigint().get_bit(j))
+                        .ok_or_else(missing)
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Commitment opening.
+        let mut commitment = EdwardsVar::zero();
+        commitment.precomputed_base_scalar_mul_le(bits.iter().zip(&self.key.bit_generators))?;
+        commitment.precomputed_base_scalar_mul_le(
+            blinding_bits.iter().zip(&self.key.blinding_multiples),
+        )?;
+        commitment.enforce_equal(&c_d)?;
+
+        // Table lookups: the low `ell` path bits are the chunk's own bits,
+        // padded with zeros to `ell`, the remaining ones the (constant) chunk
+        // index.
+        let mut total = EdwardsVar::zero();
+        let mut offset = 0;
+        for (b, &len) in self.chunk_lens.iter().enumerate() {
+            let x = FpVar::new_witness(cs.clone(), || {
+                witness.map(|w| w.points[b].x).ok_or_else(missing)
+            })?;
+            let y = FpVar::new_witness(cs.clone(), || {
+                witness.map(|w| w.points[b].y).ok_or_else(missing)
+            })?;
+            let mut node = hash_pair_var(&x, &y)?;
+            for level in 0..depth {
+                let sibling = FpVar::new_witness(cs.clone(), || {
+                    witness.map(|w| w.paths[b][level]).ok_or_else(missing)
+                })?;
+                let is_right = if level < len {
+                    bits[offset + level].clone()
+                } else if level < self.ell {
+                    Boolean::FALSE
+                } else {
+                    Boolean::constant((b >> (level - self.ell)) & 1 == 1)
+                };
+                let left = is_right.select(&sibling, &node)?;
+                let right = is_right.select(&node, &sibling)?;
+                node = hash_pair_var(&left, &right)?;
+            }
+            node.enforce_equal(&root)?;
+            total += EdwardsVar::new(x, y);
+            offset += len;
+        }
+        total.enforce_equal(&res_total)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_relations::r1cs::ConstraintSystem;
+    use ark_serialize::CanonicalSerialize;
+    use ark_std::UniformRand;
+
+    #[test]
+    fn tree_paths_reach_the_root() {
+        let leaves = (0..5u64).map(Fq::from).collect::<Vec<_>>();
+        let tree = TableTree::new(leaves);
+        assert_eq!(tree.depth(), 3);
+        for index in 0..8 {
+            let mut node = tree.levels[0][index];
+            for (level, sibling) in tree.path(index).into_iter().enumerate() {
+                node = if (index >> level) & 1 == 1 {
+                    hash_pair(sibling, node)
+                } else {
+                    hash_pair(node, sibling)
+                };
+            }
+            assert_eq!(node, tree.root());
+        }
+    }
+
+    #[test]
+    fn generator_test_vector() {
+        // Pinned for interoperability; see docs/regime_a_wire.md.
+        let mut bytes = Vec::new();
+        derive_generator(0)
+            .into_affine()
+            .serialize_compressed(&mut bytes)
+            .unwrap();
+        assert_eq!(hex::encode(bytes), GENERATOR_0);
+        assert_eq!(hex::encode(leaf_bytes()), LEAF_HASH);
+    }
+
+    fn leaf_bytes() -> Vec<u8> {
+        let mut bytes = Vec::new();
+        hash_pair(Fq::from(1u64), Fq::from(2u64))
+            .serialize_compressed(&mut bytes)
+            .unwrap();
+        bytes
+    }
+
+    const GENERATOR_0: &str = "585eb8b3857286339284d65514daa485e20f536fe3c1f70c8d0ad61d2ef2d164";
+    const LEAF_HASH: &str = "c4ddb1b43766a56e9b85b9e98c11c156a92ba85e94d8cf96a84353c912e3f351";
+
+    #[test]
+    fn commitment_gadget_matches_native() {
+        let key = CommitmentKey::new(4);
+        let blinding = Fr::rand(&mut ark_std::test_rng());
+        let bits = [1u8, 0, 1, 1];
+        let expected = key.commit(&bits, &blinding);
+
+        let cs = ConstraintSystem::<Fq>::new_ref();
+        let mut commitment = EdwardsVar::zero();
+        let bit_vars = bits
+            .iter()
+            .map(|bit| Boolean::new_witness(cs.clone(), || Ok(*bit == 1)))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let blinding_vars = blinding
+            .into_bigint()
+            .to_bits_le()
+            .into_iter()
+            .take(Fr::MODULUS_BIT_SIZE as usize)
+            .map(|bit| Boolean::new_witness(cs.clone(), || Ok(bit)))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        commitment
+            .precomputed_base_scalar_mul_le(bit_vars.iter().zip(&key.bit_generators))
+            .unwrap();
+        commitment
+            .precomputed_base_scalar_mul_le(blinding_vars.iter().zip(&key.blinding_multiples))
+            .unwrap();
+        assert_eq!(commitment.value().unwrap(), expected.into_group());
+        assert!(cs.is_satisfied().unwrap());
+    }
+}
@@ -0,0 +1,448 @@
+//! Disclosing which database entry a Yes matched.
+//!
+//! A [`ServerDecision`] only says whether some entry is near the query. For
+//! actionable reports, [`TtpSetup::disclosure_setup`] also gives every label
+//! a secret tag `tau`, shared by all entries enrolled under that label, and
+//! publishes label tables
+//!
+//! ```text
+//! L_b[x] = g^{t_b(x) + u_b},   t_b(x) = sum_i tau_i * gamma_ib * z_b(dist(x, chunk_b(db_i)))
+//! ```
+//!
+//! under fresh masks `u_b`. With [`client_submit_with_disclosure`] a client
+//! also sums the label points its query selects into `res_label` and proves
+//! it with the submission circuit, against the label tree and the same
+//! commitment `c_d`, so both sums come from the same bits.
+//!
+//! If every entry near the query carries the same label, then
+//! `res_total - g^{r_sum} = g^c` and `res_label - g^{u_sum} = g^{tau c}` for
+//! the same unknown `c = sum_i sum_b gamma_ib * z_b(...)`, so the holder of
+//! the [`DisclosureKey`] finds the label whose tag maps one onto the other,
+//! at one scalar multiplication per label. Enrolling the
+//! [`dihedral_hashes`](super::pdq::dihedral_hashes) of an image under one
+//! label therefore discloses that label even when several orientations are
+//! near the query. On a No both differences are zero and there is nothing to
+//! find; the key holder does not even look at the disclosure. When entries
+//! with different labels are near the query no tag fits and the outcome is
+//! [`Disclosed::Ambiguous`].
+//!
+//! Label tables differ from `g^{u_b}` exactly where the submission tables
+//! differ from `g^{r_b}`, so they show no chunk values the client key does
+//! not. At a near point the label offset is the submission offset times the
+//! tag, but the weights `gamma_ib` are independent across chunks, so telling
+//! whether offsets in different chunks share a tag, and hence an entry or a
+//! label, is a decisional Diffie–Hellman problem. A disclosure setup is bound
+//! to the epoch and entry order it was made at: repeat it after every
+//! [`update`](super::update), which redraws the weights.
+
+use super::circuit::{self, TableTree};
+use super::transcript::Transcript;
+use super::{
+    chunk_value, in_prime_order_subgroup, masked_table, nonzero_weight, proof_well_formed,
+    prove_lookups, server_verify_and_decide, submit_with_blinding, table_tree, ClientKey,
+    ClientSubmission, RegimeAParams, ServerDecision, ServerKey, SubmissionHeader, TtpSetup,
+    VerifyError,
+};
+use ark_bls12_381::Bls12_381;
+use ark_ec::{AffineRepr, CurveGroup};
+use ark_ed_on_bls12_381::{EdwardsAffine, EdwardsProjective, Fq, Fr};
+use ark_groth16::{Groth16, Proof};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use ark_snark::SNARK;
+use ark_std::rand::{CryptoRng, RngCore};
+use ark_std::UniformRand;
+use std::collections::HashMap;
+
+/// Published label tables, used by clients alongside their [`ClientKey`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct LabelTables {
+    /// Parameters the tables were built for.
+    pub params: RegimeAParams,
+    /// Database version the tables reflect.
+    pub epoch: u64,
+    /// `tables[b][x] = g^{t_b(x) + u_b}` for every chunk value `x`.
+    pub tables: Vec<Vec<EdwardsAffine>>,
+    tree: TableTree,
+}
+
+impl LabelTables {
+    /// Assemble label tables, rebuilding their tree.
+    pub fn new(params: RegimeAParams, epoch: u64, tables: Vec<Vec<EdwardsAffine>>) -> Self {
+        assert_eq!(tables.len(), params.b_chunks);
+        assert!(tables.iter().all(|t| t.len() == 1 << params.ell));
+        let tree = table_tree(&tables);
+        Self {
+            params,
+            epoch,
+            tables,
+            tree,
+        }
+    }
+
+    /// Root of the Merkle tree over all label points.
+    pub fn root(&self) -> Fq {
+        self.tree.root()
+    }
+
+    /// `res_label` for the bit vector `d`.
+    pub fn masked_sum(&self, d: &[u8]) -> EdwardsAffine {
+        assert_eq!(d.len(), self.params.lambda());
+        (0..self.params.b_chunks)
+            .map(|b| self.tables[b][chunk_value(self.params.chunk(d, b))].into_group())
+            .sum::<EdwardsProjective>()
+            .into_affine()
+    }
+}
+
+/// What an authorized party needs to learn which entry a submission matched.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DisclosureKey {
+    /// Key to verify and decide the submission a disclosure comes with.
+    pub server_key: ServerKey,
+    /// Root of the label tree disclosures must prove against.
+    pub label_root: Fq,
+    /// `Σ_b u_b`, so that a far query sums to `g^{label_r_sum}`.
+    pub label_r_sum: Fr,
+    /// Secret tag of each entry, in database order; entries with the same
+    /// label share a tag.
+    pub tags: Vec<Fr>,
+    /// Label of each entry, in database order.
+    pub labels: Vec<Vec<u8>>,
+}
+
+/// Output of [`TtpSetup::disclosure_setup`].
+#[derive(Clone, Debug)]
+pub struct DisclosureSetup {
+    /// Label tables, safe to publish to clients.
+    pub tables: LabelTables,
+    /// Tags, labels and masks, for the authorized party only.
+    pub key: DisclosureKey,
+}
+
+/// A client's proven label sum, sent along with its submission.
+#[derive(Clone, Debug, PartialEq, CanonicalSerialize, CanonicalDeserialize)]
+pub struct Disclosure {
+    /// Label tree root the proof was produced against.
+    pub root: Fq,
+    /// Sum of the label points the query selects.
+    pub res_label: EdwardsAffine,
+    /// Groth16 proof that `res_label` comes from the committed query bits.
+    pub proof: Proof<Bls12_381>,
+}
+
+/// What a disclosure reveals about its submission.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Disclosed {
+    /// The submission was a No: no entry matched.
+    NoMatch,
+    /// Every entry that matched carries this label.
+    Label {
+        /// The matching label.
+        label: Vec<u8>,
+        /// Positions in the database of every entry enrolled under the
+        /// label, whether or not it was near the query.
+        entries: Vec<usize>,
+    },
+    /// Entries with different labels matched, and the disclosure cannot
+    /// tell which.
+    Ambiguous,
+}
+
+impl TtpSetup {
+    /// Tag every label and build label tables for `labels`, one per entry in
+    /// database order. Entries with equal labels get the same tag.
+    pub fn disclosure_setup<R: RngCore + CryptoRng>(
+        &self,
+        labels: Vec<Vec<u8>>,
+        rng: &mut R,
+    ) -> DisclosureSetup {
+        let secret = &self.secret;
+        let params = &secret.params;
+        assert_eq!(labels.len(), secret.db.len());
+
+        let mut label_tags = HashMap::new();
+        let tags = labels
+            .iter()
+            .map(|label| {
+                *label_tags
+                    .entry(label)
+                    .or_insert_with(|| nonzero_weight(rng))
+            })
+            .collect::<Vec<_>>();
+        let masks = (0..params.b_chunks)
+            .map(|_| Fr::rand(rng))
+            .collect::<Vec<_>>();
+        let tables = masks
+            .iter()
+            .enumerate()
+            .map(|(b, mask)| {
+                let weights = secret
+                    .gamma
+                    .iter()
+                    .zip(&tags)
+                    .map(|(gamma, tag)| gamma[b] * tag)
+                    .collect::<Vec<_>>();
+                masked_table(params, &params.chunk_scores(&secret.db, &weights, b), *mask)
+            })
+            .collect();
+
+        let tables = LabelTables::new(params.clone(), self.client_key.epoch, tables);
+        let key = DisclosureKey {
+            server_key: self.server_key.clone(),
+            label_root: tables.root(),
+            label_r_sum: masks.iter().sum(),
+            tags,
+            labels,
+        };
+        DisclosureSetup { tables, key }
+    }
+}
+
+/// [`client_submit`](super::client_submit), together with a disclosure of
+/// the same query bits against `labels`.
+pub fn client_submit_with_disclosure<R: RngCore + CryptoRng>(
+    key: &ClientKey,
+    labels: &LabelTables,
+    d: Vec<u8>,
+    header: SubmissionHeader,
+    rng: &mut R,
+) -> (ClientSubmission, Disclosure) {
+    assert_eq!(labels.params, key.params);
+    assert_eq!(labels.epoch, key.epoch);
+    let blinding = Fr::rand(rng);
+    let submission = submit_with_blinding(key, d.clone(), header, blinding, rng);
+
+    let res_label = labels.masked_sum(&d);
+    let root = labels.root();
+    let statement = disclosure_digest(&submission, &root, &res_label);
+    let proof = prove_lookups(
+        key,
+        &labels.tables,
+        &labels.tree,
+        statement,
+        submission.c_d,
+        res_label,
+        d,
+        blinding,
+        rng,
+    );
+    let disclosure = Disclosure {
+        root,
+        res_label,
+        proof,
+    };
+    (submission, disclosure)
+}
+
+/// Fiat–Shamir digest binding a disclosure proof to its submission.
+fn disclosure_digest(submission: &ClientSubmission, root: &Fq, res_label: &EdwardsAffine) -> Fq {
+    let mut transcript = Transcript::new(b"regime-a disclosure");
+    transcript.append_message(b"submission", &submission.transcript_hash());
+    transcript.append_serialized(b"root", root);
+    transcript.append_serialized(b"res_label", res_label);
+    transcript.challenge_field(b"statement")
+}
+
+impl DisclosureKey {
+    /// Verify `submission` and, if it is a Yes, its `disclosure`, and name
+    /// the label it matched.
+    pub fn disclose(
+        &self,
+        submission: &ClientSubmission,
+        disclosure: &Disclosure,
+    ) -> Result<Disclosed, VerifyError> {
+        if server_verify_and_decide(&self.server_key, submission)? == ServerDecision::No {
+            return Ok(Disclosed::NoMatch);
+        }
+        if disclosure.root != self.label_root {
+            return Err(VerifyError::RootMismatch {
+                expected: self.label_root,
+                found: disclosure.root,
+            });
+        }
+        if !in_prime_order_subgroup(&disclosure.res_label) {
+            return Err(VerifyError::InvalidResult);
+        }
+        if !proof_well_formed(&disclosure.proof) {
+            return Err(VerifyError::MalformedProof);
+        }
+        let statement = disclosure_digest(submission, &disclosure.root, &disclosure.res_label);
+        let inputs = circuit::public_inputs(
+            statement,
+            disclosure.root,
+            &submission.c_d,
+            &disclosure.res_label,
+        );
+        if !Groth16::<Bls12_381>::verify(&self.server_key.verifying_key, &inputs, &disclosure.proof)
+            .unwrap_or(false)
+        {
+            return Err(VerifyError::ProofRejected);
+        }
+
+        let g = self.server_key.params.g;
+        let matched = submission.res_total.into_group() - g * self.server_key.r_sum;
+        let labelled = disclosure.res_label.into_group() - g * self.label_r_sum;
+        let mut checked = HashMap::new();
+        let entries = self
+            .tags
+            .iter()
+            .enumerate()
+            .filter(|(_, tag)| {
+                *checked
+                    .entry(**tag)
+                    .or_insert_with(|| matched * **tag == labelled)
+            })
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>();
+        Ok(match entries.first() {
+            Some(&first) => Disclosed::Label {
+                label: self.labels[first].clone(),
+                entries,
+            },
+            None => Disclosed::Ambiguous,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::regime_a::ClientId;
+    use ark_std::rand::{rngs::StdRng, SeedableRng};
+
+    fn header(msgid: u64) -> SubmissionHeader {
+        SubmissionHeader {
+            client_id: ClientId([3; 32]),
+            msgid,
+            issued_at: 1_700_000_000,
+        }
+    }
+
+    fn bits(s: &str) -> Vec<u8> {
+        s.bytes().filter(|c| *c != b' ').map(|c| c - b'0').collect()
+    }
+
+    /// Entries 0 and 2 share their first two chunks.
+    fn setup_with(labels: [&[u8]; 3]) -> (TtpSetup, DisclosureSetup) {
+        let params = RegimeAParams::new(4, 3, 2);
+        let db = vec![
+            bits("0000 0000 0000"),
+            bits("1111 1111 1111"),
+            bits("0000 0000 1111"),
+        ];
+        let setup = TtpSetup::setup_seeded(db, params, 8);
+        let labels = labels.iter().map(|label| label.to_vec()).collect();
+        let disclosure = setup.disclosure_setup(labels, &mut StdRng::seed_from_u64(9));
+        (setup, disclosure)
+    }
+
+    fn setup() -> (TtpSetup, DisclosureSetup) {
+        setup_with([b"zeros", b"ones", b"mixed"])
+    }
+
+    #[test]
+    fn disclosure_names_the_matching_label() {
+        let (setup, disclosure) = setup();
+        let mut rng = StdRng::seed_from_u64(0);
+        let disclose = |query: &str, msgid: u64, rng: &mut StdRng| {
+            let (submission, proof) = client_submit_with_disclosure(
+                &setup.client_key,
+                &disclosure.tables,
+                bits(query),
+                header(msgid),
+                rng,
+            );
+            disclosure.key.disclose(&submission, &proof)
+        };
+
+        assert_eq!(
+            disclose("1111 1110 0110", 1, &mut rng),
+            Ok(Disclosed::Label {
+                label: b"ones".to_vec(),
+                entries: vec![1]
+            })
+        );
+        assert_eq!(
+            disclose("0110 1001 0110", 2, &mut rng),
+            Ok(Disclosed::NoMatch)
+        );
+        assert_eq!(
+            disclose("0000 0000 0000", 3, &mut rng),
+            Ok(Disclosed::Ambiguous)
+        );
+    }
+
+    #[test]
+    fn entries_sharing_a_label_disclose_together() {
+        // As when the orientations of one image are enrolled together.
+        let (setup, disclosure) = setup_with([b"bridge", b"ones", b"bridge"]);
+        assert_eq!(disclosure.key.tags[0], disclosure.key.tags[2]);
+        let (submission, proof) = client_submit_with_disclosure(
+            &setup.client_key,
+            &disclosure.tables,
+            bits("0000 0000 0000"),
+            header(1),
+            &mut StdRng::seed_from_u64(2),
+        );
+        assert_eq!(
+            disclosure.key.disclose(&submission, &proof),
+            Ok(Disclosed::Label {
+                label: b"bridge".to_vec(),
+                entries: vec![0, 2]
+            })
+        );
+    }
+
+    #[test]
+    fn disclosure_is_bound_to_its_submission() {
+        let (setup, disclosure) = setup();
+        let mut rng = StdRng::seed_from_u64(1);
+        let query = bits("1111 1111 1111");
+        let (submission, proof) = client_submit_with_disclosure(
+            &setup.client_key,
+            &disclosure.tables,
+            query.clone(),
+            header(1),
+            &mut rng,
+        );
+        let (other, _) = client_submit_with_disclosure(
+            &setup.client_key,
+            &disclosure.tables,
+            query,
+            header(2),
+            &mut rng,
+        );
+        assert_eq!(
+            disclosure.key.disclose(&other, &proof),
+            Err(VerifyError::ProofRejected)
+        );
+
+        let mut forged = proof.clone();
+        forged.res_label = (forged.res_label + setup.secret.params.g).into_affine();
+        assert_eq!(
+            disclosure.key.disclose(&submission, &forged),
+            Err(VerifyError::ProofRejected)
+        );
+    }
+
+    #[test]
+    fn label_tables_reveal_no_further_chunk_values() {
+        let (setup, disclosure) = setup();
+        let label_masks = (0..setup.secret.params.b_chunks)
+            .map(|b| disclosure.tables.tables[b][0b0110])
+            .collect::<Vec<_>>();
+        for (b, (table, labels)) in setup
+            .client_key
+            .tables
+            .iter()
+            .zip(&disclosure.tables.tables)
+            .enumerate()
+        {
+            // Chunk value 0110 is far from every entry in every chunk.
+            let mask = table[0b0110];
+            for (point, label) in table.iter().zip(labels) {
+                assert_eq!(*point == mask, *label == label_masks[b]);
+            }
+        }
+    }
+}
@@ -0,0 +1,993 @@
+//! Regime A protocol implementation (single-shot masked threshold test).
+//!
+//! This module implements the algebraic protocol from the design note over
+//! the prime-order subgroup of Jubjub, with exponents in its scalar field
+//! `Fr`. Each chunk contributes `g^{s_b + r_b}` and the server learns only
+//! whether the product of the contributions differs from `g^{r_sum}`.
+//!
+//! Clients prove with Groth16 over BLS12-381 that `res_total` is the sum of
+//! the published table entries selected by a committed bit vector (see
+//! `circuit.rs`), so the server decides without seeing the bits. Jubjub is
+//! used rather than a pairing-curve group because its base field is the
+//! BLS12-381 scalar field, which keeps point arithmetic native to the circuit.
+//!
+//! The proof statement and submission ids are derived with a
+//! domain-separated [`Transcript`](crate::regime_a::transcript::Transcript);
+//! `docs/regime_a_wire.md` defines every encoding involved so that other
+//! implementations can interoperate. [`scores`] evaluates the underlying
+//! score polynomial in the clear over any prime field. The [`pdq`] module
+//! turns PDQ hashes into protocol inputs, [`simulate`] measures recall and
+//! false positives on image collections, and [`threshold`] runs the setup
+//! among `k` of `n` parties instead of a single TTP. A server can keep signed
+//! receipts of its decisions with [`audit`], and [`disclosure`] lets an
+//! authorized party learn which label a Yes matched.
+
+pub mod analysis;
+pub mod audit;
+pub mod batch;
+mod circuit;
+pub mod disclosure;
+pub mod net;
+pub mod pdq;
+pub mod scores;
+pub mod session;
+pub mod simulate;
+pub mod threshold;
+pub mod transcript;
+pub mod update;
+pub mod wire;
+
+use ark_bls12_381::Bls12_381;
+use ark_ec::{AffineRepr, CurveGroup};
+use ark_ed_on_bls12_381::{EdwardsAffine, EdwardsProjective, Fq, Fr};
+use ark_ff::Zero;
+use ark_groth16::{Groth16, Proof, ProvingKey, VerifyingKey};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use ark_snark::SNARK;
+use ark_std::rand::{CryptoRng, RngCore, SeedableRng};
+use ark_std::UniformRand;
+use circuit::{CommitmentKey, SubmissionCircuit, SubmissionWitness, TableTree};
+use rand_chacha::ChaCha20Rng;
+use std::collections::HashMap;
+use transcript::Transcript;
+
+/// Largest supported chunk length; the TTP publishes `2^ell` points per chunk.
+pub const MAX_CHUNK_BITS: usize = 20;
+
+fn chunk_value(chunk: &[u8]) -> usize {
+    chunk
+        .iter()
+        .enumerate()
+        .map(|(j, bit)| (*bit as usize) << j)
+        .sum()
+}
+
+/// Length and threshold of one chunk.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChunkParams {
+    /// Number of query bits in the chunk, at most `ell`.
+    pub len: usize,
+    /// Distance from a database chunk at which the chunk stops matching.
+    pub epsilon: usize,
+}
+
+/// Public protocol parameters.
+///
+/// The query is split into `b_chunks` consecutive chunks, each with its own
+/// length and threshold. Every table has `2^ell` entries, `ell` being the
+/// longest chunk; a shorter chunk only selects among its first entries.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RegimeAParams {
+    /// Generator of the prime-order Jubjub subgroup.
+    pub g: EdwardsAffine,
+    /// Bits indexing each table: the length of the longest chunk.
+    pub ell: usize,
+    /// Number of chunks, and of tables.
+    pub b_chunks: usize,
+    /// Length and threshold of each chunk, in query order.
+    pub chunks: Vec<ChunkParams>,
+}
+
+impl RegimeAParams {
+    /// Parameters with `b_chunks` chunks of `ell` bits and threshold `epsilon`.
+    pub fn new(ell: usize, b_chunks: usize, epsilon: usize) -> Self {
+        assert!(b_chunks > 0);
+        Self::with_chunks(vec![ChunkParams { len: ell, epsilon }; b_chunks])
+    }
+
+    /// Parameters with chunks of different lengths and thresholds.
+    pub fn with_chunks(chunks: Vec<ChunkParams>) -> Self {
+        assert!(!chunks.is_empty());
+        assert!(chunks
+            .iter()
+            .all(|c| c.len > 0 && c.len <= MAX_CHUNK_BITS && c.epsilon <= c.len));
+        Self {
+            g: EdwardsAffine::generator(),
+            ell: chunks.iter().map(|c| c.len).max().unwrap_or(0),
+            b_chunks: chunks.len(),
+            chunks,
+        }
+    }
+
+    /// Length of a query in bits: the sum of the chunk lengths.
+    pub fn lambda(&self) -> usize {
+        self.chunks.iter().map(|c| c.len).sum()
+    }
+
+    fn chunk<'a>(&self, d: &'a [u8], b: usize) -> &'a [u8] {
+        let start = self.chunks[..b].iter().map(|c| c.len).sum::<usize>();
+        &d[start..start + self.chunks[b].len]
+    }
+
+    /// Query bit lengths of the chunks, as the circuit lays them out.
+    fn chunk_lens(&self) -> Vec<usize> {
+        self.chunks.iter().map(|c| c.len).collect()
+    }
+}
+
+/// Public material a client needs to submit: the masked per-chunk tables and
+/// the proving key.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClientKey {
+    /// Parameters the tables were built for.
+    pub params: RegimeAParams,
+    /// Database version the tables reflect.
+    pub epoch: u64,
+    /// `tables[b][x] = g^{s_b(x) + r_b}` for every chunk value `x`.
+    pub tables: Vec<Vec<EdwardsAffine>>,
+    /// Groth16 proving key for the submission circuit.
+    pub proving_key: ProvingKey<Bls12_381>,
+    tree: TableTree,
+    commitment_key: CommitmentKey,
+}
+
+impl ClientKey {
+    /// Assemble a client key, rebuilding the table tree.
+    pub fn new(
+        params: RegimeAParams,
+        epoch: u64,
+        tables: Vec<Vec<EdwardsAffine>>,
+        proving_key: ProvingKey<Bls12_381>,
+    ) -> Self {
+        assert_eq!(tables.len(), params.b_chunks);
+        assert!(tables.iter().all(|t| t.len() == 1 << params.ell));
+        let tree = table_tree(&tables);
+        let commitment_key = CommitmentKey::new(params.lambda());
+        Self {
+            params,
+            epoch,
+            tables,
+            proving_key,
+            tree,
+            commitment_key,
+        }
+    }
+
+    /// Root of the Merkle tree over all published table points.
+    pub fn root(&self) -> Fq {
+        self.tree.root()
+    }
+
+    /// `res_total` for the bit vector `d`: one table lookup per chunk, so the
+    /// cost depends on `b_chunks` but not on the database size.
+    pub fn masked_sum(&self, d: &[u8]) -> EdwardsAffine {
+        assert_eq!(d.len(), self.params.lambda());
+        (0..self.params.b_chunks)
+            .map(|b| self.tables[b][chunk_value(self.params.chunk(d, b))].into_group())
+            .sum::<EdwardsProjective>()
+            .into_affine()
+    }
+}
+
+/// Merkle tree over `tables`, leaf `b * 2^ell + x` committing to
+/// `tables[b][x]`.
+fn table_tree(tables: &[Vec<EdwardsAffine>]) -> TableTree {
+    let mut leaf_cache = HashMap::new();
+    let leaves = tables
+        .iter()
+        .flatten()
+        .map(|point| {
+            *leaf_cache
+                .entry(*point)
+                .or_insert_with(|| circuit::leaf_hash(point))
+        })
+        .collect();
+    TableTree::new(leaves)
+}
+
+/// What the server needs to decide: the table root, the verifying key and the
+/// unmasking exponent `r_sum`.
+#[derive(Clone, Debug, PartialEq, CanonicalSerialize, CanonicalDeserialize)]
+pub struct ServerKey {
+    /// Parameters the tables were built for.
+    pub params: RegimeAParams,
+    /// Database version submissions must be made against.
+    pub epoch: u64,
+    /// Root of the table tree submissions must prove against.
+    pub root: Fq,
+    /// Groth16 verifying key for the submission circuit.
+    pub verifying_key: VerifyingKey<Bls12_381>,
+    /// `Σ_b r_b`, so that a far query sums to `g^{r_sum}`.
+    pub r_sum: Fr,
+}
+
+/// Secrets only the TTP keeps: the database and the per-entry and per-chunk
+/// masks.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TtpSecret {
+    /// Parameters the tables were built for.
+    pub params: RegimeAParams,
+    /// Database entries, one bit per byte.
+    pub db: Vec<Vec<u8>>,
+    /// Nonzero weights, `gamma[i][b]` for entry `i` in chunk `b`.
+    pub gamma: Vec<Vec<Fr>>,
+    /// Per-chunk table masks `r_b`.
+    pub r_masks: Vec<Fr>,
+}
+
+/// Everything the TTP produces, split by recipient.
+///
+/// Hand `client_key` to clients and `server_key` to the server; `secret`
+/// never leaves the TTP. [`client_submit`] and [`server_verify_and_decide`]
+/// take only their own key, so neither side can be given the masks by
+/// accident.
+///
+/// The published tables are `g·r_b` everywhere except near database chunks,
+/// so anyone holding the client key learns every chunk value within distance
+/// `epsilon_b - 1` of some entry, and with it the chunks of the database
+/// themselves as the centres of those balls. What the tables hide is how the
+/// chunks fit together: every entry has an independent weight in every
+/// chunk, so telling whether two near points of different tables belong to
+/// the same entry is a decisional Diffie–Hellman problem in Jubjub.
+#[derive(Clone, Debug)]
+pub struct TtpSetup {
+    /// Masked tables and proving key, safe to publish.
+    pub client_key: ClientKey,
+    /// Table root, verifying key and `r_sum`, for the server only.
+    pub server_key: ServerKey,
+    /// Database and masks, for the TTP only.
+    pub secret: TtpSecret,
+}
+
+impl TtpSetup {
+    /// Sample the weights and masks from `rng`, publish the tables and
+    /// generate the proof keys.
+    ///
+    /// The proof keys come from `rng` too, so whoever can reproduce its
+    /// output learns the masks and can forge proofs: use an OS-seeded
+    /// generator such as `OsRng` outside of tests.
+    pub fn setup<R: RngCore + CryptoRng>(
+        db: Vec<Vec<u8>>,
+        params: RegimeAParams,
+        rng: &mut R,
+    ) -> Self {
+        assert!(!db.is_empty());
+        assert!(db.iter().all(|d| d.len() == params.lambda()));
+        assert!(db
+            .iter()
+            .all(|d| d.iter().all(|bit| *bit == 0u8 || *bit == 1u8)));
+
+        let gamma = (0..db.len())
+            .map(|_| entry_weights(&params, rng))
+            .collect::<Vec<_>>();
+
+        let r_masks = (0..params.b_chunks)
+            .map(|_| Fr::rand(rng))
+            .collect::<Vec<_>>();
+
+        let (proving_key, verifying_key) = proof_keys(&params, rng);
+        let secret = TtpSecret {
+            params,
+            db,
+            gamma,
+            r_masks,
+        };
+        let client_key = ClientKey::new(secret.params.clone(), 0, secret.tables(), proving_key);
+        let server_key = ServerKey {
+            params: secret.params.clone(),
+            epoch: 0,
+            root: client_key.root(),
+            verifying_key,
+            r_sum: secret.r_sum(),
+        };
+        Self {
+            client_key,
+            server_key,
+            secret,
+        }
+    }
+
+    /// [`setup`](Self::setup) with ChaCha20 seeded from `seed`, for
+    /// reproducible tests and benchmarks. Anyone who knows the seed can
+    /// forge proofs.
+    pub fn setup_seeded(db: Vec<Vec<u8>>, params: RegimeAParams, seed: u64) -> Self {
+        Self::setup(db, params, &mut ChaCha20Rng::seed_from_u64(seed))
+    }
+}
+
+/// A uniformly random nonzero weight.
+fn nonzero_weight<R: RngCore + CryptoRng>(rng: &mut R) -> Fr {
+    loop {
+        let weight = Fr::rand(rng);
+        if !weight.is_zero() {
+            return weight;
+        }
+    }
+}
+
+/// Independent nonzero weights for one entry, one per chunk.
+fn entry_weights<R: RngCore + CryptoRng>(params: &RegimeAParams, rng: &mut R) -> Vec<Fr> {
+    (0..params.b_chunks).map(|_| nonzero_weight(rng)).collect()
+}
+
+/// Groth16 keys for the submission circuit of `params`.
+///
+/// The keys depend only on `ell` and `b_chunks`, not on the database, and
+/// whoever sees the randomness drawn from `rng` can forge proofs.
+pub fn proof_keys<R: RngCore + CryptoRng>(
+    params: &RegimeAParams,
+    rng: &mut R,
+) -> (ProvingKey<Bls12_381>, VerifyingKey<Bls12_381>) {
+    let commitment_key = CommitmentKey::new(params.lambda());
+    Groth16::<Bls12_381>::circuit_specific_setup(
+        SubmissionCircuit::blank(params.ell, params.chunk_lens(), &commitment_key),
+        rng,
+    )
+    .expect("the submission circuit is well formed")
+}
+
+impl TtpSecret {
+    /// The server's unmasking exponent `r_sum = sum_b r_b`.
+    pub fn r_sum(&self) -> Fr {
+        self.r_masks.iter().sum()
+    }
+
+    /// The masked tables for every chunk, as published in the client key.
+    pub fn tables(&self) -> Vec<Vec<EdwardsAffine>> {
+        (0..self.params.b_chunks)
+            .map(|b| self.chunk_table(b))
+            .collect()
+    }
+
+    /// Every entry's weight in chunk `b`.
+    fn chunk_weights(&self, b: usize) -> Vec<Fr> {
+        self.gamma.iter().map(|weights| weights[b]).collect()
+    }
+
+    /// `g^{s_b(x) + r_b}` for every `x`, where
+    /// `s_b(x) = sum_i gamma_ib * z_b(dist(x, chunk_b(db_i)))`.
+    ///
+    /// `z_b` vanishes at distances `epsilon_b..=len_b`, so `s_b(x)` can only
+    /// be nonzero within distance `epsilon_b - 1` of a database chunk. Every
+    /// other entry is `g^{r_b}`, which keeps large `ell` affordable.
+    fn chunk_table(&self, chunk_idx: usize) -> Vec<EdwardsAffine> {
+        let s = self
+            .params
+            .chunk_scores(&self.db, &self.chunk_weights(chunk_idx), chunk_idx);
+        masked_table(&self.params, &s, self.r_masks[chunk_idx])
+    }
+}
+
+/// `g^{s[x] + mask}` for every `x`, computing `g^{mask}` once for the
+/// (usually many) values where `s` is zero.
+fn masked_table(params: &RegimeAParams, s: &[Fr], mask: Fr) -> Vec<EdwardsAffine> {
+    let masked = (params.g * mask).into_affine();
+    let mut table = vec![masked; s.len()];
+    let nonzero = (0..s.len())
+        .filter(|x| !s[*x].is_zero())
+        .collect::<Vec<_>>();
+    let points = nonzero
+        .iter()
+        .map(|x| params.g * (s[*x] + mask))
+        .collect::<Vec<_>>();
+    for (x, point) in nonzero
+        .iter()
+        .zip(EdwardsProjective::normalize_batch(&points))
+    {
+        table[*x] = point;
+    }
+    table
+}
+
+/// Identity of a submitting client, such as a hash of its public key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ClientId(pub [u8; 32]);
+
+/// Public metadata bound into the proof of every submission.
+#[derive(Clone, Debug, PartialEq, Eq, CanonicalSerialize, CanonicalDeserialize)]
+pub struct SubmissionHeader {
+    /// Client the submission is attributed to.
+    pub client_id: ClientId,
+    /// Message id, unique per client within the replay window.
+    pub msgid: u64,
+    /// Unix time in seconds at which the submission was made.
+    pub issued_at: u64,
+}
+
+impl SubmissionHeader {
+    /// Header for `msgid`, issued now.
+    pub fn now(client_id: ClientId, msgid: u64) -> Self {
+        Self {
+            client_id,
+            msgid,
+            issued_at: unix_time(),
+        }
+    }
+
+    fn append_to(&self, transcript: &mut Transcript) {
+        transcript.append_message(b"client_id", &self.client_id.0);
+        transcript.append_u64(b"msgid", self.msgid);
+        transcript.append_u64(b"issued_at", self.issued_at);
+    }
+}
+
+/// Current Unix time in seconds.
+pub(crate) fn unix_time() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/// A client's query, proven against the tables without revealing it.
+#[derive(Clone, Debug, PartialEq, CanonicalSerialize, CanonicalDeserialize)]
+pub struct ClientSubmission {
+    /// Client, message id and time the proof is bound to.
+    pub header: SubmissionHeader,
+    /// Database epoch of the tables the proof was produced against.
+    pub epoch: u64,
+    /// Table root the proof was produced against.
+    pub root: Fq,
+    /// Hiding Pedersen commitment to the query bits.
+    pub c_d: EdwardsAffine,
+    /// Sum of the table points the query selects, `g^{s(d) + r_sum}`.
+    pub res_total: EdwardsAffine,
+    /// Groth16 proof that `res_total` was looked up honestly for `c_d`.
+    pub proof: Proof<Bls12_381>,
+}
+
+impl ClientSubmission {
+    /// Digest of the whole submission, including the proof, for logs and
+    /// receipts.
+    pub fn transcript_hash(&self) -> [u8; 32] {
+        let mut transcript = Transcript::new(b"regime-a submission id");
+        self.header.append_to(&mut transcript);
+        transcript.append_u64(b"epoch", self.epoch);
+        transcript.append_serialized(b"root", &self.root);
+        transcript.append_serialized(b"c_d", &self.c_d);
+        transcript.append_serialized(b"res_total", &self.res_total);
+        transcript.append_serialized(b"proof", &self.proof);
+        let mut digest = [0u8; 32];
+        transcript.challenge_bytes(b"id", &mut digest);
+        digest
+    }
+}
+
+/// Fiat–Shamir digest of the public statement a submission proves, binding the
+/// proof to the protocol, the parameters and the header.
+fn statement_digest(
+    params: &RegimeAParams,
+    epoch: u64,
+    header: &SubmissionHeader,
+    root: &Fq,
+    c_d: &EdwardsAffine,
+    res_total: &EdwardsAffine,
+) -> Fq {
+    let mut transcript = Transcript::new(b"regime-a submission");
+    transcript.append_u64(b"ell", params.ell as u64);
+    transcript.append_u64(b"b_chunks", params.b_chunks as u64);
+    for chunk in &params.chunks {
+        transcript.append_u64(b"len", chunk.len as u64);
+        transcript.append_u64(b"epsilon", chunk.epsilon as u64);
+    }
+    transcript.append_u64(b"epoch", epoch);
+    header.append_to(&mut transcript);
+    transcript.append_serialized(b"root", root);
+    transcript.append_serialized(b"c_d", c_d);
+    transcript.append_serialized(b"res_total", res_total);
+    transcript.challenge_field(b"statement")
+}
+
+/// Whether a query matched the database.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServerDecision {
+    /// The query is near some database entry.
+    Yes,
+    /// The query is near no database entry.
+    No,
+}
+
+/// Client logic from Regime A.
+pub fn client_submit<R: RngCore + CryptoRng>(
+    key: &ClientKey,
+    d: Vec<u8>,
+    header: SubmissionHeader,
+    rng: &mut R,
+) -> ClientSubmission {
+    let blinding = Fr::rand(rng);
+    submit_with_blinding(key, d, header, blinding, rng)
+}
+
+/// [`client_submit`] with the commitment blinding chosen by the caller.
+fn submit_with_blinding<R: RngCore + CryptoRng>(
+    key: &ClientKey,
+    d: Vec<u8>,
+    header: SubmissionHeader,
+    blinding: Fr,
+    rng: &mut R,
+) -> ClientSubmission {
+    assert_eq!(d.len(), key.params.lambda());
+    assert!(d.iter().all(|bit| *bit == 0 || *bit == 1));
+
+    let res_total = key.masked_sum(&d);
+    let c_d = key.commitment_key.commit(&d, &blinding);
+    let root = key.root();
+    let statement = statement_digest(&key.params, key.epoch, &header, &root, &c_d, &res_total);
+    let proof = prove_lookups(
+        key,
+        &key.tables,
+        &key.tree,
+        statement,
+        c_d,
+        res_total,
+        d,
+        blinding,
+        rng,
+    );
+
+    ClientSubmission {
+        header,
+        epoch: key.epoch,
+        root,
+        c_d,
+        res_total,
+        proof,
+    }
+}
+
+/// Prove that `res` is the sum of the points `d` selects from `tables`, that
+/// `tree` commits to those tables, and that `c_d` commits to `d` with
+/// `blinding`.
+fn prove_lookups<R: RngCore + CryptoRng>(
+    key: &ClientKey,
+    tables: &[Vec<EdwardsAffine>],
+    tree: &TableTree,
+    statement: Fq,
+    c_d: EdwardsAffine,
+    res: EdwardsAffine,
+    d: Vec<u8>,
+    blinding: Fr,
+    rng: &mut R,
+) -> Proof<Bls12_381> {
+    let ell = key.params.ell;
+    let indices = (0..key.params.b_chunks)
+        .map(|b| chunk_value(key.params.chunk(&d, b)))
+        .collect::<Vec<_>>();
+    let points = indices
+        .iter()
+        .enumerate()
+        .map(|(b, x)| tables[b][*x])
+        .collect::<Vec<_>>();
+    let paths = indices
+        .iter()
+        .enumerate()
+        .map(|(b, x)| tree.path((b << ell) | x))
+        .collect::<Vec<_>>();
+
+    let circuit = SubmissionCircuit {
+        ell,
+        chunk_lens: key.params.chunk_lens(),
+        key: &key.commitment_key,
+        statement,
+        root: tree.root(),
+        c_d,
+        res_total: res,
+        witness: Some(SubmissionWitness {
+            bits: d,
+            blinding,
+            points,
+            paths,
+        }),
+    };
+    Groth16::<Bls12_381>::prove(&key.proving_key, circuit, rng)
+        .expect("an honest submission satisfies the circuit")
+}
+
+/// Why the server rejected a submission.
+///
+/// The query bits, their commitment and the table lookups are all hidden
+/// behind the proof, so any inconsistency between them surfaces as
+/// [`VerifyError::ProofRejected`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum VerifyError {
+    /// The submission was made against another version of the database.
+    #[error("submission is for epoch {found}, expected {expected}")]
+    EpochMismatch {
+        /// The server's current epoch.
+        expected: u64,
+        /// Epoch named in the submission.
+        found: u64,
+    },
+    /// The submission was proven against different tables.
+    #[error("submission is for table root {found}, expected {expected}")]
+    RootMismatch {
+        /// Root of the server's tables.
+        expected: Fq,
+        /// Root named in the submission.
+        found: Fq,
+    },
+    /// `c_d` is not in the prime-order Jubjub subgroup.
+    #[error("commitment is not a point of the prime-order subgroup")]
+    InvalidCommitment,
+    /// `res_total` is not in the prime-order Jubjub subgroup.
+    #[error("result is not a point of the prime-order subgroup")]
+    InvalidResult,
+    /// The proof has points outside the BLS12-381 prime-order subgroups.
+    #[error("proof is malformed")]
+    MalformedProof,
+    /// The proof does not verify for the header, root, commitment and result.
+    #[error("proof does not verify for this submission")]
+    ProofRejected,
+}
+
+fn in_prime_order_subgroup(point: &EdwardsAffine) -> bool {
+    point.is_on_curve() && point.is_in_correct_subgroup_assuming_on_curve()
+}
+
+fn proof_well_formed(proof: &Proof<Bls12_381>) -> bool {
+    proof.a.is_on_curve()
+        && proof.a.is_in_correct_subgroup_assuming_on_curve()
+        && proof.b.is_on_curve()
+        && proof.b.is_in_correct_subgroup_assuming_on_curve()
+        && proof.c.is_on_curve()
+        && proof.c.is_in_correct_subgroup_assuming_on_curve()
+}
+
+/// Server verification and decision logic.
+///
+/// The checks run from cheapest to most expensive and stop at the first
+/// failure, which the error names anyway. The decision itself compares
+/// `res_total` with `g^{r_sum}` in constant time. See [`batch`] for verifying
+/// many submissions at once.
+pub fn server_verify_and_decide(
+    key: &ServerKey,
+    submission: &ClientSubmission,
+) -> Result<ServerDecision, VerifyError> {
+    let inputs = checked_public_inputs(key, submission)?;
+    if !Groth16::<Bls12_381>::verify(&key.verifying_key, &inputs, &submission.proof)
+        .unwrap_or(false)
+    {
+        return Err(VerifyError::ProofRejected);
+    }
+    Ok(zero_test(&key.g_r_sum(), &submission.res_total))
+}
+
+/// Every check short of the Groth16 verification, returning the proof's
+/// public inputs.
+fn checked_public_inputs(
+    key: &ServerKey,
+    submission: &ClientSubmission,
+) -> Result<Vec<Fq>, VerifyError> {
+    if submission.epoch != key.epoch {
+        return Err(VerifyError::EpochMismatch {
+            expected: key.epoch,
+            found: submission.epoch,
+        });
+    }
+    if submission.root != key.root {
+        return Err(VerifyError::RootMismatch {
+            expected: key.root,
+            found: submission.root,
+        });
+    }
+    if !in_prime_order_subgroup(&submission.c_d) {
+        return Err(VerifyError::InvalidCommitment);
+    }
+    if !in_prime_order_subgroup(&submission.res_total) {
+        return Err(VerifyError::InvalidResult);
+    }
+    if !proof_well_formed(&submission.proof) {
+        return Err(VerifyError::MalformedProof);
+    }
+
+    let statement = statement_digest(
+        &key.params,
+        submission.epoch,
+        &submission.header,
+        &submission.root,
+        &submission.c_d,
+        &submission.res_total,
+    );
+    Ok(circuit::public_inputs(
+        statement,
+        submission.root,
+        &submission.c_d,
+        &submission.res_total,
+    ))
+}
+
+impl ServerKey {
+    /// `g^{r_sum}`, what a query with every chunk far sums to.
+    fn g_r_sum(&self) -> EdwardsAffine {
+        (self.params.g * self.r_sum).into_affine()
+    }
+}
+
+/// Zero test in the exponent: every chunk far means `res_total = g^{r_sum}`.
+///
+/// The coordinates are compared without data-dependent branches, so the
+/// time taken does not reveal how much of `res_total` matched.
+fn zero_test(g_r_sum: &EdwardsAffine, res_total: &EdwardsAffine) -> ServerDecision {
+    let mut expected = Vec::with_capacity(64);
+    let mut found = Vec::with_capacity(64);
+    (g_r_sum.x, g_r_sum.y)
+        .serialize_uncompressed(&mut expected)
+        .expect("serializing into a Vec cannot fail");
+    (res_total.x, res_total.y)
+        .serialize_uncompressed(&mut found)
+        .expect("serializing into a Vec cannot fail");
+    let difference = expected
+        .iter()
+        .zip(&found)
+        .fold(0u8, |acc, (a, b)| acc | (a ^ b));
+    if std::hint::black_box(difference) != 0 {
+        ServerDecision::Yes
+    } else {
+        ServerDecision::No
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_bls12_381::G1Affine;
+    use ark_std::rand::rngs::StdRng;
+
+    fn rng() -> StdRng {
+        StdRng::seed_from_u64(0)
+    }
+
+    fn header(msgid: u64) -> SubmissionHeader {
+        SubmissionHeader {
+            client_id: ClientId([1; 32]),
+            msgid,
+            issued_at: 1_700_000_000,
+        }
+    }
+
+    #[test]
+    fn regime_a_yes_for_close_neighbor() {
+        let params = RegimeAParams::new(8, 4, 3);
+        let db = vec![vec![0; params.lambda()], vec![1; params.lambda()]];
+        let setup = TtpSetup::setup_seeded(db, params.clone(), 7);
+
+        let mut query = vec![0; params.lambda()];
+        query[0] = 1;
+        query[9] = 1;
+
+        let submission = client_submit(&setup.client_key, query, header(42), &mut rng());
+        assert_eq!(
+            server_verify_and_decide(&setup.server_key, &submission),
+            Ok(ServerDecision::Yes)
+        );
+    }
+
+    #[test]
+    fn regime_a_with_mixed_chunks() {
+        let params = RegimeAParams::with_chunks(vec![
+            ChunkParams { len: 3, epsilon: 1 },
+            ChunkParams { len: 6, epsilon: 2 },
+            ChunkParams { len: 4, epsilon: 2 },
+        ]);
+        let db = vec![vec![0; params.lambda()]];
+        let setup = TtpSetup::setup_seeded(db, params.clone(), 5);
+
+        // Only the middle chunk is close: one bit off in the first, two in
+        // the last.
+        let mut query = vec![0; params.lambda()];
+        for i in [0, 4, 9, 12] {
+            query[i] = 1;
+        }
+        let submission = client_submit(&setup.client_key, query.clone(), header(1), &mut rng());
+        assert_eq!(
+            server_verify_and_decide(&setup.server_key, &submission),
+            Ok(ServerDecision::Yes)
+        );
+
+        query[5] = 1;
+        let submission = client_submit(&setup.client_key, query, header(2), &mut rng());
+        assert_eq!(
+            server_verify_and_decide(&setup.server_key, &submission),
+            Ok(ServerDecision::No)
+        );
+    }
+
+    #[test]
+    fn seeded_setup_is_reproducible() {
+        let params = RegimeAParams::new(4, 2, 2);
+        let db = vec![vec![0; params.lambda()]];
+        let a = TtpSetup::setup_seeded(db.clone(), params.clone(), 3);
+        let b = TtpSetup::setup_seeded(db.clone(), params.clone(), 3);
+        let c = TtpSetup::setup_seeded(db, params, 4);
+        assert_eq!(a.secret.gamma, b.secret.gamma);
+        assert_eq!(a.secret.r_masks, b.secret.r_masks);
+        assert_eq!(a.client_key.root(), b.client_key.root());
+        assert_ne!(a.secret.r_masks, c.secret.r_masks);
+    }
+
+    #[test]
+    fn regime_a_no_when_every_chunk_far() {
+        let params = RegimeAParams::new(8, 4, 3);
+        let db = vec![vec![0; params.lambda()]];
+        let setup = TtpSetup::setup_seeded(db, params.clone(), 9);
+        let query = vec![1; params.lambda()];
+
+        let submission = client_submit(&setup.client_key, query, header(11), &mut rng());
+        assert_eq!(submission.res_total, params.g * setup.server_key.r_sum);
+        assert_eq!(
+            server_verify_and_decide(&setup.server_key, &submission),
+            Ok(ServerDecision::No)
+        );
+    }
+
+    #[test]
+    fn regime_a_rejects_substituted_res_total() {
+        let params = RegimeAParams::new(8, 4, 3);
+        let db = vec![vec![0; params.lambda()]];
+        let setup = TtpSetup::setup_seeded(db, params.clone(), 5);
+
+        let mut submission = client_submit(
+            &setup.client_key,
+            vec![0; params.lambda()],
+            header(3),
+            &mut rng(),
+        );
+        submission.res_total = (params.g * setup.server_key.r_sum).into_affine();
+        assert_eq!(
+            server_verify_and_decide(&setup.server_key, &submission),
+            Err(VerifyError::ProofRejected)
+        );
+    }
+
+    #[test]
+    fn regime_a_rejects_proof_for_other_msgid() {
+        let params = RegimeAParams::new(8, 4, 3);
+        let db = vec![vec![0; params.lambda()]];
+        let setup = TtpSetup::setup_seeded(db, params.clone(), 5);
+
+        let mut submission = client_submit(
+            &setup.client_key,
+            vec![0; params.lambda()],
+            header(3),
+            &mut rng(),
+        );
+        assert_eq!(
+            server_verify_and_decide(&setup.server_key, &submission),
+            Ok(ServerDecision::Yes)
+        );
+        let id = submission.transcript_hash();
+        submission.header.msgid = 4;
+        assert_ne!(submission.transcript_hash(), id);
+        assert_eq!(
+            server_verify_and_decide(&setup.server_key, &submission),
+            Err(VerifyError::ProofRejected)
+        );
+    }
+
+    #[test]
+    fn regime_a_reports_each_tampered_field() {
+        let params = RegimeAParams::new(4, 2, 2);
+        let db = vec![vec![0; params.lambda()]];
+        let setup = TtpSetup::setup_seeded(db, params.clone(), 6);
+        let honest = client_submit(
+            &setup.client_key,
+            vec![0; params.lambda()],
+            header(8),
+            &mut rng(),
+        );
+        let verify = |tamper: &dyn Fn(&mut ClientSubmission)| {
+            let mut submission = honest.clone();
+            tamper(&mut submission);
+            server_verify_and_decide(&setup.server_key, &submission)
+        };
+        // (0, -1) is on the curve but has order two.
+        let low_order = EdwardsAffine::new_unchecked(Fq::zero(), -Fq::from(1u64));
+        let off_subgroup_g1 = (1u64..)
+            .find_map(|x| {
+                G1Affine::get_point_from_x_unchecked(x.into(), false)
+                    .filter(|point| !point.is_in_correct_subgroup_assuming_on_curve())
+            })
+            .unwrap();
+
+        assert_eq!(verify(&|_| {}), Ok(ServerDecision::Yes));
+        assert_eq!(
+            verify(&|s| s.header.client_id = ClientId([2; 32])),
+            Err(VerifyError::ProofRejected)
+        );
+        assert_eq!(
+            verify(&|s| s.header.msgid += 1),
+            Err(VerifyError::ProofRejected)
+        );
+        assert_eq!(
+            verify(&|s| s.header.issued_at += 1),
+            Err(VerifyError::ProofRejected)
+        );
+        assert_eq!(
+            verify(&|s| s.root += Fq::from(1u64)),
+            Err(VerifyError::RootMismatch {
+                expected: honest.root,
+                found: honest.root + Fq::from(1u64),
+            })
+        );
+        assert_eq!(
+            verify(&|s| s.c_d = (s.c_d + params.g).into_affine()),
+            Err(VerifyError::ProofRejected)
+        );
+        assert_eq!(
+            verify(&|s| s.c_d = low_order),
+            Err(VerifyError::InvalidCommitment)
+        );
+        assert_eq!(
+            verify(&|s| s.res_total = (s.res_total + params.g).into_affine()),
+            Err(VerifyError::ProofRejected)
+        );
+        assert_eq!(
+            verify(&|s| s.res_total = low_order),
+            Err(VerifyError::InvalidResult)
+        );
+        assert_eq!(
+            verify(&|s| s.proof.a = -s.proof.a),
+            Err(VerifyError::ProofRejected)
+        );
+        assert_eq!(
+            verify(&|s| s.proof.c = off_subgroup_g1),
+            Err(VerifyError::MalformedProof)
+        );
+    }
+
+    #[test]
+    fn split_keys_agree_with_ttp_secret() {
+        let params = RegimeAParams::new(4, 2, 2);
+        let db = vec![vec![0; params.lambda()], vec![1; params.lambda()]];
+        let setup = TtpSetup::setup_seeded(db, params, 13);
+
+        assert_eq!(setup.secret.tables(), setup.client_key.tables);
+        assert_eq!(setup.server_key.root, setup.client_key.root());
+        assert_eq!(setup.server_key.r_sum, setup.secret.r_sum());
+    }
+
+    #[test]
+    fn near_points_do_not_link_across_chunks() {
+        let params = RegimeAParams::new(4, 3, 2);
+        let setup = TtpSetup::setup_seeded(vec![vec![0; params.lambda()]], params.clone(), 19);
+        let secret = &setup.secret;
+
+        // With one weight per entry, g^{gamma z_b(0)} would be the same in
+        // every chunk, tying the chunks of the entry together.
+        let offsets = (0..params.b_chunks)
+            .map(|b| setup.client_key.tables[b][0].into_group() - params.g * secret.r_masks[b])
+            .collect::<Vec<_>>();
+        for b in 1..params.b_chunks {
+            assert_ne!(offsets[b], offsets[0]);
+        }
+    }
+
+    #[test]
+    fn masked_sum_matches_direct_evaluation() {
+        let params = RegimeAParams::new(4, 3, 2);
+        let db = vec![
+            vec![0; params.lambda()],
+            vec![1; params.lambda()],
+            vec![1, 0, 1, 0, 0, 0, 1, 1, 0, 1, 0, 1],
+        ];
+        let setup = TtpSetup::setup_seeded(db, params.clone(), 17);
+        let secret = &setup.secret;
+        let params = &params;
+
+        let query = vec![1, 0, 0, 0, 0, 1, 1, 1, 0, 0, 0, 1];
+        let s = params.score(&secret.db, &secret.gamma, &query);
+        assert_eq!(
+            setup.client_key.masked_sum(&query),
+            params.g * (s + secret.r_sum())
+        );
+    }
+}
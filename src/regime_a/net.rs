@@ -0,0 +1,233 @@
+//! Reference network service for Regime A.
+//!
+//! A client sends each [`ClientSubmission`] in its [`Wire`] encoding as one
+//! frame, and the server answers every frame with one reply frame, in order,
+//! over the same connection:
+//!
+//! ```text
+//! frame    u32_le(len(payload)) ‖ payload, at most MAX_FRAME_LEN bytes
+//! reply    0 ‖ ServerDecision wire encoding
+//!        | 1 ‖ UTF-8 reason the submission was rejected
+//! ```
+//!
+//! The server decides through a [`ServerSession`], so replays are rejected
+//! across connections. Connections are handled one at a time, and one that
+//! stalls for longer than the serve timeout is dropped, so a silent client
+//! cannot hold up the others.
+
+use super::session::{ReplayStore, ServerSession};
+use super::wire::{Wire, WireError};
+use super::{ClientSubmission, ServerDecision};
+use std::io::{self, Read, Write};
+use std::net::TcpListener;
+use std::time::Duration;
+
+/// Largest accepted frame payload; submissions are well under 1 KiB.
+pub const MAX_FRAME_LEN: usize = 1 << 20;
+
+const REPLY_DECISION: u8 = 0;
+const REPLY_REJECTED: u8 = 1;
+
+/// Error exchanging Regime A messages over a stream.
+#[derive(Debug, thiserror::Error)]
+pub enum NetError {
+    /// The stream failed.
+    #[error("i/o error: {0}")]
+    Io(#[from] io::Error),
+    /// A frame did not hold the expected message.
+    #[error("bad message: {0}")]
+    Wire(#[from] WireError),
+    /// A frame announced more than [`MAX_FRAME_LEN`] bytes.
+    #[error("frame of {0} bytes is too large")]
+    FrameTooLarge(usize),
+    /// A reply frame was neither a decision nor a rejection.
+    #[error("malformed reply")]
+    BadReply,
+    /// The server rejected the submission.
+    #[error("submission rejected: {0}")]
+    Rejected(String),
+}
+
+/// Write `payload` as one frame.
+pub fn write_frame<W: Write>(mut writer: W, payload: &[u8]) -> Result<(), NetError> {
+    if payload.len() > MAX_FRAME_LEN {
+        return Err(NetError::FrameTooLarge(payload.len()));
+    }
+    writer.write_all(&(payload.len() as u32).to_le_bytes())?;
+    writer.write_all(payload)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Read one frame, or None if the stream ends cleanly before it.
+pub fn read_frame<R: Read>(mut reader: R) -> Result<Option<Vec<u8>>, NetError> {
+    let mut len = [0u8; 4];
+    let mut filled = 0;
+    while filled < len.len() {
+        match reader.read(&mut len[filled..])? {
+            0 if filled == 0 => return Ok(None),
+            0 => return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+            n => filled += n,
+        }
+    }
+    let len = u32::from_le_bytes(len) as usize;
+    if len > MAX_FRAME_LEN {
+        return Err(NetError::FrameTooLarge(len));
+    }
+    let mut payload = vec![0; len];
+    reader.read_exact(&mut payload)?;
+    Ok(Some(payload))
+}
+
+/// Answer submissions on `stream` until the client closes it.
+pub fn handle_connection<S: Read + Write, R: ReplayStore>(
+    mut stream: S,
+    session: &mut ServerSession<R>,
+) -> Result<(), NetError> {
+    while let Some(frame) = read_frame(&mut stream)? {
+        let outcome = ClientSubmission::from_bytes(&frame)
+            .map_err(|e| e.to_string())
+            .and_then(|submission| session.decide(&submission).map_err(|e| e.to_string()));
+        let reply = match outcome {
+            Ok(decision) => {
+                log::debug!("decided {:?}", decision);
+                let mut reply = vec![REPLY_DECISION];
+                reply.extend(decision.to_bytes());
+                reply
+            }
+            Err(reason) => {
+                log::debug!("rejected submission: {}", reason);
+                let mut reply = vec![REPLY_REJECTED];
+                reply.extend(reason.into_bytes());
+                reply
+            }
+        };
+        write_frame(&mut stream, &reply)?;
+    }
+    Ok(())
+}
+
+/// Serve every connection accepted on `listener`, one at a time. Only
+/// failing to accept ends the loop; a broken connection, or one where a read
+/// or write waits longer than `timeout`, is logged and dropped.
+pub fn serve_tcp<R: ReplayStore>(
+    listener: &TcpListener,
+    session: &mut ServerSession<R>,
+    timeout: Duration,
+) -> io::Result<()> {
+    loop {
+        let (stream, peer) = listener.accept()?;
+        let outcome = stream
+            .set_read_timeout(Some(timeout))
+            .and_then(|()| stream.set_write_timeout(Some(timeout)))
+            .map_err(NetError::from)
+            .and_then(|()| handle_connection(stream, session));
+        if let Err(e) = outcome {
+            log::warn!("connection from {} failed: {}", peer, e);
+        }
+    }
+}
+
+/// [`serve_tcp`] for a Unix domain socket.
+#[cfg(unix)]
+pub fn serve_unix<R: ReplayStore>(
+    listener: &std::os::unix::net::UnixListener,
+    session: &mut ServerSession<R>,
+    timeout: Duration,
+) -> io::Result<()> {
+    loop {
+        let (stream, _) = listener.accept()?;
+        let outcome = stream
+            .set_read_timeout(Some(timeout))
+            .and_then(|()| stream.set_write_timeout(Some(timeout)))
+            .map_err(NetError::from)
+            .and_then(|()| handle_connection(stream, session));
+        if let Err(e) = outcome {
+            log::warn!("connection failed: {}", e);
+        }
+    }
+}
+
+/// Send `submission` over `stream` and wait for the decision.
+pub fn submit<S: Read + Write>(
+    mut stream: S,
+    submission: &ClientSubmission,
+) -> Result<ServerDecision, NetError> {
+    write_frame(&mut stream, &submission.to_bytes())?;
+    let reply =
+        read_frame(&mut stream)?.ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+    match reply.split_first() {
+        Some((&REPLY_DECISION, decision)) => Ok(ServerDecision::from_bytes(decision)?),
+        Some((&REPLY_REJECTED, reason)) => Err(NetError::Rejected(
+            String::from_utf8_lossy(reason).into_owned(),
+        )),
+        _ => Err(NetError::BadReply),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::regime_a::{client_submit, ClientId, RegimeAParams, SubmissionHeader, TtpSetup};
+    use ark_std::rand::{rngs::StdRng, SeedableRng};
+    use std::net::TcpStream;
+
+    #[test]
+    fn frames_roundtrip_and_are_bounded() {
+        let mut stream = Vec::new();
+        write_frame(&mut stream, b"abc").unwrap();
+        write_frame(&mut stream, b"").unwrap();
+        let mut reader = stream.as_slice();
+        assert_eq!(read_frame(&mut reader).unwrap(), Some(b"abc".to_vec()));
+        assert_eq!(read_frame(&mut reader).unwrap(), Some(Vec::new()));
+        assert_eq!(read_frame(&mut reader).unwrap(), None);
+
+        let huge = ((MAX_FRAME_LEN + 1) as u32).to_le_bytes();
+        assert!(matches!(
+            read_frame(&huge[..]),
+            Err(NetError::FrameTooLarge(_))
+        ));
+        assert!(matches!(read_frame(&[1, 0][..]), Err(NetError::Io(_))));
+    }
+
+    #[test]
+    fn tcp_service_decides_and_rejects_replays() {
+        let params = RegimeAParams::new(4, 2, 2);
+        let setup = TtpSetup::setup_seeded(vec![vec![0; params.lambda()]], params.clone(), 6);
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut session = ServerSession::new(setup.server_key.clone(), Duration::from_secs(300));
+        std::thread::spawn(move || serve_tcp(&listener, &mut session, Duration::from_millis(200)));
+
+        let mut rng = StdRng::seed_from_u64(0);
+        let client_id = ClientId([3; 32]);
+        let yes = client_submit(
+            &setup.client_key,
+            vec![0, 0, 0, 1, 1, 1, 1, 1],
+            SubmissionHeader::now(client_id, 1),
+            &mut rng,
+        );
+        let no = client_submit(
+            &setup.client_key,
+            vec![1; params.lambda()],
+            SubmissionHeader::now(client_id, 2),
+            &mut rng,
+        );
+
+        // A client that never sends is dropped instead of blocking the next.
+        let _idle = TcpStream::connect(addr).unwrap();
+        let mut stream = TcpStream::connect(addr).unwrap();
+        assert_eq!(submit(&mut stream, &yes).unwrap(), ServerDecision::Yes);
+        assert_eq!(submit(&mut stream, &no).unwrap(), ServerDecision::No);
+        drop(stream);
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        assert!(matches!(
+            submit(&mut stream, &yes),
+            Err(NetError::Rejected(reason)) if reason.contains("already used")
+        ));
+        write_frame(&mut stream, b"junk").unwrap();
+        let reply = read_frame(&mut stream).unwrap().unwrap();
+        assert_eq!(reply[0], REPLY_REJECTED);
+    }
+}
@@ -0,0 +1,379 @@
+//! Encoding PDQ hashes as Regime A bit vectors.
+//!
+//! A 256-bit PDQ hash becomes 256 protocol bits, bit `i` being bit `i % 8`
+//! of byte `i / 8`, so Hamming distance is preserved. [`RegimeAParams::pdq`]
+//! splits them into 16 chunks of 16 bits and flags a chunk within distance 1.
+//! Two hashes within the usual PDQ match threshold of 31 bits cannot differ in
+//! two or more bits in every one of the 16 chunks, so every such pair is a
+//! Yes. An unrelated hash lands within distance 1 of a given 16-bit chunk with
+//! probability `17 / 2^16`, so each unrelated database entry triggers a false
+//! Yes with probability about `16 * 17 / 2^16 ≈ 0.4%`.
+//!
+//! Each hash bit is the sign of one DCT coefficient, and in hash order the
+//! chunks above are rows of the 16x16 DCT. Perturbations such as blurring,
+//! rescaling and cropping flip the high-frequency bits far more often than
+//! the low-frequency ones, so a [`PdqLayout`] can instead assign coefficients
+//! to chunks in order of increasing frequency. [`PdqLayout::frequency`] uses
+//! the 240 lowest-frequency coefficients in 15 chunks of 16 bits, flagging
+//! the lowest-frequency chunk within distance 2 and the others within
+//! distance 1. Hashes within 30 bits always match, since 31 flips are needed
+//! to push every chunk past its threshold, and an unrelated entry triggers a
+//! false Yes with probability about `(137 + 14 * 17) / 2^16 ≈ 0.57%`. On the
+//! bundled bridge transforms under blurring, rescaling, brightness, contrast,
+//! cropping and JPEG recompression it matches noticeably more perturbed
+//! copies than the row layout.
+//!
+//! PDQ is not invariant under rotation or mirroring. Enrol an image with
+//! [`dihedral_hashes`] to match its rotated and flipped copies as well.
+
+use super::{ChunkParams, RegimeAParams};
+use crate::{generate_pdq, PDQ_HASH_LENGTH};
+use image::DynamicImage;
+
+/// Number of bits in a PDQ hash.
+pub const PDQ_BITS: usize = PDQ_HASH_LENGTH * 8;
+
+/// Hamming distance at or below which PDQ hashes are conventionally treated as
+/// the same image.
+pub const PDQ_MATCH_THRESHOLD: usize = 31;
+
+/// Side length of the DCT matrix whose coefficient signs make up a PDQ hash.
+const DCT_SIZE: usize = 16;
+
+impl RegimeAParams {
+    /// Parameters for 256-bit PDQ hashes in hash order: `ell = 16`,
+    /// `b_chunks = 16`, `epsilon = 2`. See the
+    /// [module docs](crate::regime_a::pdq) for the resulting error rates.
+    pub fn pdq() -> Self {
+        Self::new(16, PDQ_BITS / 16, 2)
+    }
+}
+
+/// Protocol bits of a PDQ hash, one bit per byte.
+pub fn hash_to_bits(hash: &[u8; PDQ_HASH_LENGTH]) -> Vec<u8> {
+    (0..PDQ_BITS)
+        .map(|i| (hash[i / 8] >> (i % 8)) & 1)
+        .collect()
+}
+
+/// Protocol bits of every hash in a database.
+pub fn database_to_bits(hashes: &[[u8; PDQ_HASH_LENGTH]]) -> Vec<Vec<u8>> {
+    hashes.iter().map(hash_to_bits).collect()
+}
+
+/// Assignment of PDQ hash bits to Regime A chunks.
+///
+/// Coefficients are numbered `row * 16 + col` in the 16x16 DCT, the order in
+/// which the PDQ hasher thresholds them; coefficient `k` ends up in bit
+/// `k % 8` of byte `31 - k / 8` of the hash.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PdqLayout {
+    coefficients: Vec<usize>,
+    params: RegimeAParams,
+}
+
+impl PdqLayout {
+    /// Layout feeding protocol bit `i` from `coefficients[i]`, so that chunk
+    /// `b` takes the coefficients at the positions [`RegimeAParams::chunk`]
+    /// gives it.
+    ///
+    /// # Panics
+    ///
+    /// If the coefficients do not match `params.lambda()` in number, or are
+    /// not distinct coefficients of a PDQ hash.
+    pub fn new(coefficients: Vec<usize>, params: RegimeAParams) -> Self {
+        assert_eq!(coefficients.len(), params.lambda());
+        let mut used = [false; PDQ_BITS];
+        for &k in &coefficients {
+            assert!(k < PDQ_BITS && !used[k], "coefficient {k} is not usable");
+            used[k] = true;
+        }
+        Self {
+            coefficients,
+            params,
+        }
+    }
+
+    /// Every hash bit in hash order, so that [`PdqLayout::hash_to_bits`]
+    /// agrees with [`hash_to_bits`].
+    pub fn in_hash_order(params: RegimeAParams) -> Self {
+        let coefficients = (0..PDQ_BITS)
+            .map(|i| (PDQ_HASH_LENGTH - 1 - i / 8) * 8 + i % 8)
+            .collect();
+        Self::new(coefficients, params)
+    }
+
+    /// [`RegimeAParams::pdq`] in hash order, one DCT row per chunk.
+    pub fn rows() -> Self {
+        Self::in_hash_order(RegimeAParams::pdq())
+    }
+
+    /// Chunks filled with coefficients in zigzag order, lowest frequency
+    /// first. Coefficients beyond the total chunk length are left out.
+    pub fn by_frequency(chunks: Vec<ChunkParams>) -> Self {
+        let mut zigzag = (0..PDQ_BITS).collect::<Vec<_>>();
+        zigzag.sort_by_key(|k| {
+            let (row, col) = (k / DCT_SIZE, k % DCT_SIZE);
+            (row + col, row)
+        });
+        let params = RegimeAParams::with_chunks(chunks);
+        zigzag.truncate(params.lambda());
+        Self::new(zigzag, params)
+    }
+
+    /// Recommended layout: the 240 lowest-frequency coefficients in 15
+    /// chunks of 16 bits, the first flagged within distance 2 and the rest
+    /// within distance 1. See the [module docs](crate::regime_a::pdq) for the
+    /// resulting error rates.
+    pub fn frequency() -> Self {
+        let mut chunks = vec![
+            ChunkParams {
+                len: 16,
+                epsilon: 2
+            };
+            15
+        ];
+        chunks[0].epsilon = 3;
+        Self::by_frequency(chunks)
+    }
+
+    /// The layout of a key's parameters: [`PdqLayout::frequency`] if they
+    /// match it, otherwise hash order if they cover a whole PDQ hash.
+    pub fn for_params(params: &RegimeAParams) -> Option<Self> {
+        let frequency = Self::frequency();
+        if *params == frequency.params {
+            Some(frequency)
+        } else if params.lambda() == PDQ_BITS {
+            Some(Self::in_hash_order(params.clone()))
+        } else {
+            None
+        }
+    }
+
+    /// Parameters to set up keys with.
+    pub fn params(&self) -> &RegimeAParams {
+        &self.params
+    }
+
+    /// DCT coefficient feeding each protocol bit.
+    pub fn coefficients(&self) -> &[usize] {
+        &self.coefficients
+    }
+
+    /// Protocol bits of a PDQ hash, one bit per byte.
+    pub fn hash_to_bits(&self, hash: &[u8; PDQ_HASH_LENGTH]) -> Vec<u8> {
+        self.coefficients
+            .iter()
+            .map(|k| (hash[PDQ_HASH_LENGTH - 1 - k / 8] >> (k % 8)) & 1)
+            .collect()
+    }
+
+    /// Protocol bits of every hash in a database.
+    pub fn database_to_bits(&self, hashes: &[[u8; PDQ_HASH_LENGTH]]) -> Vec<Vec<u8>> {
+        hashes.iter().map(|hash| self.hash_to_bits(hash)).collect()
+    }
+}
+
+/// PDQ hashes of the eight rotations and reflections of `image`, starting
+/// with the image as given.
+///
+/// Returns None if the image is too small to hash.
+pub fn dihedral_hashes(image: &DynamicImage) -> Option<Vec<[u8; PDQ_HASH_LENGTH]>> {
+    let transposed = image.rotate90().fliph();
+    [
+        image.clone(),
+        image.rotate90(),
+        image.rotate180(),
+        image.rotate270(),
+        image.fliph(),
+        image.flipv(),
+        transposed.clone(),
+        transposed.rotate180(),
+    ]
+    .iter()
+    .map(|oriented| generate_pdq(oriented).map(|(hash, _quality)| hash))
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::regime_a::{
+        client_submit, server_verify_and_decide, ClientId, ServerDecision, SubmissionHeader,
+        TtpSetup,
+    };
+    use ark_std::rand::{rngs::StdRng, SeedableRng};
+    use image::{imageops::FilterType, ImageOutputFormat};
+    use std::io::Cursor;
+
+    fn distance(a: &[u8], b: &[u8]) -> usize {
+        a.iter().zip(b).filter(|(x, y)| x != y).count()
+    }
+
+    fn load(data: &[u8]) -> DynamicImage {
+        image::load_from_memory(data).unwrap()
+    }
+
+    fn bridge_hashes() -> Vec<[u8; PDQ_HASH_LENGTH]> {
+        dihedral_hashes(&load(include_bytes!("../test_data/bridge-1-original.jpg"))).unwrap()
+    }
+
+    #[test]
+    fn bits_preserve_hamming_distance() {
+        let a = [0x0fu8; PDQ_HASH_LENGTH];
+        let mut b = a;
+        b[3] ^= 0b1010_0000;
+        b[31] ^= 0b0000_0001;
+        let (a_bits, b_bits) = (hash_to_bits(&a), hash_to_bits(&b));
+        assert_eq!(a_bits.len(), RegimeAParams::pdq().lambda());
+        assert_eq!(&a_bits[..8], &[1, 1, 1, 1, 0, 0, 0, 0]);
+        assert_eq!(distance(&a_bits, &b_bits), 3);
+    }
+
+    /// Whether some chunk of `query` is within its threshold of the same
+    /// chunk of some entry, which is when the server answers Yes.
+    fn matches(params: &RegimeAParams, db: &[Vec<u8>], query: &[u8]) -> bool {
+        db.iter().any(|item| {
+            (0..params.b_chunks).any(|b| {
+                distance(params.chunk(item, b), params.chunk(query, b)) < params.chunks[b].epsilon
+            })
+        })
+    }
+
+    /// Blurred, rescaled, brightened, contrast-adjusted, cropped and
+    /// recompressed copies of `image`.
+    fn perturbations(image: &DynamicImage) -> Vec<DynamicImage> {
+        let (w, h) = (image.width(), image.height());
+        let mut copies = Vec::new();
+        for sigma in [1.0, 2.0, 3.0, 4.0] {
+            copies.push(image.blur(sigma));
+        }
+        for factor in [2, 4, 8] {
+            copies.push(image.resize_exact(w / factor, h / factor, FilterType::Triangle));
+        }
+        for value in [-60, -30, 30, 60] {
+            copies.push(image.brighten(value));
+        }
+        for contrast in [-30.0, 30.0] {
+            copies.push(image.adjust_contrast(contrast));
+        }
+        for percent in [3, 6, 10] {
+            let (dx, dy) = (w * percent / 100, h * percent / 100);
+            copies.push(image.crop_imm(dx, dy, w - 2 * dx, h - 2 * dy));
+        }
+        for quality in [10, 30] {
+            let mut jpeg = Cursor::new(Vec::new());
+            image
+                .write_to(&mut jpeg, ImageOutputFormat::Jpeg(quality))
+                .unwrap();
+            copies.push(image::load_from_memory(jpeg.get_ref()).unwrap());
+        }
+        copies
+    }
+
+    #[test]
+    fn layouts_place_coefficients() {
+        let hash = bridge_hashes()[0];
+        assert_eq!(PdqLayout::rows().hash_to_bits(&hash), hash_to_bits(&hash));
+
+        let frequency = PdqLayout::frequency();
+        assert_eq!(frequency.params().lambda(), 240);
+        assert_eq!(&frequency.coefficients()[..6], &[0, 1, 16, 2, 17, 32]);
+        let dc = (hash[PDQ_HASH_LENGTH - 1]) & 1;
+        assert_eq!(frequency.hash_to_bits(&hash)[0], dc);
+
+        assert_eq!(
+            PdqLayout::for_params(frequency.params()),
+            Some(frequency.clone())
+        );
+        assert_eq!(
+            PdqLayout::for_params(&RegimeAParams::pdq()),
+            Some(PdqLayout::rows())
+        );
+        assert_eq!(PdqLayout::for_params(&RegimeAParams::new(4, 2, 2)), None);
+    }
+
+    #[test]
+    fn frequency_layout_improves_recall_on_perturbed_bridges() {
+        let hashes = bridge_hashes();
+        let (rows, frequency) = (PdqLayout::rows(), PdqLayout::frequency());
+        let (rows_db, frequency_db) = (
+            rows.database_to_bits(&hashes),
+            frequency.database_to_bits(&hashes),
+        );
+        let (mut rows_recall, mut frequency_recall, mut total) = (0, 0, 0);
+        for data in [
+            &include_bytes!("../test_data/bridge-1-original.jpg")[..],
+            include_bytes!("../test_data/bridge-2-rotate-90.jpg"),
+            include_bytes!("../test_data/bridge-3-rotate-180.jpg"),
+            include_bytes!("../test_data/bridge-4-rotate-270.jpg"),
+            include_bytes!("../test_data/bridge-5-flipx.jpg"),
+            include_bytes!("../test_data/bridge-6-flipy.jpg"),
+            include_bytes!("../test_data/bridge-7-flip-plus-1.jpg"),
+            include_bytes!("../test_data/bridge-8-flip-minus-1.jpg"),
+        ] {
+            for copy in perturbations(&load(data)) {
+                let hash = generate_pdq(&copy).unwrap().0;
+                total += 1;
+                rows_recall += matches(rows.params(), &rows_db, &rows.hash_to_bits(&hash)) as usize;
+                frequency_recall += matches(
+                    frequency.params(),
+                    &frequency_db,
+                    &frequency.hash_to_bits(&hash),
+                ) as usize;
+            }
+        }
+        assert!(
+            frequency_recall > rows_recall,
+            "frequency layout matched {frequency_recall}/{total}, rows {rows_recall}/{total}"
+        );
+    }
+
+    #[test]
+    fn dihedral_hashes_cover_bridge_transforms() {
+        let db = database_to_bits(&bridge_hashes());
+        for data in [
+            &include_bytes!("../test_data/bridge-2-rotate-90.jpg")[..],
+            include_bytes!("../test_data/bridge-3-rotate-180.jpg"),
+            include_bytes!("../test_data/bridge-4-rotate-270.jpg"),
+            include_bytes!("../test_data/bridge-5-flipx.jpg"),
+            include_bytes!("../test_data/bridge-6-flipy.jpg"),
+            include_bytes!("../test_data/bridge-7-flip-plus-1.jpg"),
+            include_bytes!("../test_data/bridge-8-flip-minus-1.jpg"),
+        ] {
+            let query = hash_to_bits(&generate_pdq(&load(data)).unwrap().0);
+            let closest = db.iter().map(|d| distance(d, &query)).min().unwrap();
+            assert!(closest <= PDQ_MATCH_THRESHOLD, "closest entry at {closest}");
+        }
+    }
+
+    #[test]
+    fn regime_a_matches_bridge_transform_but_not_emma() {
+        let setup =
+            TtpSetup::setup_seeded(database_to_bits(&bridge_hashes()), RegimeAParams::pdq(), 3);
+        let mut rng = StdRng::seed_from_u64(0);
+        let decide = |data: &[u8], msgid: u64, rng: &mut StdRng| {
+            let query = hash_to_bits(&generate_pdq(&load(data)).unwrap().0);
+            let submission = client_submit(
+                &setup.client_key,
+                query,
+                SubmissionHeader::now(ClientId([0; 32]), msgid),
+                rng,
+            );
+            server_verify_and_decide(&setup.server_key, &submission)
+        };
+
+        assert_eq!(
+            decide(
+                include_bytes!("../test_data/bridge-2-rotate-90.jpg"),
+                1,
+                &mut rng
+            ),
+            Ok(ServerDecision::Yes)
+        );
+        assert_eq!(
+            decide(include_bytes!("../test_data/emma.jpeg"), 2, &mut rng),
+            Ok(ServerDecision::No)
+        );
+    }
+}
@@ -0,0 +1,276 @@
+//! The Regime A score polynomial over any prime field.
+//!
+//! Chunk `b` of a query `d` scores
+//!
+//! ```text
+//! s_b(x) = sum_i gamma_ib * z_b(dist(x, chunk_b(db_i))),   z_b(t) = prod_{epsilon_b <= k <= len_b} (k - t)
+//! ```
+//!
+//! at `x = chunk_b(d)`, and the server answers Yes when `sum_b s_b` is
+//! nonzero. Below `epsilon_b` every factor of `z_b` is positive, so each near
+//! chunk of entry `i` adds a positive integer of at most `len_b! < 2^62`
+//! times its own weight `gamma_ib`, whatever the lengths and thresholds of
+//! the chunks.
+//!
+//! The protocol evaluates this in the exponent of Jubjub, so its tables and
+//! masks live in the Jubjub scalar field, but nothing here depends on that:
+//! every function is generic over an arkworks [`PrimeField`], so the same
+//! scores can be computed over the BLS12-381 scalar field of the `snark`
+//! module, or any other. As long as `p` exceeds the coefficients, the score
+//! is a nonzero linear form in the weights whenever some chunk is near, so
+//! weights drawn uniformly from a field of size `p` give a wrong No with
+//! probability at most `1/p`, and the decision is the same in every large
+//! field.
+
+use super::{chunk_value, ChunkParams, RegimeAParams, ServerDecision};
+use ark_ff::PrimeField;
+
+impl RegimeAParams {
+    /// `z_b(distance)` for chunk `b`, which vanishes exactly at distances
+    /// `epsilon_b..=len_b` and is a positive integer below them.
+    pub fn z_poly<F: PrimeField>(&self, b: usize, distance: usize) -> F {
+        let ChunkParams { len, epsilon } = self.chunks[b];
+        let distance = F::from(distance as u64);
+        (epsilon..=len).fold(F::one(), |acc, t| acc * (F::from(t as u64) - distance))
+    }
+
+    /// XOR masks of weight below `epsilon_b`, with `z_b` of their weight: the
+    /// only offsets from a database chunk where `z_b` does not vanish.
+    pub(super) fn near_masks<F: PrimeField>(&self, b: usize) -> Vec<(usize, F)> {
+        let ChunkParams { len, epsilon } = self.chunks[b];
+        (0..1usize << len)
+            .filter(|mask| (mask.count_ones() as usize) < epsilon)
+            .map(|mask| (mask, self.z_poly(b, mask.count_ones() as usize)))
+            .collect()
+    }
+
+    /// `s_b(x)` for every chunk value `x` of chunk `b`, given each entry's
+    /// weight `gamma_ib` in that chunk.
+    pub fn chunk_scores<F: PrimeField>(&self, db: &[Vec<u8>], gamma_b: &[F], b: usize) -> Vec<F> {
+        assert_eq!(db.len(), gamma_b.len());
+        let size = 1usize << self.ell;
+        let mut weights = vec![F::zero(); size];
+        for (item, gamma) in db.iter().zip(gamma_b) {
+            weights[chunk_value(self.chunk(item, b))] += gamma;
+        }
+        let near = self.near_masks::<F>(b);
+
+        let mut s = vec![F::zero(); size];
+        for (y, w) in weights.iter().enumerate().filter(|(_, w)| !w.is_zero()) {
+            for (mask, z) in &near {
+                s[y ^ mask] += *w * z;
+            }
+        }
+        s
+    }
+
+    /// `sum_b s_b(chunk_b(query))`, evaluated directly from the database;
+    /// `gamma[i][b]` is the weight of entry `i` in chunk `b`.
+    pub fn score<F: PrimeField>(&self, db: &[Vec<u8>], gamma: &[Vec<F>], query: &[u8]) -> F {
+        assert_eq!(db.len(), gamma.len());
+        assert!(gamma.iter().all(|weights| weights.len() == self.b_chunks));
+        assert_eq!(query.len(), self.lambda());
+        (0..self.b_chunks)
+            .flat_map(|b| {
+                let x = chunk_value(self.chunk(query, b));
+                db.iter().zip(gamma).map(move |(item, weights)| {
+                    let y = chunk_value(self.chunk(item, b));
+                    weights[b] * self.z_poly::<F>(b, (x ^ y).count_ones() as usize)
+                })
+            })
+            .sum()
+    }
+
+    /// The decision the server reaches on `query`, computed in the clear.
+    pub fn decide_in_clear<F: PrimeField>(
+        &self,
+        db: &[Vec<u8>],
+        gamma: &[Vec<F>],
+        query: &[u8],
+    ) -> ServerDecision {
+        if self.score(db, gamma, query).is_zero() {
+            ServerDecision::No
+        } else {
+            ServerDecision::Yes
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::regime_a::{pdq::PdqLayout, TtpSetup};
+    use ark_bls12_381::{Fq as BlsFq, Fr as BlsFr};
+    use ark_ec::AffineRepr;
+    use ark_ed_on_bls12_381::Fr;
+    use ark_std::rand::{rngs::StdRng, Rng, SeedableRng};
+    use ark_std::UniformRand;
+
+    fn random_bits(lambda: usize, rng: &mut StdRng) -> Vec<u8> {
+        (0..lambda).map(|_| rng.gen_range(0..2)).collect()
+    }
+
+    /// Random weights for every entry and chunk.
+    fn random_weights<F: UniformRand>(
+        db_len: usize,
+        b_chunks: usize,
+        rng: &mut StdRng,
+    ) -> Vec<Vec<F>> {
+        (0..db_len)
+            .map(|_| (0..b_chunks).map(|_| F::rand(rng)).collect())
+            .collect()
+    }
+
+    /// Whether some chunk of `query` is within `epsilon - 1` of the same
+    /// chunk of some entry.
+    fn near(params: &RegimeAParams, db: &[Vec<u8>], query: &[u8]) -> bool {
+        (0..params.b_chunks).any(|b| {
+            db.iter().any(|item| {
+                let distance = params
+                    .chunk(item, b)
+                    .iter()
+                    .zip(params.chunk(query, b))
+                    .filter(|(x, y)| x != y)
+                    .count();
+                distance < params.chunks[b].epsilon
+            })
+        })
+    }
+
+    #[test]
+    fn decisions_agree_across_fields() {
+        let mut rng = StdRng::seed_from_u64(3);
+        for trial in 0..200 {
+            let chunks = (0..rng.gen_range(1..=4))
+                .map(|_| {
+                    let len = rng.gen_range(1..=6);
+                    ChunkParams {
+                        len,
+                        epsilon: rng.gen_range(0..=len),
+                    }
+                })
+                .collect();
+            let params = RegimeAParams::with_chunks(chunks);
+            let db = (0..rng.gen_range(1..=5))
+                .map(|_| random_bits(params.lambda(), &mut rng))
+                .collect::<Vec<_>>();
+            // Half the queries are perturbed database entries.
+            let query = if trial % 2 == 0 {
+                let mut query = db[0].clone();
+                for _ in 0..rng.gen_range(0..=params.lambda()) {
+                    query[rng.gen_range(0..params.lambda())] ^= 1;
+                }
+                query
+            } else {
+                random_bits(params.lambda(), &mut rng)
+            };
+
+            let expected = if near(&params, &db, &query) {
+                ServerDecision::Yes
+            } else {
+                ServerDecision::No
+            };
+            let jubjub = random_weights::<Fr>(db.len(), params.b_chunks, &mut rng);
+            let bls_fr = random_weights::<BlsFr>(db.len(), params.b_chunks, &mut rng);
+            let bls_fq = random_weights::<BlsFq>(db.len(), params.b_chunks, &mut rng);
+            assert_eq!(params.decide_in_clear(&db, &jubjub, &query), expected);
+            assert_eq!(params.decide_in_clear(&db, &bls_fr, &query), expected);
+            assert_eq!(params.decide_in_clear(&db, &bls_fq, &query), expected);
+        }
+    }
+
+    #[test]
+    fn mixed_thresholds_do_not_cancel() {
+        let mut rng = StdRng::seed_from_u64(7);
+        // z_0(0) = 2 * 3 and z_1(2) = 1 * 2 * 3: with opposite signs and the
+        // same weight in both chunks, this query at distance 2 would be a No.
+        let params = RegimeAParams::with_chunks(vec![
+            ChunkParams { len: 3, epsilon: 2 },
+            ChunkParams { len: 5, epsilon: 3 },
+        ]);
+        let db = vec![vec![0; 8]];
+        let query = [0, 0, 0, 1, 1, 0, 0, 0];
+        assert_eq!(params.z_poly::<Fr>(0, 0), params.z_poly::<Fr>(1, 2));
+        let gamma = [vec![Fr::rand(&mut rng); 2]];
+        assert_eq!(
+            params.decide_in_clear(&db, &gamma, &query),
+            ServerDecision::Yes
+        );
+
+        // PDQ frequency layout: one bit off in the first chunk and in one
+        // other, two in each of the remaining 13, 28 bits in all.
+        let params = PdqLayout::frequency().params().clone();
+        let db = vec![random_bits(params.lambda(), &mut rng)];
+        let mut query = db[0].clone();
+        for b in 0..params.b_chunks {
+            let start = b * 16;
+            let flips = if b < 2 { 1 } else { 2 };
+            for bit in &mut query[start..start + flips] {
+                *bit ^= 1;
+            }
+        }
+        for _ in 0..10 {
+            let gamma = random_weights::<Fr>(1, params.b_chunks, &mut rng);
+            assert_eq!(
+                params.decide_in_clear(&db, &gamma, &query),
+                ServerDecision::Yes
+            );
+        }
+    }
+
+    #[test]
+    fn chunk_scores_match_direct_evaluation() {
+        let mut rng = StdRng::seed_from_u64(4);
+        let params = RegimeAParams::with_chunks(vec![
+            ChunkParams { len: 5, epsilon: 3 },
+            ChunkParams { len: 4, epsilon: 1 },
+            ChunkParams { len: 3, epsilon: 2 },
+        ]);
+        let db = (0..4)
+            .map(|_| random_bits(params.lambda(), &mut rng))
+            .collect::<Vec<_>>();
+        let gamma = random_weights::<BlsFr>(db.len(), params.b_chunks, &mut rng);
+        let tables = (0..params.b_chunks)
+            .map(|b| {
+                let gamma_b = gamma.iter().map(|weights| weights[b]).collect::<Vec<_>>();
+                params.chunk_scores(&db, &gamma_b, b)
+            })
+            .collect::<Vec<_>>();
+        for _ in 0..50 {
+            let query = random_bits(params.lambda(), &mut rng);
+            let from_tables = (0..params.b_chunks)
+                .map(|b| tables[b][chunk_value(params.chunk(&query, b))])
+                .sum::<BlsFr>();
+            assert_eq!(from_tables, params.score(&db, &gamma, &query));
+        }
+    }
+
+    #[test]
+    fn group_decisions_match_the_clear_score() {
+        let mut rng = StdRng::seed_from_u64(5);
+        let params = RegimeAParams::with_chunks(vec![
+            ChunkParams { len: 4, epsilon: 2 },
+            ChunkParams { len: 3, epsilon: 1 },
+            ChunkParams { len: 4, epsilon: 3 },
+        ]);
+        let db = (0..3)
+            .map(|_| random_bits(params.lambda(), &mut rng))
+            .collect::<Vec<_>>();
+        let setup = TtpSetup::setup_seeded(db, params.clone(), 6);
+        let secret = &setup.secret;
+        let g_r_sum = params.g * secret.r_sum();
+        for _ in 0..30 {
+            let query = random_bits(params.lambda(), &mut rng);
+            let masked = setup.client_key.masked_sum(&query).into_group();
+            let decision = if masked == g_r_sum {
+                ServerDecision::No
+            } else {
+                ServerDecision::Yes
+            };
+            assert_eq!(
+                decision,
+                params.decide_in_clear(&secret.db, &secret.gamma, &query)
+            );
+        }
+    }
+}
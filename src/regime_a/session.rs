@@ -0,0 +1,360 @@
+//! Server-side replay protection for Regime A.
+//!
+//! Every proof is bound to a [`SubmissionHeader`]: the client id, a message
+//! id and the time it was issued. A [`ServerSession`] accepts a submission
+//! only if it was issued within the last `ttl` and its `(client_id, msgid)`
+//! pair has not been accepted before. Used pairs are kept in a
+//! [`ReplayStore`] until their submissions go stale, so the store stays
+//! bounded while every replay is still caught, either as a
+//! [`SessionError::Replay`] or as a [`SessionError::Stale`] submission.
+//!
+//! The client id is not authenticated: anyone holding the
+//! [`ClientKey`](super::ClientKey) can prove a submission under any id, and
+//! so use up another client's message ids. Deployments that need per-client
+//! ids must authenticate clients on the connection.
+
+use super::audit::AuditLog;
+use super::{
+    server_verify_and_decide, unix_time, ClientId, ClientSubmission, ServerDecision, ServerKey,
+    SubmissionHeader, VerifyError,
+};
+use std::collections::{BTreeSet, HashMap};
+use std::time::Duration;
+
+/// How far in the future a submission may be dated, to allow for clock skew.
+pub const DEFAULT_MAX_CLOCK_SKEW: Duration = Duration::from_secs(60);
+
+/// Why a session rejected a submission.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum SessionError {
+    /// The submission did not verify against the server key.
+    #[error("submission failed verification: {0}")]
+    Invalid(#[from] VerifyError),
+    /// The client already used this message id.
+    #[error("message id {msgid} was already used by this client")]
+    Replay {
+        /// The reused message id.
+        msgid: u64,
+    },
+    /// The submission was issued too long ago, or too far in the future.
+    #[error("submission issued at {issued_at} is outside the accepted window")]
+    Stale {
+        /// Issue time claimed by the submission.
+        issued_at: u64,
+    },
+    /// The decision could not be written to the audit log, so it is withheld.
+    #[error("the decision could not be recorded in the audit log")]
+    Unrecorded,
+}
+
+/// Storage for message ids a session has accepted.
+pub trait ReplayStore {
+    /// Record `(client_id, msgid)` until Unix time `expires_at`, returning
+    /// `false` if it is already recorded. Entries that expired by `now` may
+    /// be dropped.
+    fn insert(&mut self, client_id: ClientId, msgid: u64, expires_at: u64, now: u64) -> bool;
+
+    /// Forget `(client_id, msgid)`, so that it can be used again.
+    fn remove(&mut self, client_id: ClientId, msgid: u64);
+}
+
+/// In-memory [`ReplayStore`] that drops entries as they expire.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryReplayStore {
+    expiry: HashMap<(ClientId, u64), u64>,
+    by_expiry: BTreeSet<(u64, ClientId, u64)>,
+}
+
+impl MemoryReplayStore {
+    /// Empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of message ids currently remembered.
+    pub fn len(&self) -> usize {
+        self.expiry.len()
+    }
+
+    /// Whether no message ids are remembered.
+    pub fn is_empty(&self) -> bool {
+        self.expiry.is_empty()
+    }
+
+    fn purge(&mut self, now: u64) {
+        while let Some(&(expires_at, client_id, msgid)) = self.by_expiry.first() {
+            if expires_at > now {
+                break;
+            }
+            self.by_expiry.pop_first();
+            self.expiry.remove(&(client_id, msgid));
+        }
+    }
+}
+
+impl ReplayStore for MemoryReplayStore {
+    fn insert(&mut self, client_id: ClientId, msgid: u64, expires_at: u64, now: u64) -> bool {
+        self.purge(now);
+        if self.expiry.contains_key(&(client_id, msgid)) {
+            return false;
+        }
+        self.expiry.insert((client_id, msgid), expires_at);
+        self.by_expiry.insert((expires_at, client_id, msgid));
+        true
+    }
+
+    fn remove(&mut self, client_id: ClientId, msgid: u64) {
+        if let Some(expires_at) = self.expiry.remove(&(client_id, msgid)) {
+            self.by_expiry.remove(&(expires_at, client_id, msgid));
+        }
+    }
+}
+
+/// A server key together with the message ids it has accepted.
+#[derive(Debug)]
+pub struct ServerSession<S = MemoryReplayStore> {
+    key: ServerKey,
+    store: S,
+    ttl: Duration,
+    max_clock_skew: Duration,
+    audit: Option<AuditLog>,
+}
+
+impl ServerSession<MemoryReplayStore> {
+    /// Session accepting submissions issued within the last `ttl`, with an
+    /// in-memory store.
+    pub fn new(key: ServerKey, ttl: Duration) -> Self {
+        Self::with_store(key, MemoryReplayStore::new(), ttl)
+    }
+}
+
+impl<S: ReplayStore> ServerSession<S> {
+    /// Session backed by `store`.
+    pub fn with_store(key: ServerKey, store: S, ttl: Duration) -> Self {
+        Self {
+            key,
+            store,
+            ttl,
+            max_clock_skew: DEFAULT_MAX_CLOCK_SKEW,
+            audit: None,
+        }
+    }
+
+    /// Accept submissions dated up to `skew` in the future.
+    pub fn with_max_clock_skew(mut self, skew: Duration) -> Self {
+        self.max_clock_skew = skew;
+        self
+    }
+
+    /// Record a receipt of every decision in `log`.
+    pub fn with_audit_log(mut self, log: AuditLog) -> Self {
+        self.audit = Some(log);
+        self
+    }
+
+    /// The audit log, if decisions are recorded.
+    pub fn audit_log(&self) -> Option<&AuditLog> {
+        self.audit.as_ref()
+    }
+
+    /// The server key submissions are checked against.
+    pub fn key(&self) -> &ServerKey {
+        &self.key
+    }
+
+    /// The replay store.
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Verify and decide `submission`, consuming its message id.
+    pub fn decide(
+        &mut self,
+        submission: &ClientSubmission,
+    ) -> Result<ServerDecision, SessionError> {
+        self.decide_at(submission, unix_time())
+    }
+
+    /// [`decide`](Self::decide) with the current Unix time given explicitly.
+    ///
+    /// A message id is only consumed by a submission that verifies and whose
+    /// decision is returned, so invalid proofs cannot burn a client's ids,
+    /// and a submission refused as [`Unrecorded`](SessionError::Unrecorded)
+    /// can be sent again. Valid proofs under another client's id can, since
+    /// ids are not authenticated. Replays are caught before the proof is
+    /// verified, so they cost no pairings.
+    pub fn decide_at(
+        &mut self,
+        submission: &ClientSubmission,
+        now: u64,
+    ) -> Result<ServerDecision, SessionError> {
+        let SubmissionHeader {
+            client_id,
+            msgid,
+            issued_at,
+        } = submission.header;
+        let expires_at = issued_at.saturating_add(self.ttl.as_secs());
+        if expires_at <= now || issued_at > now.saturating_add(self.max_clock_skew.as_secs()) {
+            return Err(SessionError::Stale { issued_at });
+        }
+
+        if !self.store.insert(client_id, msgid, expires_at, now) {
+            return Err(SessionError::Replay { msgid });
+        }
+        let decision = match server_verify_and_decide(&self.key, submission) {
+            Ok(decision) => decision,
+            Err(e) => {
+                self.store.remove(client_id, msgid);
+                return Err(e.into());
+            }
+        };
+        if let Some(audit) = &mut self.audit {
+            if let Err(e) = audit.record(submission, decision, now) {
+                log::error!("failed to record decision for msgid {}: {}", msgid, e);
+                // The decision was withheld, so the client may retry.
+                self.store.remove(client_id, msgid);
+                return Err(SessionError::Unrecorded);
+            }
+        }
+        Ok(decision)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::regime_a::audit::{verify_log, AuditKey};
+    use crate::regime_a::{client_submit, RegimeAParams, TtpSetup};
+    use ark_std::rand::{rngs::StdRng, SeedableRng};
+
+    const NOW: u64 = 1_700_000_000;
+    const TTL: Duration = Duration::from_secs(300);
+
+    #[test]
+    fn memory_store_forgets_expired_ids() {
+        let mut store = MemoryReplayStore::new();
+        let (alice, bob) = (ClientId([1; 32]), ClientId([2; 32]));
+        assert!(store.insert(alice, 7, 100, 0));
+        assert!(!store.insert(alice, 7, 100, 50));
+        assert!(store.insert(bob, 7, 100, 50));
+        assert_eq!(store.len(), 2);
+        assert!(store.insert(alice, 7, 200, 100));
+        assert_eq!(store.len(), 1);
+        store.remove(alice, 7);
+        assert!(store.is_empty());
+        assert!(store.insert(alice, 7, 200, 100));
+    }
+
+    #[test]
+    fn session_rejects_replays_and_stale_submissions() {
+        let params = RegimeAParams::new(4, 2, 2);
+        let setup = TtpSetup::setup_seeded(vec![vec![0; params.lambda()]], params.clone(), 4);
+        let mut session = ServerSession::new(setup.server_key.clone(), TTL);
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut submit = |client: u8, msgid: u64, issued_at: u64| {
+            let header = SubmissionHeader {
+                client_id: ClientId([client; 32]),
+                msgid,
+                issued_at,
+            };
+            client_submit(
+                &setup.client_key,
+                vec![0; params.lambda()],
+                header,
+                &mut rng,
+            )
+        };
+
+        let first = submit(1, 1, NOW);
+        assert_eq!(session.decide_at(&first, NOW), Ok(ServerDecision::Yes));
+        assert_eq!(
+            session.decide_at(&first, NOW + 1),
+            Err(SessionError::Replay { msgid: 1 })
+        );
+        // Once the id has been forgotten the submission is too old anyway.
+        assert_eq!(
+            session.decide_at(&first, NOW + TTL.as_secs()),
+            Err(SessionError::Stale { issued_at: NOW })
+        );
+
+        // Message ids are per client, and bound into the proof.
+        let other_client = submit(2, 1, NOW);
+        assert_eq!(
+            session.decide_at(&other_client, NOW),
+            Ok(ServerDecision::Yes)
+        );
+        // Replays are turned away before their proof is looked at.
+        let mut replayed = first.clone();
+        replayed.proof = other_client.proof.clone();
+        assert_eq!(
+            session.decide_at(&replayed, NOW),
+            Err(SessionError::Replay { msgid: 1 })
+        );
+        let mut stolen = submit(3, 2, NOW);
+        stolen.header.client_id = ClientId([4; 32]);
+        assert_eq!(
+            session.decide_at(&stolen, NOW),
+            Err(SessionError::Invalid(VerifyError::ProofRejected))
+        );
+        stolen.header.client_id = ClientId([3; 32]);
+        assert_eq!(session.decide_at(&stolen, NOW), Ok(ServerDecision::Yes));
+        // The invalid proof did not use up client 4's id, but ids are not
+        // authenticated: anyone with the client key can prove under them.
+        let forged = submit(4, 2, NOW);
+        assert_eq!(session.decide_at(&forged, NOW), Ok(ServerDecision::Yes));
+        let own = submit(4, 2, NOW);
+        assert_eq!(
+            session.decide_at(&own, NOW),
+            Err(SessionError::Replay { msgid: 2 })
+        );
+
+        let future = submit(1, 3, NOW + 2 * DEFAULT_MAX_CLOCK_SKEW.as_secs());
+        assert_eq!(
+            session.decide_at(&future, NOW),
+            Err(SessionError::Stale {
+                issued_at: NOW + 2 * DEFAULT_MAX_CLOCK_SKEW.as_secs()
+            })
+        );
+    }
+
+    #[test]
+    fn session_records_a_receipt_per_decision() {
+        let params = RegimeAParams::new(4, 2, 2);
+        let setup = TtpSetup::setup_seeded(vec![vec![0; params.lambda()]], params.clone(), 4);
+        let mut rng = StdRng::seed_from_u64(0);
+        let key = AuditKey::generate(&mut rng);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.log");
+        let log = AuditLog::open(&path, key.clone()).unwrap();
+        let mut session = ServerSession::new(setup.server_key.clone(), TTL).with_audit_log(log);
+
+        let header = SubmissionHeader {
+            client_id: ClientId([1; 32]),
+            msgid: 1,
+            issued_at: NOW,
+        };
+        let submission = client_submit(
+            &setup.client_key,
+            vec![1; params.lambda()],
+            header,
+            &mut rng,
+        );
+        assert_eq!(session.decide_at(&submission, NOW), Ok(ServerDecision::No));
+        // Rejected submissions get no receipt.
+        assert!(session.decide_at(&submission, NOW).is_err());
+        assert_eq!(session.audit_log().unwrap().summary().len, 1);
+
+        let mut receipts = Vec::new();
+        let log = std::fs::read(&path).unwrap();
+        verify_log(log.as_slice(), &key.public_key(), |r| {
+            receipts.push(r.clone())
+        })
+        .unwrap();
+        assert_eq!(receipts.len(), 1);
+        assert!(receipts[0].covers(&submission));
+        assert_eq!(
+            (receipts[0].decision, receipts[0].timestamp),
+            (ServerDecision::No, NOW)
+        );
+    }
+}
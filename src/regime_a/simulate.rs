@@ -0,0 +1,419 @@
+//! Measuring Regime A recall and false positives on image collections.
+//!
+//! A [`Simulation`] enrols a set of original images with the TTP, then
+//! submits every [`Perturbation`] of the originals and of a set of unrelated
+//! images through [`client_submit`] and [`server_verify_and_decide`]. Yes
+//! decisions on perturbed originals are true positives and Yes decisions on
+//! unrelated images are false positives; the [`SimulationReport`] gives both
+//! rates per perturbation, next to the false positive rate
+//! [`analysis`](super::analysis) predicts for random hashes.
+//!
+//! Proving dominates the cost of a run. With [`Simulation::in_clear`] set, the
+//! score is evaluated directly from the database instead, which reaches the
+//! same decisions except with probability about `2^-251` per query and skips
+//! the key setup as well.
+
+use super::pdq::{dihedral_hashes, PdqLayout, PDQ_MATCH_THRESHOLD};
+use super::{
+    client_submit, entry_weights, server_verify_and_decide, ClientId, ServerDecision,
+    SubmissionHeader, TtpSetup, VerifyError,
+};
+use crate::{generate_pdq, PDQ_HASH_LENGTH};
+use ark_ed_on_bls12_381::Fr;
+use ark_std::rand::{CryptoRng, RngCore};
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageOutputFormat};
+use std::fmt;
+use std::io::Cursor;
+use std::str::FromStr;
+
+/// An edit applied to an image before it is hashed and submitted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Perturbation {
+    /// The image as given.
+    None,
+    /// Re-encode as JPEG at this quality, from 1 to 100.
+    Jpeg(u8),
+    /// Scale both sides to this percentage of their size.
+    Resize(u32),
+    /// Cut this percentage of the width and height off every side.
+    Crop(u32),
+    /// Add this value to every channel.
+    Brighten(i32),
+}
+
+impl Perturbation {
+    /// The perturbations the CLI runs by default.
+    pub fn standard() -> Vec<Self> {
+        vec![
+            Self::None,
+            Self::Jpeg(30),
+            Self::Jpeg(10),
+            Self::Resize(50),
+            Self::Resize(25),
+            Self::Crop(5),
+            Self::Crop(10),
+            Self::Brighten(30),
+            Self::Brighten(-30),
+        ]
+    }
+
+    /// Apply the edit to a copy of `image`.
+    pub fn apply(&self, image: &DynamicImage) -> DynamicImage {
+        let (w, h) = (image.width(), image.height());
+        match *self {
+            Self::None => image.clone(),
+            Self::Jpeg(quality) => {
+                let mut jpeg = Cursor::new(Vec::new());
+                DynamicImage::ImageRgb8(image.to_rgb8())
+                    .write_to(&mut jpeg, ImageOutputFormat::Jpeg(quality))
+                    .expect("encoding RGB to memory cannot fail");
+                image::load_from_memory(jpeg.get_ref()).expect("the encoder wrote a valid JPEG")
+            }
+            Self::Resize(percent) => {
+                let scale = |side: u32| (side as u64 * percent as u64 / 100).max(1) as u32;
+                image.resize_exact(scale(w), scale(h), FilterType::Triangle)
+            }
+            Self::Crop(percent) => {
+                let (dx, dy) = (w * percent / 100, h * percent / 100);
+                image.crop_imm(dx, dy, w - 2 * dx, h - 2 * dy)
+            }
+            Self::Brighten(value) => image.brighten(value),
+        }
+    }
+}
+
+impl fmt::Display for Perturbation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::None => write!(f, "none"),
+            Self::Jpeg(quality) => write!(f, "jpeg:{quality}"),
+            Self::Resize(percent) => write!(f, "resize:{percent}"),
+            Self::Crop(percent) => write!(f, "crop:{percent}"),
+            Self::Brighten(value) => write!(f, "brighten:{value}"),
+        }
+    }
+}
+
+/// A perturbation spec that [`Perturbation::from_str`] does not accept.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error(
+    "invalid perturbation {0:?}: expected none, jpeg:1-100, resize:1-400, crop:0-49 or brighten:N"
+)]
+pub struct ParsePerturbationError(String);
+
+impl FromStr for Perturbation {
+    type Err = ParsePerturbationError;
+
+    /// Parse the form [`Display`](fmt::Display) writes, such as `jpeg:30`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ParsePerturbationError(s.to_string());
+        if s == "none" {
+            return Ok(Self::None);
+        }
+        let (kind, value) = s.split_once(':').ok_or_else(err)?;
+        let perturbation = match kind {
+            "jpeg" => Self::Jpeg(value.parse().map_err(|_| err())?),
+            "resize" => Self::Resize(value.parse().map_err(|_| err())?),
+            "crop" => Self::Crop(value.parse().map_err(|_| err())?),
+            "brighten" => Self::Brighten(value.parse().map_err(|_| err())?),
+            _ => return Err(err()),
+        };
+        let valid = match perturbation {
+            Self::Jpeg(quality) => (1..=100).contains(&quality),
+            Self::Resize(percent) => (1..=400).contains(&percent),
+            Self::Crop(percent) => percent < 50,
+            Self::None | Self::Brighten(_) => true,
+        };
+        valid.then_some(perturbation).ok_or_else(err)
+    }
+}
+
+/// Why a simulation could not run to completion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum SimulationError {
+    /// None of the originals could be hashed.
+    #[error("no original image could be hashed")]
+    EmptyDatabase,
+    /// The server rejected an honestly generated submission.
+    #[error("server rejected an honest submission: {0}")]
+    Rejected(#[from] VerifyError),
+}
+
+/// Decision counts for one perturbation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PerturbationReport {
+    /// The edit applied to every query image.
+    pub perturbation: Perturbation,
+    /// Perturbed originals answered Yes.
+    pub true_positives: usize,
+    /// Perturbed originals submitted.
+    pub positives: usize,
+    /// Perturbed unrelated images answered Yes.
+    pub false_positives: usize,
+    /// Perturbed unrelated images submitted.
+    pub negatives: usize,
+    /// Perturbed images too small to hash, which were not submitted.
+    pub skipped: usize,
+}
+
+impl PerturbationReport {
+    /// Fraction of perturbed originals answered Yes, NaN if there were none.
+    pub fn true_positive_rate(&self) -> f64 {
+        self.true_positives as f64 / self.positives as f64
+    }
+
+    /// Fraction of perturbed unrelated images answered Yes, NaN if there were
+    /// none.
+    pub fn false_positive_rate(&self) -> f64 {
+        self.false_positives as f64 / self.negatives as f64
+    }
+}
+
+/// Outcome of a [`Simulation`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct SimulationReport {
+    /// Number of database entries the originals were enrolled as.
+    pub database_entries: usize,
+    /// False positive rate [`analysis`](super::analysis) predicts for a
+    /// uniformly random hash against the database.
+    pub predicted_false_positive_rate: f64,
+    /// Counts for each perturbation, in the order they were given.
+    pub perturbations: Vec<PerturbationReport>,
+}
+
+impl fmt::Display for SimulationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<16} {:>12} {:>8} {:>12} {:>8} {:>8}",
+            "perturbation", "yes/orig", "tpr", "yes/other", "fpr", "skipped"
+        )?;
+        for row in &self.perturbations {
+            writeln!(
+                f,
+                "{:<16} {:>12} {:>8.3} {:>12} {:>8.3} {:>8}",
+                row.perturbation.to_string(),
+                format!("{}/{}", row.true_positives, row.positives),
+                row.true_positive_rate(),
+                format!("{}/{}", row.false_positives, row.negatives),
+                row.false_positive_rate(),
+                row.skipped
+            )?;
+        }
+        writeln!(
+            f,
+            "{} database entries, predicted fpr for random hashes {:.4}",
+            self.database_entries, self.predicted_false_positive_rate
+        )
+    }
+}
+
+/// Settings for a simulated deployment.
+#[derive(Clone, Debug)]
+pub struct Simulation {
+    /// Assignment of hash bits to chunks, which also fixes the parameters.
+    pub layout: PdqLayout,
+    /// Edits applied to every query image.
+    pub perturbations: Vec<Perturbation>,
+    /// Enrol the eight rotations and reflections of each original.
+    pub dihedral: bool,
+    /// Evaluate the score directly instead of proving each submission.
+    pub in_clear: bool,
+}
+
+impl Default for Simulation {
+    /// The recommended layout and the standard perturbations, with every
+    /// submission proved.
+    fn default() -> Self {
+        Self {
+            layout: PdqLayout::frequency(),
+            perturbations: Perturbation::standard(),
+            dihedral: false,
+            in_clear: false,
+        }
+    }
+}
+
+/// What answers the submissions: a full deployment, or the score itself.
+enum Decider {
+    Protocol(Box<TtpSetup>),
+    InClear {
+        db: Vec<Vec<u8>>,
+        gamma: Vec<Vec<Fr>>,
+    },
+}
+
+impl Simulation {
+    /// Enrol `originals` and submit every perturbation of them and of
+    /// `unrelated`.
+    pub fn run<R: RngCore + CryptoRng>(
+        &self,
+        originals: &[DynamicImage],
+        unrelated: &[DynamicImage],
+        rng: &mut R,
+    ) -> Result<SimulationReport, SimulationError> {
+        let params = self.layout.params();
+        let hashes = originals
+            .iter()
+            .flat_map(|image| {
+                let hashes = if self.dihedral {
+                    dihedral_hashes(image)
+                } else {
+                    generate_pdq(image).map(|(hash, _quality)| vec![hash])
+                };
+                if hashes.is_none() {
+                    log::warn!("skipping an original too small to hash");
+                }
+                hashes.unwrap_or_default()
+            })
+            .collect::<Vec<_>>();
+        if hashes.is_empty() {
+            return Err(SimulationError::EmptyDatabase);
+        }
+        let db = self.layout.database_to_bits(&hashes);
+        let database_entries = db.len();
+        let decider = if self.in_clear {
+            let gamma = db.iter().map(|_| entry_weights(params, rng)).collect();
+            Decider::InClear { db, gamma }
+        } else {
+            Decider::Protocol(Box::new(TtpSetup::setup(db, params.clone(), rng)))
+        };
+
+        let mut msgid = 0;
+        let mut decide = |hash: &[u8; PDQ_HASH_LENGTH], rng: &mut R| {
+            let query = self.layout.hash_to_bits(hash);
+            msgid += 1;
+            match &decider {
+                Decider::Protocol(setup) => {
+                    let header = SubmissionHeader::now(ClientId([0; 32]), msgid);
+                    let submission = client_submit(&setup.client_key, query, header, rng);
+                    server_verify_and_decide(&setup.server_key, &submission)
+                }
+                Decider::InClear { db, gamma } => Ok(params.decide_in_clear(db, gamma, &query)),
+            }
+        };
+
+        let mut perturbations = Vec::with_capacity(self.perturbations.len());
+        for &perturbation in &self.perturbations {
+            let mut row = PerturbationReport {
+                perturbation,
+                true_positives: 0,
+                positives: 0,
+                false_positives: 0,
+                negatives: 0,
+                skipped: 0,
+            };
+            for (images, original) in [(originals, true), (unrelated, false)] {
+                for image in images {
+                    let Some((hash, _quality)) = generate_pdq(&perturbation.apply(image)) else {
+                        row.skipped += 1;
+                        continue;
+                    };
+                    let yes = decide(&hash, rng)? == ServerDecision::Yes;
+                    if original {
+                        row.positives += 1;
+                        row.true_positives += yes as usize;
+                    } else {
+                        row.negatives += 1;
+                        row.false_positives += yes as usize;
+                    }
+                }
+            }
+            log::info!(
+                "{perturbation}: {}/{} originals, {}/{} unrelated matched",
+                row.true_positives,
+                row.positives,
+                row.false_positives,
+                row.negatives
+            );
+            perturbations.push(row);
+        }
+
+        Ok(SimulationReport {
+            database_entries,
+            predicted_false_positive_rate: params
+                .analyze(database_entries, PDQ_MATCH_THRESHOLD)
+                .false_positive,
+            perturbations,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_std::rand::{rngs::StdRng, SeedableRng};
+
+    fn load(data: &[u8]) -> DynamicImage {
+        image::load_from_memory(data).unwrap()
+    }
+
+    fn bridge() -> DynamicImage {
+        load(include_bytes!("../test_data/bridge-1-original.jpg"))
+    }
+
+    fn emma() -> DynamicImage {
+        load(include_bytes!("../test_data/emma.jpeg"))
+    }
+
+    #[test]
+    fn perturbations_parse_what_they_display() {
+        for perturbation in Perturbation::standard() {
+            assert_eq!(perturbation.to_string().parse(), Ok(perturbation));
+        }
+        assert_eq!("crop:0".parse(), Ok(Perturbation::Crop(0)));
+        for bad in [
+            "", "jpeg", "jpeg:0", "jpeg:101", "resize:0", "crop:50", "blur:2",
+        ] {
+            assert!(bad.parse::<Perturbation>().is_err(), "{bad} parsed");
+        }
+
+        let image = bridge();
+        let (w, h) = (image.width(), image.height());
+        let resized = Perturbation::Resize(50).apply(&image);
+        assert_eq!((resized.width(), resized.height()), (w / 2, h / 2));
+        let cropped = Perturbation::Crop(10).apply(&image);
+        assert_eq!(cropped.width(), w - 2 * (w / 10));
+    }
+
+    #[test]
+    fn in_clear_simulation_separates_bridge_from_emma() {
+        let simulation = Simulation {
+            dihedral: true,
+            in_clear: true,
+            ..Simulation::default()
+        };
+        let report = simulation
+            .run(&[bridge()], &[emma()], &mut StdRng::seed_from_u64(0))
+            .unwrap();
+        assert_eq!(report.database_entries, 8);
+        assert_eq!(report.perturbations.len(), Perturbation::standard().len());
+        let unperturbed = &report.perturbations[0];
+        assert_eq!(unperturbed.true_positive_rate(), 1.0);
+        assert_eq!(unperturbed.false_positive_rate(), 0.0);
+        for row in &report.perturbations {
+            assert_eq!((row.positives, row.negatives, row.skipped), (1, 1, 0));
+        }
+        assert!(report.predicted_false_positive_rate < 0.05);
+        assert!(report.to_string().starts_with("perturbation"));
+    }
+
+    #[test]
+    fn protocol_simulation_matches_in_clear() {
+        let mut simulation = Simulation {
+            perturbations: vec![Perturbation::None, Perturbation::Jpeg(30)],
+            ..Simulation::default()
+        };
+        let (originals, unrelated) = ([bridge()], [emma()]);
+        let proved = simulation
+            .run(&originals, &unrelated, &mut StdRng::seed_from_u64(1))
+            .unwrap();
+        simulation.in_clear = true;
+        let in_clear = simulation
+            .run(&originals, &unrelated, &mut StdRng::seed_from_u64(1))
+            .unwrap();
+        assert_eq!(proved, in_clear);
+        assert_eq!(proved.perturbations[0].true_positives, 1);
+    }
+}
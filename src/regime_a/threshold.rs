@@ -0,0 +1,525 @@
+//! Running the Regime A setup as `k` of `n` parties.
+//!
+//! [`TtpSetup::setup`](super::TtpSetup::setup) trusts a single party with the
+//! weights `gamma` and the masks `r_b`. Here they are Shamir-shared among `n`
+//! parties without a dealer: every party deals random sharings
+//! ([`Party::deal`]) and each party adds up the sub-shares addressed to it
+//! ([`Party::new`]). Each `gamma_ib` and `r_b` is the sum of every dealer's
+//! contribution, so it stays uniform and unknown to all parties as long as
+//! one dealer is honest (`gamma_ib` is then zero only with probability
+//! `1 / |Fr|`).
+//!
+//! The tables are linear in the weights and masks, so every party publishes
+//! its share of them in the exponent ([`Party::table_share`]) and any `k`
+//! table shares interpolate to the real tables ([`combine_tables`]). The
+//! parties send their shares of `r_sum` to the server alone, which
+//! interpolates it with [`combine_r_sum`].
+//!
+//! Nothing here is verifiable: dealings carry no commitments, so a faulty
+//! dealer or party yields wrong tables or a wrong `r_sum`. Table shares
+//! beyond the first `k` are checked against the interpolated tables, which
+//! detects, but does not locate, a table share that disagrees with the rest.
+//!
+//! The parties all know the database; only the weights and masks are hidden
+//! from them. The Groth16 keys depend on neither and come from
+//! [`proof_keys`](super::proof_keys), ideally run as a separate ceremony.
+//! Incremental [`update`](super::update)s still need a single TTP.
+
+use super::{RegimeAParams, TtpSecret};
+use ark_ec::{AffineRepr, CurveGroup};
+use ark_ed_on_bls12_381::{EdwardsAffine, EdwardsProjective, Fr};
+use ark_ff::{Field, Zero};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use ark_std::rand::{CryptoRng, RngCore};
+use ark_std::UniformRand;
+use std::collections::{BTreeSet, HashMap};
+
+/// `k` of `n`: how many parties hold shares and how many are needed to
+/// combine them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Threshold {
+    /// Shares needed to reconstruct.
+    pub k: usize,
+    /// Number of parties.
+    pub n: usize,
+}
+
+impl Threshold {
+    /// Panics unless `1 <= k <= n < 2^32`.
+    pub fn new(k: usize, n: usize) -> Self {
+        assert!(1 <= k && k <= n && n < u32::MAX as usize);
+        Self { k, n }
+    }
+
+    /// Party indices, starting at 1 since 0 is where the secret sits.
+    fn indices(&self) -> impl Iterator<Item = u32> {
+        1..=self.n as u32
+    }
+}
+
+/// Why shares could not be used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum ThresholdError {
+    /// Fewer shares than the threshold.
+    #[error("need {needed} shares, got {found}")]
+    TooFewShares {
+        /// Shares required.
+        needed: usize,
+        /// Shares given.
+        found: usize,
+    },
+    /// A party index that is out of range, repeated, or addressed to
+    /// someone else.
+    #[error("unexpected party index {0}")]
+    BadIndex(u32),
+    /// A share or database entry that does not match the parameters.
+    #[error("share does not match the parameters")]
+    Malformed,
+    /// The table share of this party does not lie on the polynomial through
+    /// the first `k`, so at least one of them is wrong.
+    #[error("table share {0} disagrees with the others")]
+    Inconsistent(u32),
+}
+
+/// The value at `index` of a random polynomial of degree `k - 1` whose
+/// constant term is the secret.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, CanonicalSerialize, CanonicalDeserialize)]
+pub struct Share {
+    /// Party index, at least 1.
+    pub index: u32,
+    /// Share of the secret.
+    pub value: Fr,
+}
+
+/// Split `secret` into one share per party.
+pub fn share<R: RngCore + CryptoRng>(secret: Fr, threshold: Threshold, rng: &mut R) -> Vec<Share> {
+    let coefficients = std::iter::once(secret)
+        .chain((1..threshold.k).map(|_| Fr::rand(rng)))
+        .collect::<Vec<_>>();
+    threshold
+        .indices()
+        .map(|index| {
+            let x = Fr::from(index);
+            let value = coefficients
+                .iter()
+                .rev()
+                .fold(Fr::zero(), |acc, c| acc * x + c);
+            Share { index, value }
+        })
+        .collect()
+}
+
+/// Recover a secret from `threshold.k` or more shares.
+pub fn reconstruct(threshold: Threshold, shares: &[Share]) -> Result<Fr, ThresholdError> {
+    let indices = shares.iter().map(|s| s.index).collect::<Vec<_>>();
+    let lambdas = lagrange_at_zero(threshold, &indices)?;
+    Ok(shares.iter().zip(lambdas).map(|(s, l)| s.value * l).sum())
+}
+
+/// Lagrange coefficients interpolating the value at 0 from `indices`.
+fn lagrange_at_zero(threshold: Threshold, indices: &[u32]) -> Result<Vec<Fr>, ThresholdError> {
+    check_indices(threshold, indices)?;
+    Ok(lagrange_at(Fr::zero(), indices))
+}
+
+/// At least `threshold.k` distinct indices, all in range.
+fn check_indices(threshold: Threshold, indices: &[u32]) -> Result<(), ThresholdError> {
+    if indices.len() < threshold.k {
+        return Err(ThresholdError::TooFewShares {
+            needed: threshold.k,
+            found: indices.len(),
+        });
+    }
+    let mut seen = BTreeSet::new();
+    if let Some(bad) = indices
+        .iter()
+        .find(|i| **i == 0 || **i as usize > threshold.n || !seen.insert(**i))
+    {
+        return Err(ThresholdError::BadIndex(*bad));
+    }
+    Ok(())
+}
+
+/// Lagrange coefficients interpolating the value at `x` from the distinct
+/// `indices`.
+fn lagrange_at(x: Fr, indices: &[u32]) -> Vec<Fr> {
+    indices
+        .iter()
+        .map(|j| {
+            let x_j = Fr::from(*j);
+            let (num, den) = indices.iter().filter(|m| *m != j).fold(
+                (Fr::from(1u64), Fr::from(1u64)),
+                |(num, den), m| {
+                    let x_m = Fr::from(*m);
+                    (num * (x - x_m), den * (x_j - x_m))
+                },
+            );
+            num * den.inverse().expect("indices are distinct")
+        })
+        .collect()
+}
+
+/// One dealer's sub-shares of fresh weights and masks for one recipient.
+#[derive(Clone, Debug, PartialEq, Eq, CanonicalSerialize, CanonicalDeserialize)]
+pub struct Dealing {
+    /// Index of the dealing party.
+    pub dealer: u32,
+    /// Index of the party the dealing is for.
+    pub recipient: u32,
+    /// Sub-shares of the weights, per entry and chunk.
+    pub gamma: Vec<Vec<Fr>>,
+    /// Sub-shares of the per-chunk masks.
+    pub r_masks: Vec<Fr>,
+}
+
+/// A party's share of the masked tables, `g^{[s_b(x) + r_b]_j}`.
+#[derive(Clone, Debug, PartialEq, Eq, CanonicalSerialize, CanonicalDeserialize)]
+pub struct TableShare {
+    /// Index of the party.
+    pub index: u32,
+    /// Table shares, shaped like [`ClientKey::tables`](super::ClientKey::tables).
+    pub tables: Vec<Vec<EdwardsAffine>>,
+}
+
+/// One of the `n` setup parties, holding shares of every weight and mask.
+#[derive(Clone, Debug)]
+pub struct Party {
+    index: u32,
+    /// The database with the party's shares in place of the weights and masks.
+    shares: TtpSecret,
+}
+
+impl Party {
+    /// Dealings from party `dealer` for a database of `db_len` entries, one
+    /// per party in index order. Send each to its recipient privately.
+    pub fn deal<R: RngCore + CryptoRng>(
+        dealer: u32,
+        params: &RegimeAParams,
+        db_len: usize,
+        threshold: Threshold,
+        rng: &mut R,
+    ) -> Vec<Dealing> {
+        let gamma = (0..db_len)
+            .map(|_| {
+                (0..params.b_chunks)
+                    .map(|_| share(Fr::rand(rng), threshold, rng))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let r_masks = (0..params.b_chunks)
+            .map(|_| share(Fr::rand(rng), threshold, rng))
+            .collect::<Vec<_>>();
+        threshold
+            .indices()
+            .zip(0..)
+            .map(|(recipient, j)| Dealing {
+                dealer,
+                recipient,
+                gamma: gamma
+                    .iter()
+                    .map(|entry| entry.iter().map(|shares| shares[j].value).collect())
+                    .collect(),
+                r_masks: r_masks.iter().map(|shares| shares[j].value).collect(),
+            })
+            .collect()
+    }
+
+    /// Party `index`, from the dealings of all `n` parties addressed to it.
+    pub fn new(
+        index: u32,
+        threshold: Threshold,
+        params: RegimeAParams,
+        db: Vec<Vec<u8>>,
+        dealings: &[Dealing],
+    ) -> Result<Self, ThresholdError> {
+        let lambda = params.lambda();
+        if db
+            .iter()
+            .any(|d| d.len() != lambda || d.iter().any(|bit| *bit > 1))
+        {
+            return Err(ThresholdError::Malformed);
+        }
+        if index == 0 || index as usize > threshold.n {
+            return Err(ThresholdError::BadIndex(index));
+        }
+        if dealings.len() != threshold.n {
+            return Err(ThresholdError::TooFewShares {
+                needed: threshold.n,
+                found: dealings.len(),
+            });
+        }
+        let mut dealers = BTreeSet::new();
+        let mut gamma = vec![vec![Fr::zero(); params.b_chunks]; db.len()];
+        let mut r_masks = vec![Fr::zero(); params.b_chunks];
+        for dealing in dealings {
+            if dealing.recipient != index
+                || dealing.dealer == 0
+                || dealing.dealer as usize > threshold.n
+                || !dealers.insert(dealing.dealer)
+            {
+                return Err(ThresholdError::BadIndex(dealing.dealer));
+            }
+            if dealing.gamma.len() != db.len()
+                || dealing.gamma.iter().any(|sub| sub.len() != params.b_chunks)
+                || dealing.r_masks.len() != params.b_chunks
+            {
+                return Err(ThresholdError::Malformed);
+            }
+            for (sum, sub) in gamma
+                .iter_mut()
+                .flatten()
+                .zip(dealing.gamma.iter().flatten())
+            {
+                *sum += sub;
+            }
+            for (sum, sub) in r_masks.iter_mut().zip(&dealing.r_masks) {
+                *sum += sub;
+            }
+        }
+        Ok(Self {
+            index,
+            shares: TtpSecret {
+                params,
+                db,
+                gamma,
+                r_masks,
+            },
+        })
+    }
+
+    /// This party's index.
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    /// This party's share of the tables, safe to publish.
+    pub fn table_share(&self) -> TableShare {
+        TableShare {
+            index: self.index,
+            tables: self.shares.tables(),
+        }
+    }
+
+    /// This party's share of `r_sum`, for the server only.
+    pub fn r_sum_share(&self) -> Share {
+        Share {
+            index: self.index,
+            value: self.shares.r_sum(),
+        }
+    }
+}
+
+/// The masked tables, interpolated in the exponent from the first
+/// `threshold.k` shares. Every further share is checked against them, at the
+/// cost of one more interpolation each.
+pub fn combine_tables(
+    params: &RegimeAParams,
+    threshold: Threshold,
+    shares: &[TableShare],
+) -> Result<Vec<Vec<EdwardsAffine>>, ThresholdError> {
+    check_indices(
+        threshold,
+        &shares.iter().map(|s| s.index).collect::<Vec<_>>(),
+    )?;
+    let well_formed = shares.iter().all(|share| {
+        share.tables.len() == params.b_chunks
+            && share.tables.iter().all(|t| t.len() == 1 << params.ell)
+    });
+    if !well_formed {
+        return Err(ThresholdError::Malformed);
+    }
+
+    let (basis, extra) = shares.split_at(threshold.k);
+    let indices = basis.iter().map(|s| s.index).collect::<Vec<_>>();
+    let tables = interpolate(params, basis, &lagrange_at(Fr::zero(), &indices));
+    for share in extra {
+        let expected = interpolate(params, basis, &lagrange_at(Fr::from(share.index), &indices));
+        if expected != share.tables {
+            return Err(ThresholdError::Inconsistent(share.index));
+        }
+    }
+    Ok(tables)
+}
+
+/// `Σ_j coefficients[j] · shares[j]`, table entry by table entry.
+fn interpolate(
+    params: &RegimeAParams,
+    shares: &[TableShare],
+    coefficients: &[Fr],
+) -> Vec<Vec<EdwardsAffine>> {
+    // Most entries of a share are its `g^{[r_b]_j}`, so the same column of
+    // points recurs across a chunk and is combined once.
+    let mut combined = HashMap::new();
+    (0..params.b_chunks)
+        .map(|b| {
+            (0..1usize << params.ell)
+                .map(|x| {
+                    let column = shares.iter().map(|s| s.tables[b][x]).collect::<Vec<_>>();
+                    *combined.entry(column).or_insert_with_key(|column| {
+                        column
+                            .iter()
+                            .zip(coefficients)
+                            .map(|(point, l)| point.into_group() * l)
+                            .sum::<EdwardsProjective>()
+                            .into_affine()
+                    })
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// `r_sum`, interpolated by the server from the parties' shares.
+pub fn combine_r_sum(threshold: Threshold, shares: &[Share]) -> Result<Fr, ThresholdError> {
+    reconstruct(threshold, shares)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::regime_a::{
+        client_submit, proof_keys, server_verify_and_decide, ClientId, ClientKey, ServerDecision,
+        ServerKey, SubmissionHeader,
+    };
+    use ark_std::rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn any_k_shares_reconstruct() {
+        let threshold = Threshold::new(3, 5);
+        let mut rng = StdRng::seed_from_u64(0);
+        let secret = Fr::rand(&mut rng);
+        let shares = share(secret, threshold, &mut rng);
+        for subset in [[0, 1, 2], [0, 2, 4], [4, 3, 1]] {
+            let chosen = subset.map(|i| shares[i]);
+            assert_eq!(reconstruct(threshold, &chosen), Ok(secret));
+        }
+        assert_eq!(reconstruct(threshold, &shares), Ok(secret));
+        assert_eq!(
+            reconstruct(threshold, &shares[..2]),
+            Err(ThresholdError::TooFewShares {
+                needed: 3,
+                found: 2
+            })
+        );
+        assert_eq!(
+            reconstruct(threshold, &[shares[0], shares[1], shares[0]]),
+            Err(ThresholdError::BadIndex(1))
+        );
+    }
+
+    #[test]
+    fn k_of_n_setup_matches_a_single_ttp() {
+        let params = RegimeAParams::new(4, 2, 2);
+        let threshold = Threshold::new(3, 5);
+        let db = vec![vec![0; params.lambda()], vec![1; params.lambda()]];
+        let mut rng = StdRng::seed_from_u64(1);
+
+        let dealings = threshold
+            .indices()
+            .map(|dealer| Party::deal(dealer, &params, db.len(), threshold, &mut rng))
+            .collect::<Vec<_>>();
+        let parties = threshold
+            .indices()
+            .zip(0..)
+            .map(|(index, j)| {
+                let inbox = dealings.iter().map(|d| d[j].clone()).collect::<Vec<_>>();
+                Party::new(index, threshold, params.clone(), db.clone(), &inbox).unwrap()
+            })
+            .collect::<Vec<_>>();
+        assert!(matches!(
+            Party::new(1, threshold, params.clone(), db.clone(), &dealings[0]),
+            Err(ThresholdError::BadIndex(1))
+        ));
+        let inbox = dealings.iter().map(|d| d[0].clone()).collect::<Vec<_>>();
+        assert!(matches!(
+            Party::new(1, threshold, params.clone(), vec![vec![2; 8]; 2], &inbox),
+            Err(ThresholdError::Malformed)
+        ));
+
+        let table_shares = parties.iter().map(Party::table_share).collect::<Vec<_>>();
+        let tables = combine_tables(&params, threshold, &table_shares).unwrap();
+        let others = [&table_shares[4], &table_shares[1], &table_shares[3]].map(Clone::clone);
+        assert_eq!(
+            combine_tables(&params, threshold, &others),
+            Ok(tables.clone())
+        );
+        assert_eq!(
+            combine_tables(&params, threshold, &table_shares[..2]),
+            Err(ThresholdError::TooFewShares {
+                needed: 3,
+                found: 2
+            })
+        );
+        // Shares past the first k expose a faulty one.
+        let mut faulty = table_shares.clone();
+        faulty[1].tables[0][3] = faulty[1].tables[0][4];
+        assert_eq!(
+            combine_tables(&params, threshold, &faulty),
+            Err(ThresholdError::Inconsistent(4))
+        );
+        assert_eq!(
+            combine_tables(&params, threshold, &faulty[2..]),
+            Ok(tables.clone())
+        );
+
+        // What a single TTP with the jointly sampled secrets would publish.
+        let joint = |secret: &dyn Fn(&TtpSecret) -> Fr| {
+            let shares = parties
+                .iter()
+                .map(|p| Share {
+                    index: p.index,
+                    value: secret(&p.shares),
+                })
+                .collect::<Vec<_>>();
+            reconstruct(threshold, &shares).unwrap()
+        };
+        let secret = TtpSecret {
+            params: params.clone(),
+            db: db.clone(),
+            gamma: (0..db.len())
+                .map(|i| {
+                    (0..params.b_chunks)
+                        .map(|b| joint(&|s| s.gamma[i][b]))
+                        .collect()
+                })
+                .collect(),
+            r_masks: (0..params.b_chunks)
+                .map(|b| joint(&|s| s.r_masks[b]))
+                .collect(),
+        };
+        assert_eq!(tables, secret.tables());
+        let r_sum_shares = parties[1..4]
+            .iter()
+            .map(Party::r_sum_share)
+            .collect::<Vec<_>>();
+        let r_sum = combine_r_sum(threshold, &r_sum_shares).unwrap();
+        assert_eq!(r_sum, secret.r_sum());
+
+        let (proving_key, verifying_key) = proof_keys(&params, &mut rng);
+        let client_key = ClientKey::new(params.clone(), 0, tables, proving_key);
+        let server_key = ServerKey {
+            params: params.clone(),
+            epoch: 0,
+            root: client_key.root(),
+            verifying_key,
+            r_sum,
+        };
+        let header = |msgid| SubmissionHeader {
+            client_id: ClientId([7; 32]),
+            msgid,
+            issued_at: 1_700_000_000,
+        };
+        let near = vec![1, 1, 1, 0, 0, 1, 0, 1];
+        let far = vec![1, 1, 0, 0, 0, 0, 1, 1];
+        let yes = client_submit(&client_key, near, header(1), &mut rng);
+        assert_eq!(
+            server_verify_and_decide(&server_key, &yes),
+            Ok(ServerDecision::Yes)
+        );
+        let no = client_submit(&client_key, far, header(2), &mut rng);
+        assert_eq!(
+            server_verify_and_decide(&server_key, &no),
+            Ok(ServerDecision::No)
+        );
+    }
+}
@@ -0,0 +1,107 @@
+//! Domain-separated Fiat–Shamir transcript for Regime A.
+//!
+//! A transcript is a BLAKE3 hash in key-derivation mode over a sequence of
+//! labelled messages. Every message is framed as
+//!
+//! ```text
+//! len(label)   u32 little endian
+//! label        bytes
+//! len(message) u64 little endian
+//! message      bytes
+//! ```
+//!
+//! so no two different sequences of appends produce the same hash input.
+//! Challenges are read from the BLAKE3 output stream after appending the
+//! challenge label with an empty message, and are themselves appended so
+//! later challenges depend on earlier ones. `docs/regime_a_wire.md` gives the
+//! encoding of every value the protocol appends.
+
+use ark_ff::PrimeField;
+use ark_serialize::CanonicalSerialize;
+
+/// BLAKE3 key-derivation context shared by every Regime A transcript.
+const TRANSCRIPT_CONTEXT: &str = "pdqhash 2024 regime-a transcript v1";
+
+/// Running Fiat–Shamir transcript.
+#[derive(Clone, Debug)]
+pub struct Transcript {
+    hasher: blake3::Hasher,
+}
+
+impl Transcript {
+    /// Start a transcript for the protocol step named `domain`.
+    pub fn new(domain: &[u8]) -> Self {
+        let mut transcript = Self {
+            hasher: blake3::Hasher::new_derive_key(TRANSCRIPT_CONTEXT),
+        };
+        transcript.append_message(b"domain", domain);
+        transcript
+    }
+
+    /// Append `message` under `label`.
+    pub fn append_message(&mut self, label: &[u8], message: &[u8]) {
+        self.hasher.update(&(label.len() as u32).to_le_bytes());
+        self.hasher.update(label);
+        self.hasher.update(&(message.len() as u64).to_le_bytes());
+        self.hasher.update(message);
+    }
+
+    /// Append a `u64`, encoded as 8 little-endian bytes.
+    pub fn append_u64(&mut self, label: &[u8], value: u64) {
+        self.append_message(label, &value.to_le_bytes());
+    }
+
+    /// Append a field element or curve point in its compressed arkworks
+    /// encoding.
+    pub fn append_serialized<T: CanonicalSerialize>(&mut self, label: &[u8], value: &T) {
+        let mut bytes = Vec::with_capacity(value.compressed_size());
+        value
+            .serialize_compressed(&mut bytes)
+            .expect("serializing into a Vec cannot fail");
+        self.append_message(label, &bytes);
+    }
+
+    /// Fill `out` with challenge bytes bound to everything appended so far.
+    pub fn challenge_bytes(&mut self, label: &[u8], out: &mut [u8]) {
+        self.append_message(label, &[]);
+        self.hasher.finalize_xof().fill(out);
+        self.append_message(b"challenge", out);
+    }
+
+    /// Challenge in `F`, reduced from 64 output bytes so it is statistically
+    /// uniform.
+    pub fn challenge_field<F: PrimeField>(&mut self, label: &[u8]) -> F {
+        let mut bytes = [0u8; 64];
+        self.challenge_bytes(label, &mut bytes);
+        F::from_le_bytes_mod_order(&bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_ed_on_bls12_381::Fq;
+
+    #[test]
+    fn transcript_test_vector() {
+        // Pinned so that independent implementations can check themselves
+        // against this one; changing it is a wire-format break.
+        let mut transcript = Transcript::new(b"test");
+        transcript.append_u64(b"msgid", 42);
+        transcript.append_serialized(b"value", &Fq::from(7u64));
+        let mut out = [0u8; 32];
+        transcript.challenge_bytes(b"c", &mut out);
+        assert_eq!(hex::encode(out), TEST_VECTOR);
+    }
+
+    #[test]
+    fn framing_separates_labels_from_messages() {
+        let mut a = Transcript::new(b"test");
+        a.append_message(b"ab", b"c");
+        let mut b = Transcript::new(b"test");
+        b.append_message(b"a", b"bc");
+        assert_ne!(a.challenge_field::<Fq>(b"x"), b.challenge_field::<Fq>(b"x"));
+    }
+
+    const TEST_VECTOR: &str = "50e1011835e44d7f6273156c9ad81bae4c4cd1302f3a8d5a22403f972ddb7a7d";
+}
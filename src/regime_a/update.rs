@@ -0,0 +1,348 @@
+//! Regime A database updates without a new Groth16 setup.
+//!
+//! Adding or revoking entries moves the TTP to the next epoch and produces a
+//! [`TableDelta`]: the new tables and their root. Clients apply it to their
+//! [`ClientKey`] and the server to its [`ServerKey`]; submissions name the
+//! epoch they were made against, so the server rejects any made with stale
+//! tables. The Groth16 keys do not depend on the database and never change.
+//! An update that would leave the database as it is fails with
+//! [`UpdateError::Unchanged`] instead of making every client download new
+//! tables.
+//!
+//! Every epoch draws fresh weights for all entries and fresh masks `r_b`
+//! with the same sum, so every point of every table changes and the server
+//! keeps its `r_sum`. A delta therefore carries whole tables rather than the
+//! points near the changed entries, which would name their chunks. As with
+//! any set of tables, the new ones still show which chunk values are near
+//! some entry, so comparing two epochs shows where near points appeared or
+//! disappeared.
+
+use super::{entry_weights, ClientKey, ServerKey, TtpSetup};
+use ark_ed_on_bls12_381::{EdwardsAffine, Fq, Fr};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use ark_std::rand::{CryptoRng, RngCore};
+use ark_std::UniformRand;
+
+/// The published tables of a new epoch.
+#[derive(Clone, Debug, PartialEq, CanonicalSerialize, CanonicalDeserialize)]
+pub struct TableDelta {
+    /// Epoch the delta applies to.
+    pub from_epoch: u64,
+    /// Epoch after applying the delta.
+    pub epoch: u64,
+    /// Table root after applying the delta.
+    pub root: Fq,
+    /// Re-masked tables, shaped like [`ClientKey::tables`].
+    pub tables: Vec<Vec<EdwardsAffine>>,
+}
+
+/// Why a delta could not be applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum DeltaError {
+    /// The key is not at the epoch the delta starts from.
+    #[error("delta applies to epoch {found}, key is at epoch {expected}")]
+    EpochMismatch {
+        /// The key's epoch.
+        expected: u64,
+        /// The delta's starting epoch.
+        found: u64,
+    },
+    /// The delta's tables do not have the shape of the key's.
+    #[error("delta tables do not match the parameters")]
+    BadShape,
+    /// The new tables do not hash to the delta's root.
+    #[error("updated tables do not match the delta's root")]
+    RootMismatch,
+}
+
+/// Why the TTP did not publish a new epoch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum UpdateError {
+    /// The entry at this index is not a bit vector of the query length.
+    #[error("entry {0} is not a bit vector of the query length")]
+    BadEntry(usize),
+    /// The update would leave the database as it is.
+    #[error("the update does not change the database")]
+    Unchanged,
+}
+
+impl ClientKey {
+    /// Move the tables to the delta's epoch. The key is unchanged on error.
+    pub fn apply_delta(&mut self, delta: &TableDelta) -> Result<(), DeltaError> {
+        if delta.from_epoch != self.epoch {
+            return Err(DeltaError::EpochMismatch {
+                expected: self.epoch,
+                found: delta.from_epoch,
+            });
+        }
+        let shape_ok = delta.tables.len() == self.params.b_chunks
+            && delta.tables.iter().all(|t| t.len() == 1 << self.params.ell);
+        if !shape_ok {
+            return Err(DeltaError::BadShape);
+        }
+
+        let updated = ClientKey::new(
+            self.params.clone(),
+            delta.epoch,
+            delta.tables.clone(),
+            self.proving_key.clone(),
+        );
+        if updated.root() != delta.root {
+            return Err(DeltaError::RootMismatch);
+        }
+        *self = updated;
+        Ok(())
+    }
+}
+
+impl ServerKey {
+    /// Move to the delta's epoch and root.
+    pub fn apply_delta(&mut self, delta: &TableDelta) -> Result<(), DeltaError> {
+        if delta.from_epoch != self.epoch {
+            return Err(DeltaError::EpochMismatch {
+                expected: self.epoch,
+                found: delta.from_epoch,
+            });
+        }
+        self.epoch = delta.epoch;
+        self.root = delta.root;
+        Ok(())
+    }
+}
+
+impl TtpSetup {
+    /// Add `entries` to the database. Nothing changes on error, so no
+    /// epoch is spent on an empty or malformed update.
+    pub fn add_entries<R: RngCore + CryptoRng>(
+        &mut self,
+        entries: Vec<Vec<u8>>,
+        rng: &mut R,
+    ) -> Result<TableDelta, UpdateError> {
+        let lambda = self.secret.params.lambda();
+        if let Some(bad) = entries
+            .iter()
+            .position(|d| d.len() != lambda || d.iter().any(|bit| *bit > 1))
+        {
+            return Err(UpdateError::BadEntry(bad));
+        }
+        if entries.is_empty() {
+            return Err(UpdateError::Unchanged);
+        }
+        self.secret.db.extend(entries);
+        Ok(self.publish(rng))
+    }
+
+    /// Remove every occurrence of `entries` from the database. Entries that
+    /// are not in the database are ignored; if none are, nothing changes.
+    pub fn revoke_entries<R: RngCore + CryptoRng>(
+        &mut self,
+        entries: &[Vec<u8>],
+        rng: &mut R,
+    ) -> Result<TableDelta, UpdateError> {
+        let before = self.secret.db.len();
+        self.secret.db.retain(|item| !entries.contains(item));
+        if self.secret.db.len() == before {
+            return Err(UpdateError::Unchanged);
+        }
+        Ok(self.publish(rng))
+    }
+
+    /// Redraw every weight and mask, keeping `r_sum`, and move both keys to
+    /// the next epoch.
+    fn publish<R: RngCore + CryptoRng>(&mut self, rng: &mut R) -> TableDelta {
+        let secret = &mut self.secret;
+        secret.gamma = secret
+            .db
+            .iter()
+            .map(|_| entry_weights(&secret.params, rng))
+            .collect();
+        let r_sum = secret.r_sum();
+        let b_chunks = secret.params.b_chunks;
+        for mask in &mut secret.r_masks[..b_chunks - 1] {
+            *mask = Fr::rand(rng);
+        }
+        secret.r_masks[b_chunks - 1] = r_sum - secret.r_masks[..b_chunks - 1].iter().sum::<Fr>();
+
+        let from_epoch = self.client_key.epoch;
+        self.client_key = ClientKey::new(
+            secret.params.clone(),
+            from_epoch + 1,
+            secret.tables(),
+            self.client_key.proving_key.clone(),
+        );
+        let delta = TableDelta {
+            from_epoch,
+            epoch: self.client_key.epoch,
+            root: self.client_key.root(),
+            tables: self.client_key.tables.clone(),
+        };
+        self.server_key
+            .apply_delta(&delta)
+            .expect("the server key follows the client key");
+        delta
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::regime_a::{
+        client_submit, server_verify_and_decide, ClientId, RegimeAParams, ServerDecision,
+        SubmissionHeader, VerifyError,
+    };
+    use ark_std::rand::{rngs::StdRng, SeedableRng};
+
+    fn header(msgid: u64) -> SubmissionHeader {
+        SubmissionHeader {
+            client_id: ClientId([5; 32]),
+            msgid,
+            issued_at: 1_700_000_000,
+        }
+    }
+
+    #[test]
+    fn deltas_match_a_fresh_build() {
+        let params = RegimeAParams::new(4, 3, 2);
+        let zeros = vec![0; params.lambda()];
+        let ones = vec![1; params.lambda()];
+        let mut setup = TtpSetup::setup_seeded(vec![zeros.clone()], params, 2);
+        let mut rng = StdRng::seed_from_u64(0);
+
+        let mut client = setup.client_key.clone();
+        let delta = setup
+            .add_entries(vec![ones.clone(), ones.clone()], &mut rng)
+            .unwrap();
+        assert_eq!(delta.epoch, 1);
+        assert_eq!(setup.secret.db.len(), 3);
+        assert_eq!(setup.client_key.tables, setup.secret.tables());
+        client.apply_delta(&delta).unwrap();
+        assert_eq!(client, setup.client_key);
+
+        let delta = setup.revoke_entries(&[ones], &mut rng).unwrap();
+        assert_eq!(setup.secret.db, vec![zeros]);
+        assert_eq!(setup.client_key.tables, setup.secret.tables());
+        client.apply_delta(&delta).unwrap();
+        assert_eq!(client, setup.client_key);
+        assert_eq!(setup.server_key.root, client.root());
+        assert_eq!(setup.server_key.epoch, 2);
+    }
+
+    #[test]
+    fn updates_that_change_nothing_keep_the_epoch() {
+        let params = RegimeAParams::new(4, 2, 2);
+        let zeros = vec![0; params.lambda()];
+        let mut setup = TtpSetup::setup_seeded(vec![zeros.clone()], params.clone(), 6);
+        let mut rng = StdRng::seed_from_u64(0);
+        let before = setup.client_key.clone();
+
+        assert_eq!(
+            setup.add_entries(Vec::new(), &mut rng),
+            Err(UpdateError::Unchanged)
+        );
+        assert_eq!(
+            setup.add_entries(vec![zeros.clone(), vec![0; 3]], &mut rng),
+            Err(UpdateError::BadEntry(1))
+        );
+        assert_eq!(
+            setup.add_entries(vec![vec![2; params.lambda()]], &mut rng),
+            Err(UpdateError::BadEntry(0))
+        );
+        assert_eq!(
+            setup.revoke_entries(&[vec![1; params.lambda()]], &mut rng),
+            Err(UpdateError::Unchanged)
+        );
+        assert_eq!(setup.client_key, before);
+        assert_eq!(setup.secret.db, vec![zeros]);
+    }
+
+    #[test]
+    fn every_point_changes_between_epochs() {
+        let params = RegimeAParams::new(4, 3, 2);
+        let mut setup = TtpSetup::setup_seeded(vec![vec![0; params.lambda()]], params.clone(), 5);
+        let before = setup.client_key.tables.clone();
+        let r_sum = setup.server_key.r_sum;
+
+        let delta = setup
+            .add_entries(
+                vec![vec![1; params.lambda()]],
+                &mut StdRng::seed_from_u64(0),
+            )
+            .unwrap();
+        for (old, new) in before.iter().flatten().zip(delta.tables.iter().flatten()) {
+            assert_ne!(old, new);
+        }
+        assert_eq!(setup.server_key.r_sum, r_sum);
+        assert_eq!(setup.secret.r_sum(), r_sum);
+    }
+
+    #[test]
+    fn rejected_delta_leaves_key_unchanged() {
+        let params = RegimeAParams::new(4, 2, 2);
+        let mut setup = TtpSetup::setup_seeded(vec![vec![0; params.lambda()]], params.clone(), 3);
+        let mut client = setup.client_key.clone();
+        let mut delta = setup
+            .add_entries(
+                vec![vec![1; params.lambda()]],
+                &mut StdRng::seed_from_u64(0),
+            )
+            .unwrap();
+        let original = client.clone();
+
+        let mut wrong_root = delta.clone();
+        wrong_root.root += Fq::from(1u64);
+        assert_eq!(
+            client.apply_delta(&wrong_root),
+            Err(DeltaError::RootMismatch)
+        );
+        assert_eq!(client, original);
+
+        delta.from_epoch = 1;
+        assert_eq!(
+            client.apply_delta(&delta),
+            Err(DeltaError::EpochMismatch {
+                expected: 0,
+                found: 1
+            })
+        );
+    }
+
+    #[test]
+    fn server_checks_submission_epoch() {
+        let params = RegimeAParams::new(4, 2, 2);
+        let zeros = vec![0; params.lambda()];
+        let mut setup = TtpSetup::setup_seeded(vec![zeros.clone()], params.clone(), 4);
+        let mut rng = StdRng::seed_from_u64(0);
+        let query = vec![1; params.lambda()];
+
+        let stale_client = setup.client_key.clone();
+        let no = client_submit(&setup.client_key, query.clone(), header(1), &mut rng);
+        assert_eq!(
+            server_verify_and_decide(&setup.server_key, &no),
+            Ok(ServerDecision::No)
+        );
+
+        setup.add_entries(vec![query.clone()], &mut rng).unwrap();
+        let yes = client_submit(&setup.client_key, query.clone(), header(2), &mut rng);
+        assert_eq!(
+            server_verify_and_decide(&setup.server_key, &yes),
+            Ok(ServerDecision::Yes)
+        );
+        let stale = client_submit(&stale_client, query.clone(), header(3), &mut rng);
+        assert_eq!(
+            server_verify_and_decide(&setup.server_key, &stale),
+            Err(VerifyError::EpochMismatch {
+                expected: 1,
+                found: 0
+            })
+        );
+
+        setup
+            .revoke_entries(std::slice::from_ref(&query), &mut rng)
+            .unwrap();
+        let revoked = client_submit(&setup.client_key, query, header(4), &mut rng);
+        assert_eq!(
+            server_verify_and_decide(&setup.server_key, &revoked),
+            Ok(ServerDecision::No)
+        );
+    }
+}
@@ -0,0 +1,1452 @@
+//! Versioned binary and JSON encodings of the Regime A keys and messages.
+//!
+//! The binary form of every type implementing [`Wire`] is
+//!
+//! ```text
+//! magic    5 bytes   "PDQRA"
+//! version  1 byte    WIRE_VERSION
+//! kind     1 byte    Wire::KIND
+//! body     ...       arkworks compressed encoding
+//! ```
+//!
+//! The body uses the encodings of `docs/regime_a_wire.md`, with
+//! vectors prefixed by their length as a little-endian `u64`. Parameters are
+//! encoded as `b_chunks` followed by each chunk's `len` and `epsilon` (`u64`
+//! each); `ell` and the generator are implied.
+//!
+//! With the `serde` feature the same types also implement `Serialize` and
+//! `Deserialize`. The JSON form is an object with a `version` field.
+//! Field elements, points, keys and proofs appear as hex strings of their
+//! compressed encodings. Decoding in either form validates points and shapes,
+//! so a decoded key is always safe to use.
+
+use super::{
+    audit::{AuditKey, AuditPublicKey, Receipt, Signature},
+    disclosure::{Disclosure, DisclosureKey, LabelTables},
+    update::TableDelta,
+    ChunkParams, ClientId, ClientKey, ClientSubmission, RegimeAParams, ServerDecision, ServerKey,
+    TtpSecret, MAX_CHUNK_BITS,
+};
+use ark_ed_on_bls12_381::EdwardsAffine;
+use ark_serialize::{
+    CanonicalDeserialize, CanonicalSerialize, Compress, Read, SerializationError, Valid, Validate,
+    Write,
+};
+use std::collections::HashMap;
+
+/// Current version of both encodings.
+pub const WIRE_VERSION: u8 = 2;
+/// Leading bytes of every binary message.
+const WIRE_MAGIC: &[u8; 5] = b"PDQRA";
+
+/// Error decoding a Regime A message.
+#[derive(Debug, thiserror::Error)]
+pub enum WireError {
+    /// The input does not start with the Regime A magic bytes.
+    #[error("not a Regime A message")]
+    BadMagic,
+    /// The input was written by an incompatible version.
+    #[error("unsupported wire version {0}")]
+    UnsupportedVersion(u8),
+    /// The input holds a different type of message.
+    #[error("expected a {expected} message, found kind {found}")]
+    WrongKind {
+        /// Name of the expected type.
+        expected: &'static str,
+        /// Kind byte found in the input.
+        found: u8,
+    },
+    /// The body could not be decoded or failed validation.
+    #[error("malformed body: {0}")]
+    Malformed(#[from] SerializationError),
+    /// The input continues after the message.
+    #[error("{0} trailing bytes after message")]
+    TrailingBytes(usize),
+}
+
+/// A Regime A type with a versioned binary encoding.
+pub trait Wire: CanonicalSerialize + CanonicalDeserialize {
+    /// Kind byte identifying the type on the wire.
+    const KIND: u8;
+    /// Human-readable name used in errors.
+    const NAME: &'static str;
+
+    /// Encode with the versioned header.
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(WIRE_MAGIC.len() + 2 + self.compressed_size());
+        bytes.extend_from_slice(WIRE_MAGIC);
+        bytes.push(WIRE_VERSION);
+        bytes.push(Self::KIND);
+        self.serialize_compressed(&mut bytes)
+            .expect("serializing into a Vec cannot fail");
+        bytes
+    }
+
+    /// Decode and validate a message that occupies all of `bytes`.
+    fn from_bytes(bytes: &[u8]) -> Result<Self, WireError> {
+        let header_len = WIRE_MAGIC.len() + 2;
+        if bytes.len() < header_len || &bytes[..WIRE_MAGIC.len()] != WIRE_MAGIC {
+            return Err(WireError::BadMagic);
+        }
+        let version = bytes[WIRE_MAGIC.len()];
+        if version != WIRE_VERSION {
+            return Err(WireError::UnsupportedVersion(version));
+        }
+        let kind = bytes[WIRE_MAGIC.len() + 1];
+        if kind != Self::KIND {
+            return Err(WireError::WrongKind {
+                expected: Self::NAME,
+                found: kind,
+            });
+        }
+        let mut body = &bytes[header_len..];
+        let value = Self::deserialize_compressed(&mut body)?;
+        if !body.is_empty() {
+            return Err(WireError::TrailingBytes(body.len()));
+        }
+        Ok(value)
+    }
+}
+
+impl Wire for RegimeAParams {
+    const KIND: u8 = 1;
+    const NAME: &'static str = "parameters";
+}
+
+impl Wire for ClientKey {
+    const KIND: u8 = 2;
+    const NAME: &'static str = "client key";
+}
+
+impl Wire for ServerKey {
+    const KIND: u8 = 3;
+    const NAME: &'static str = "server key";
+}
+
+impl Wire for TtpSecret {
+    const KIND: u8 = 4;
+    const NAME: &'static str = "TTP secret";
+}
+
+impl Wire for ClientSubmission {
+    const KIND: u8 = 5;
+    const NAME: &'static str = "client submission";
+}
+
+impl Wire for ServerDecision {
+    const KIND: u8 = 6;
+    const NAME: &'static str = "server decision";
+}
+
+impl Wire for TableDelta {
+    const KIND: u8 = 7;
+    const NAME: &'static str = "table delta";
+}
+
+impl Wire for AuditKey {
+    const KIND: u8 = 8;
+    const NAME: &'static str = "audit key";
+}
+
+impl Wire for AuditPublicKey {
+    const KIND: u8 = 9;
+    const NAME: &'static str = "audit public key";
+}
+
+impl Wire for Receipt {
+    const KIND: u8 = 10;
+    const NAME: &'static str = "receipt";
+}
+
+impl Wire for LabelTables {
+    const KIND: u8 = 11;
+    const NAME: &'static str = "label tables";
+}
+
+impl Wire for DisclosureKey {
+    const KIND: u8 = 12;
+    const NAME: &'static str = "disclosure key";
+}
+
+impl Wire for Disclosure {
+    const KIND: u8 = 13;
+    const NAME: &'static str = "disclosure";
+}
+
+impl RegimeAParams {
+    /// Parameters from untrusted `(len, epsilon)` pairs, or `None` if
+    /// [`RegimeAParams::with_chunks`] would reject them.
+    fn checked(chunks: Vec<(u64, u64)>) -> Option<Self> {
+        let valid = !chunks.is_empty()
+            && chunks
+                .iter()
+                .all(|(len, epsilon)| *len > 0 && *len <= MAX_CHUNK_BITS as u64 && epsilon <= len);
+        valid.then(|| {
+            Self::with_chunks(
+                chunks
+                    .into_iter()
+                    .map(|(len, epsilon)| ChunkParams {
+                        len: len as usize,
+                        epsilon: epsilon as usize,
+                    })
+                    .collect(),
+            )
+        })
+    }
+}
+
+impl CanonicalSerialize for RegimeAParams {
+    fn serialize_with_mode<W: Write>(
+        &self,
+        mut writer: W,
+        compress: Compress,
+    ) -> Result<(), SerializationError> {
+        (self.b_chunks as u64).serialize_with_mode(&mut writer, compress)?;
+        for chunk in &self.chunks {
+            (chunk.len as u64).serialize_with_mode(&mut writer, compress)?;
+            (chunk.epsilon as u64).serialize_with_mode(&mut writer, compress)?;
+        }
+        Ok(())
+    }
+
+    fn serialized_size(&self, _compress: Compress) -> usize {
+        8 + self.chunks.len() * 2 * 8
+    }
+}
+
+impl Valid for RegimeAParams {
+    fn check(&self) -> Result<(), SerializationError> {
+        Ok(())
+    }
+}
+
+impl CanonicalDeserialize for RegimeAParams {
+    fn deserialize_with_mode<R: Read>(
+        mut reader: R,
+        compress: Compress,
+        validate: Validate,
+    ) -> Result<Self, SerializationError> {
+        let b_chunks = u64::deserialize_with_mode(&mut reader, compress, validate)?;
+        if b_chunks > u32::MAX as u64 {
+            return Err(SerializationError::InvalidData);
+        }
+        // Not preallocated: the count is untrusted until the chunks are read.
+        let mut chunks = Vec::new();
+        for _ in 0..b_chunks {
+            let len = u64::deserialize_with_mode(&mut reader, compress, validate)?;
+            let epsilon = u64::deserialize_with_mode(&mut reader, compress, validate)?;
+            chunks.push((len, epsilon));
+        }
+        // Always checked: every other type relies on the parameters' bounds.
+        Self::checked(chunks).ok_or(SerializationError::InvalidData)
+    }
+}
+
+impl CanonicalSerialize for ClientKey {
+    fn serialize_with_mode<W: Write>(
+        &self,
+        mut writer: W,
+        compress: Compress,
+    ) -> Result<(), SerializationError> {
+        self.params.serialize_with_mode(&mut writer, compress)?;
+        self.epoch.serialize_with_mode(&mut writer, compress)?;
+        self.tables.serialize_with_mode(&mut writer, compress)?;
+        self.proving_key.serialize_with_mode(&mut writer, compress)
+    }
+
+    fn serialized_size(&self, compress: Compress) -> usize {
+        self.params.serialized_size(compress)
+            + self.epoch.serialized_size(compress)
+            + self.tables.serialized_size(compress)
+            + self.proving_key.serialized_size(compress)
+    }
+}
+
+/// Decode tables encoded as `Vec<Vec<EdwardsAffine>>`.
+///
+/// Decompressing and validating a point costs a square root and a subgroup
+/// check, and most table entries repeat their chunk's `g^{r_b}`, so each
+/// distinct encoding is decoded once.
+fn deserialize_tables<R: Read>(
+    mut reader: R,
+    compress: Compress,
+    validate: Validate,
+) -> Result<Vec<Vec<EdwardsAffine>>, SerializationError> {
+    let point_len = EdwardsAffine::zero().serialized_size(compress);
+    let mut decoded = HashMap::<Vec<u8>, EdwardsAffine>::new();
+    let mut encoding = vec![0u8; point_len];
+    let chunks = u64::deserialize_with_mode(&mut reader, compress, validate)?;
+    (0..chunks)
+        .map(|_| {
+            let len = u64::deserialize_with_mode(&mut reader, compress, validate)?;
+            (0..len)
+                .map(|_| {
+                    reader.read_exact(&mut encoding)?;
+                    if let Some(point) = decoded.get(&encoding) {
+                        return Ok(*point);
+                    }
+                    let point =
+                        EdwardsAffine::deserialize_with_mode(&encoding[..], compress, validate)?;
+                    decoded.insert(encoding.clone(), point);
+                    Ok(point)
+                })
+                .collect()
+        })
+        .collect()
+}
+
+impl Valid for ClientKey {
+    fn check(&self) -> Result<(), SerializationError> {
+        let shape_ok = self.tables.len() == self.params.b_chunks
+            && self.tables.iter().all(|t| t.len() == 1 << self.params.ell);
+        if !shape_ok {
+            return Err(SerializationError::InvalidData);
+        }
+        self.proving_key.check()
+    }
+}
+
+impl CanonicalDeserialize for ClientKey {
+    fn deserialize_with_mode<R: Read>(
+        mut reader: R,
+        compress: Compress,
+        validate: Validate,
+    ) -> Result<Self, SerializationError> {
+        let params = RegimeAParams::deserialize_with_mode(&mut reader, compress, validate)?;
+        let epoch = u64::deserialize_with_mode(&mut reader, compress, validate)?;
+        let tables = deserialize_tables(&mut reader, compress, validate)?;
+        let proving_key =
+            CanonicalDeserialize::deserialize_with_mode(&mut reader, compress, validate)?;
+        // The shape is checked regardless of `validate`, since `new` would
+        // panic on a mismatch.
+        let shape_ok =
+            tables.len() == params.b_chunks && tables.iter().all(|t| t.len() == 1 << params.ell);
+        if !shape_ok {
+            return Err(SerializationError::InvalidData);
+        }
+        Ok(Self::new(params, epoch, tables, proving_key))
+    }
+}
+
+impl CanonicalSerialize for LabelTables {
+    fn serialize_with_mode<W: Write>(
+        &self,
+        mut writer: W,
+        compress: Compress,
+    ) -> Result<(), SerializationError> {
+        self.params.serialize_with_mode(&mut writer, compress)?;
+        self.epoch.serialize_with_mode(&mut writer, compress)?;
+        self.tables.serialize_with_mode(&mut writer, compress)
+    }
+
+    fn serialized_size(&self, compress: Compress) -> usize {
+        self.params.serialized_size(compress)
+            + self.epoch.serialized_size(compress)
+            + self.tables.serialized_size(compress)
+    }
+}
+
+impl Valid for LabelTables {
+    fn check(&self) -> Result<(), SerializationError> {
+        let shape_ok = self.tables.len() == self.params.b_chunks
+            && self.tables.iter().all(|t| t.len() == 1 << self.params.ell);
+        if shape_ok {
+            Ok(())
+        } else {
+            Err(SerializationError::InvalidData)
+        }
+    }
+}
+
+impl CanonicalDeserialize for LabelTables {
+    fn deserialize_with_mode<R: Read>(
+        mut reader: R,
+        compress: Compress,
+        validate: Validate,
+    ) -> Result<Self, SerializationError> {
+        let params = RegimeAParams::deserialize_with_mode(&mut reader, compress, validate)?;
+        let epoch = u64::deserialize_with_mode(&mut reader, compress, validate)?;
+        let tables = deserialize_tables(&mut reader, compress, validate)?;
+        // Checked regardless of `validate`, as for client keys.
+        let shape_ok =
+            tables.len() == params.b_chunks && tables.iter().all(|t| t.len() == 1 << params.ell);
+        if !shape_ok {
+            return Err(SerializationError::InvalidData);
+        }
+        Ok(Self::new(params, epoch, tables))
+    }
+}
+
+impl CanonicalSerialize for DisclosureKey {
+    fn serialize_with_mode<W: Write>(
+        &self,
+        mut writer: W,
+        compress: Compress,
+    ) -> Result<(), SerializationError> {
+        self.server_key.serialize_with_mode(&mut writer, compress)?;
+        self.label_root.serialize_with_mode(&mut writer, compress)?;
+        self.label_r_sum
+            .serialize_with_mode(&mut writer, compress)?;
+        self.tags.serialize_with_mode(&mut writer, compress)?;
+        self.labels.serialize_with_mode(&mut writer, compress)
+    }
+
+    fn serialized_size(&self, compress: Compress) -> usize {
+        self.server_key.serialized_size(compress)
+            + self.label_root.serialized_size(compress)
+            + self.label_r_sum.serialized_size(compress)
+            + self.tags.serialized_size(compress)
+            + self.labels.serialized_size(compress)
+    }
+}
+
+impl Valid for DisclosureKey {
+    fn check(&self) -> Result<(), SerializationError> {
+        if self.tags.len() != self.labels.len() {
+            return Err(SerializationError::InvalidData);
+        }
+        self.server_key.check()
+    }
+}
+
+impl CanonicalDeserialize for DisclosureKey {
+    fn deserialize_with_mode<R: Read>(
+        mut reader: R,
+        compress: Compress,
+        validate: Validate,
+    ) -> Result<Self, SerializationError> {
+        let key = Self {
+            server_key: CanonicalDeserialize::deserialize_with_mode(
+                &mut reader,
+                compress,
+                validate,
+            )?,
+            label_root: CanonicalDeserialize::deserialize_with_mode(
+                &mut reader,
+                compress,
+                validate,
+            )?,
+            label_r_sum: CanonicalDeserialize::deserialize_with_mode(
+                &mut reader,
+                compress,
+                validate,
+            )?,
+            tags: CanonicalDeserialize::deserialize_with_mode(&mut reader, compress, validate)?,
+            labels: CanonicalDeserialize::deserialize_with_mode(&mut reader, compress, validate)?,
+        };
+        if validate == Validate::Yes {
+            key.check()?;
+        }
+        Ok(key)
+    }
+}
+
+impl CanonicalSerialize for TtpSecret {
+    fn serialize_with_mode<W: Write>(
+        &self,
+        mut writer: W,
+        compress: Compress,
+    ) -> Result<(), SerializationError> {
+        self.params.serialize_with_mode(&mut writer, compress)?;
+        self.db.serialize_with_mode(&mut writer, compress)?;
+        self.gamma.serialize_with_mode(&mut writer, compress)?;
+        self.r_masks.serialize_with_mode(&mut writer, compress)
+    }
+
+    fn serialized_size(&self, compress: Compress) -> usize {
+        self.params.serialized_size(compress)
+            + self.db.serialized_size(compress)
+            + self.gamma.serialized_size(compress)
+            + self.r_masks.serialized_size(compress)
+    }
+}
+
+impl Valid for TtpSecret {
+    fn check(&self) -> Result<(), SerializationError> {
+        let lambda = self.params.lambda();
+        let valid = self
+            .db
+            .iter()
+            .all(|d| d.len() == lambda && d.iter().all(|bit| *bit == 0 || *bit == 1))
+            && self.gamma.len() == self.db.len()
+            && self
+                .gamma
+                .iter()
+                .all(|weights| weights.len() == self.params.b_chunks)
+            && self.r_masks.len() == self.params.b_chunks;
+        if valid {
+            Ok(())
+        } else {
+            Err(SerializationError::InvalidData)
+        }
+    }
+}
+
+impl CanonicalDeserialize for TtpSecret {
+    fn deserialize_with_mode<R: Read>(
+        mut reader: R,
+        compress: Compress,
+        validate: Validate,
+    ) -> Result<Self, SerializationError> {
+        let secret = Self {
+            params: RegimeAParams::deserialize_with_mode(&mut reader, compress, validate)?,
+            db: CanonicalDeserialize::deserialize_with_mode(&mut reader, compress, validate)?,
+            gamma: CanonicalDeserialize::deserialize_with_mode(&mut reader, compress, validate)?,
+            r_masks: CanonicalDeserialize::deserialize_with_mode(&mut reader, compress, validate)?,
+        };
+        if validate == Validate::Yes {
+            secret.check()?;
+        }
+        Ok(secret)
+    }
+}
+
+impl CanonicalSerialize for ClientId {
+    fn serialize_with_mode<W: Write>(
+        &self,
+        mut writer: W,
+        _compress: Compress,
+    ) -> Result<(), SerializationError> {
+        writer.write_all(&self.0)?;
+        Ok(())
+    }
+
+    fn serialized_size(&self, _compress: Compress) -> usize {
+        self.0.len()
+    }
+}
+
+impl Valid for ClientId {
+    fn check(&self) -> Result<(), SerializationError> {
+        Ok(())
+    }
+}
+
+impl CanonicalDeserialize for ClientId {
+    fn deserialize_with_mode<R: Read>(
+        mut reader: R,
+        _compress: Compress,
+        _validate: Validate,
+    ) -> Result<Self, SerializationError> {
+        // Read directly: arkworks' array impl panics on short input.
+        let mut id = [0u8; 32];
+        reader.read_exact(&mut id)?;
+        Ok(Self(id))
+    }
+}
+
+impl CanonicalSerialize for ServerDecision {
+    fn serialize_with_mode<W: Write>(
+        &self,
+        mut writer: W,
+        compress: Compress,
+    ) -> Result<(), SerializationError> {
+        let byte = match self {
+            ServerDecision::No => 0u8,
+            ServerDecision::Yes => 1u8,
+        };
+        byte.serialize_with_mode(&mut writer, compress)
+    }
+
+    fn serialized_size(&self, _compress: Compress) -> usize {
+        1
+    }
+}
+
+impl Valid for ServerDecision {
+    fn check(&self) -> Result<(), SerializationError> {
+        Ok(())
+    }
+}
+
+impl CanonicalDeserialize for ServerDecision {
+    fn deserialize_with_mode<R: Read>(
+        mut reader: R,
+        compress: Compress,
+        validate: Validate,
+    ) -> Result<Self, SerializationError> {
+        match u8::deserialize_with_mode(&mut reader, compress, validate)? {
+            0 => Ok(ServerDecision::No),
+            1 => Ok(ServerDecision::Yes),
+            _ => Err(SerializationError::InvalidData),
+        }
+    }
+}
+
+impl CanonicalSerialize for Receipt {
+    fn serialize_with_mode<W: Write>(
+        &self,
+        mut writer: W,
+        compress: Compress,
+    ) -> Result<(), SerializationError> {
+        self.seq.serialize_with_mode(&mut writer, compress)?;
+        writer.write_all(&self.prev)?;
+        self.client_id.serialize_with_mode(&mut writer, compress)?;
+        self.msgid.serialize_with_mode(&mut writer, compress)?;
+        writer.write_all(&self.submission)?;
+        self.epoch.serialize_with_mode(&mut writer, compress)?;
+        self.decision.serialize_with_mode(&mut writer, compress)?;
+        self.timestamp.serialize_with_mode(&mut writer, compress)?;
+        self.signature.serialize_with_mode(&mut writer, compress)
+    }
+
+    fn serialized_size(&self, compress: Compress) -> usize {
+        4 * 8 + 3 * 32 + 1 + self.signature.serialized_size(compress)
+    }
+}
+
+impl Valid for Receipt {
+    fn check(&self) -> Result<(), SerializationError> {
+        self.signature.check()
+    }
+}
+
+impl CanonicalDeserialize for Receipt {
+    fn deserialize_with_mode<R: Read>(
+        mut reader: R,
+        compress: Compress,
+        validate: Validate,
+    ) -> Result<Self, SerializationError> {
+        let seq = u64::deserialize_with_mode(&mut reader, compress, validate)?;
+        let prev = read_digest(&mut reader)?;
+        let client_id = ClientId::deserialize_with_mode(&mut reader, compress, validate)?;
+        let msgid = u64::deserialize_with_mode(&mut reader, compress, validate)?;
+        let submission = read_digest(&mut reader)?;
+        let epoch = u64::deserialize_with_mode(&mut reader, compress, validate)?;
+        let decision = ServerDecision::deserialize_with_mode(&mut reader, compress, validate)?;
+        let timestamp = u64::deserialize_with_mode(&mut reader, compress, validate)?;
+        let signature = Signature::deserialize_with_mode(&mut reader, compress, validate)?;
+        Ok(Self {
+            seq,
+            prev,
+            client_id,
+            msgid,
+            submission,
+            epoch,
+            decision,
+            timestamp,
+            signature,
+        })
+    }
+}
+
+fn read_digest<R: Read>(mut reader: R) -> Result<[u8; 32], SerializationError> {
+    let mut digest = [0u8; 32];
+    reader.read_exact(&mut digest)?;
+    Ok(digest)
+}
+
+#[cfg(feature = "serde")]
+mod json {
+    use super::*;
+    use crate::regime_a::SubmissionHeader;
+    use ark_bls12_381::Bls12_381;
+    use ark_ed_on_bls12_381::{Fq, Fr};
+    use ark_groth16::{Proof, ProvingKey, VerifyingKey};
+    use serde::de::Error as _;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    /// An arkworks value as the hex string of its compressed encoding.
+    struct Hex<T>(T);
+
+    impl<T: CanonicalSerialize> Serialize for Hex<T> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut bytes = Vec::with_capacity(self.0.compressed_size());
+            self.0
+                .serialize_compressed(&mut bytes)
+                .map_err(serde::ser::Error::custom)?;
+            let hex = bytes
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<String>();
+            serializer.serialize_str(&hex)
+        }
+    }
+
+    impl<'de, T: CanonicalDeserialize> Deserialize<'de> for Hex<T> {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let hex = String::deserialize(deserializer)?;
+            if hex.len() % 2 != 0 || !hex.is_ascii() {
+                return Err(D::Error::custom("invalid hex string"));
+            }
+            let bytes = (0..hex.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(D::Error::custom)?;
+            let mut reader = bytes.as_slice();
+            let value = T::deserialize_compressed(&mut reader).map_err(D::Error::custom)?;
+            if !reader.is_empty() {
+                return Err(D::Error::custom("trailing bytes in hex value"));
+            }
+            Ok(Hex(value))
+        }
+    }
+
+    fn check_version<E: serde::de::Error>(version: u8) -> Result<(), E> {
+        if version == WIRE_VERSION {
+            Ok(())
+        } else {
+            Err(E::custom(WireError::UnsupportedVersion(version)))
+        }
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct ChunkJson {
+        len: u64,
+        epsilon: u64,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct ParamsJson {
+        chunks: Vec<ChunkJson>,
+    }
+
+    impl From<&RegimeAParams> for ParamsJson {
+        fn from(params: &RegimeAParams) -> Self {
+            let chunks = params
+                .chunks
+                .iter()
+                .map(|chunk| ChunkJson {
+                    len: chunk.len as u64,
+                    epsilon: chunk.epsilon as u64,
+                })
+                .collect();
+            Self { chunks }
+        }
+    }
+
+    impl ParamsJson {
+        fn into_params<E: serde::de::Error>(self) -> Result<RegimeAParams, E> {
+            let chunks = self
+                .chunks
+                .into_iter()
+                .map(|chunk| (chunk.len, chunk.epsilon))
+                .collect();
+            RegimeAParams::checked(chunks).ok_or_else(|| E::custom("invalid Regime A parameters"))
+        }
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Versioned<T> {
+        version: u8,
+        #[serde(flatten)]
+        body: T,
+    }
+
+    impl Serialize for RegimeAParams {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            Versioned {
+                version: WIRE_VERSION,
+                body: ParamsJson::from(self),
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for RegimeAParams {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let json = Versioned::<ParamsJson>::deserialize(deserializer)?;
+            check_version(json.version)?;
+            json.body.into_params()
+        }
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct ClientKeyJson {
+        params: ParamsJson,
+        epoch: u64,
+        tables: Vec<Vec<Hex<EdwardsAffine>>>,
+        proving_key: Hex<ProvingKey<Bls12_381>>,
+    }
+
+    impl Serialize for ClientKey {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let tables = self
+                .tables
+                .iter()
+                .map(|table| table.iter().copied().map(Hex).collect())
+                .collect();
+            Versioned {
+                version: WIRE_VERSION,
+                body: ClientKeyJson {
+                    params: ParamsJson::from(&self.params),
+                    epoch: self.epoch,
+                    tables,
+                    proving_key: Hex(self.proving_key.clone()),
+                },
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for ClientKey {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let json = Versioned::<ClientKeyJson>::deserialize(deserializer)?;
+            check_version(json.version)?;
+            let params = json.body.params.into_params()?;
+            let tables = json
+                .body
+                .tables
+                .into_iter()
+                .map(|table| table.into_iter().map(|point| point.0).collect::<Vec<_>>())
+                .collect::<Vec<_>>();
+            let shape_ok = tables.len() == params.b_chunks
+                && tables.iter().all(|t| t.len() == 1 << params.ell);
+            if !shape_ok {
+                return Err(D::Error::custom("table shape does not match parameters"));
+            }
+            Ok(ClientKey::new(
+                params,
+                json.body.epoch,
+                tables,
+                json.body.proving_key.0,
+            ))
+        }
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct ServerKeyJson {
+        params: ParamsJson,
+        epoch: u64,
+        root: Hex<Fq>,
+        verifying_key: Hex<VerifyingKey<Bls12_381>>,
+        r_sum: Hex<Fr>,
+    }
+
+    impl From<&ServerKey> for ServerKeyJson {
+        fn from(key: &ServerKey) -> Self {
+            Self {
+                params: ParamsJson::from(&key.params),
+                epoch: key.epoch,
+                root: Hex(key.root),
+                verifying_key: Hex(key.verifying_key.clone()),
+                r_sum: Hex(key.r_sum),
+            }
+        }
+    }
+
+    impl ServerKeyJson {
+        fn into_key<E: serde::de::Error>(self) -> Result<ServerKey, E> {
+            Ok(ServerKey {
+                params: self.params.into_params()?,
+                epoch: self.epoch,
+                root: self.root.0,
+                verifying_key: self.verifying_key.0,
+                r_sum: self.r_sum.0,
+            })
+        }
+    }
+
+    impl Serialize for ServerKey {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            Versioned {
+                version: WIRE_VERSION,
+                body: ServerKeyJson::from(self),
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for ServerKey {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let json = Versioned::<ServerKeyJson>::deserialize(deserializer)?;
+            check_version(json.version)?;
+            json.body.into_key()
+        }
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct TtpSecretJson {
+        params: ParamsJson,
+        db: Vec<Vec<u8>>,
+        gamma: Vec<Vec<Hex<Fr>>>,
+        r_masks: Vec<Hex<Fr>>,
+    }
+
+    impl Serialize for TtpSecret {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            Versioned {
+                version: WIRE_VERSION,
+                body: TtpSecretJson {
+                    params: ParamsJson::from(&self.params),
+                    db: self.db.clone(),
+                    gamma: self
+                        .gamma
+                        .iter()
+                        .map(|weights| weights.iter().copied().map(Hex).collect())
+                        .collect(),
+                    r_masks: self.r_masks.iter().copied().map(Hex).collect(),
+                },
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for TtpSecret {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let json = Versioned::<TtpSecretJson>::deserialize(deserializer)?;
+            check_version(json.version)?;
+            let secret = TtpSecret {
+                params: json.body.params.into_params()?,
+                db: json.body.db,
+                gamma: json
+                    .body
+                    .gamma
+                    .into_iter()
+                    .map(|weights| weights.into_iter().map(|x| x.0).collect())
+                    .collect(),
+                r_masks: json.body.r_masks.into_iter().map(|x| x.0).collect(),
+            };
+            secret.check().map_err(D::Error::custom)?;
+            Ok(secret)
+        }
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct SubmissionJson {
+        client_id: Hex<ClientId>,
+        msgid: u64,
+        issued_at: u64,
+        epoch: u64,
+        root: Hex<Fq>,
+        c_d: Hex<EdwardsAffine>,
+        res_total: Hex<EdwardsAffine>,
+        proof: Hex<Proof<Bls12_381>>,
+    }
+
+    impl Serialize for ClientSubmission {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            Versioned {
+                version: WIRE_VERSION,
+                body: SubmissionJson {
+                    client_id: Hex(self.header.client_id),
+                    msgid: self.header.msgid,
+                    issued_at: self.header.issued_at,
+                    epoch: self.epoch,
+                    root: Hex(self.root),
+                    c_d: Hex(self.c_d),
+                    res_total: Hex(self.res_total),
+                    proof: Hex(self.proof.clone()),
+                },
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for ClientSubmission {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let json = Versioned::<SubmissionJson>::deserialize(deserializer)?;
+            check_version(json.version)?;
+            Ok(ClientSubmission {
+                header: SubmissionHeader {
+                    client_id: json.body.client_id.0,
+                    msgid: json.body.msgid,
+                    issued_at: json.body.issued_at,
+                },
+                epoch: json.body.epoch,
+                root: json.body.root.0,
+                c_d: json.body.c_d.0,
+                res_total: json.body.res_total.0,
+                proof: json.body.proof.0,
+            })
+        }
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct TableDeltaJson {
+        from_epoch: u64,
+        epoch: u64,
+        root: Hex<Fq>,
+        tables: Vec<Vec<Hex<EdwardsAffine>>>,
+    }
+
+    impl Serialize for TableDelta {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let tables = self
+                .tables
+                .iter()
+                .map(|table| table.iter().copied().map(Hex).collect())
+                .collect();
+            Versioned {
+                version: WIRE_VERSION,
+                body: TableDeltaJson {
+                    from_epoch: self.from_epoch,
+                    epoch: self.epoch,
+                    root: Hex(self.root),
+                    tables,
+                },
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for TableDelta {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let json = Versioned::<TableDeltaJson>::deserialize(deserializer)?;
+            check_version(json.version)?;
+            let tables = json
+                .body
+                .tables
+                .into_iter()
+                .map(|table| table.into_iter().map(|point| point.0).collect())
+                .collect();
+            Ok(TableDelta {
+                from_epoch: json.body.from_epoch,
+                epoch: json.body.epoch,
+                root: json.body.root.0,
+                tables,
+            })
+        }
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct AuditKeyJson {
+        secret: Hex<Fr>,
+    }
+
+    impl Serialize for AuditKey {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            Versioned {
+                version: WIRE_VERSION,
+                body: AuditKeyJson {
+                    secret: Hex(self.secret),
+                },
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for AuditKey {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let json = Versioned::<AuditKeyJson>::deserialize(deserializer)?;
+            check_version(json.version)?;
+            Ok(AuditKey {
+                secret: json.body.secret.0,
+            })
+        }
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct AuditPublicKeyJson {
+        public_key: Hex<EdwardsAffine>,
+    }
+
+    impl Serialize for AuditPublicKey {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            Versioned {
+                version: WIRE_VERSION,
+                body: AuditPublicKeyJson {
+                    public_key: Hex(self.0),
+                },
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for AuditPublicKey {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let json = Versioned::<AuditPublicKeyJson>::deserialize(deserializer)?;
+            check_version(json.version)?;
+            Ok(AuditPublicKey(json.body.public_key.0))
+        }
+    }
+
+    /// Digests share the 32-byte encoding of client ids.
+    #[derive(Serialize, Deserialize)]
+    struct ReceiptJson {
+        seq: u64,
+        prev: Hex<ClientId>,
+        client_id: Hex<ClientId>,
+        msgid: u64,
+        submission: Hex<ClientId>,
+        epoch: u64,
+        decision: DecisionJson,
+        timestamp: u64,
+        signature: Hex<Signature>,
+    }
+
+    impl Serialize for Receipt {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            Versioned {
+                version: WIRE_VERSION,
+                body: ReceiptJson {
+                    seq: self.seq,
+                    prev: Hex(ClientId(self.prev)),
+                    client_id: Hex(self.client_id),
+                    msgid: self.msgid,
+                    submission: Hex(ClientId(self.submission)),
+                    epoch: self.epoch,
+                    decision: self.decision.into(),
+                    timestamp: self.timestamp,
+                    signature: Hex(self.signature.clone()),
+                },
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Receipt {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let json = Versioned::<ReceiptJson>::deserialize(deserializer)?;
+            check_version(json.version)?;
+            Ok(Receipt {
+                seq: json.body.seq,
+                prev: json.body.prev.0 .0,
+                client_id: json.body.client_id.0,
+                msgid: json.body.msgid,
+                submission: json.body.submission.0 .0,
+                epoch: json.body.epoch,
+                decision: json.body.decision.into(),
+                timestamp: json.body.timestamp,
+                signature: json.body.signature.0,
+            })
+        }
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct LabelTablesJson {
+        params: ParamsJson,
+        epoch: u64,
+        tables: Vec<Vec<Hex<EdwardsAffine>>>,
+    }
+
+    impl Serialize for LabelTables {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let tables = self
+                .tables
+                .iter()
+                .map(|table| table.iter().copied().map(Hex).collect())
+                .collect();
+            Versioned {
+                version: WIRE_VERSION,
+                body: LabelTablesJson {
+                    params: ParamsJson::from(&self.params),
+                    epoch: self.epoch,
+                    tables,
+                },
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for LabelTables {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let json = Versioned::<LabelTablesJson>::deserialize(deserializer)?;
+            check_version(json.version)?;
+            let params = json.body.params.into_params()?;
+            let tables = json
+                .body
+                .tables
+                .into_iter()
+                .map(|table| table.into_iter().map(|point| point.0).collect::<Vec<_>>())
+                .collect::<Vec<_>>();
+            let shape_ok = tables.len() == params.b_chunks
+                && tables.iter().all(|t| t.len() == 1 << params.ell);
+            if !shape_ok {
+                return Err(D::Error::custom("table shape does not match parameters"));
+            }
+            Ok(LabelTables::new(params, json.body.epoch, tables))
+        }
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct DisclosureKeyJson {
+        server_key: ServerKeyJson,
+        label_root: Hex<Fq>,
+        label_r_sum: Hex<Fr>,
+        tags: Vec<Hex<Fr>>,
+        labels: Vec<Vec<u8>>,
+    }
+
+    impl Serialize for DisclosureKey {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            Versioned {
+                version: WIRE_VERSION,
+                body: DisclosureKeyJson {
+                    server_key: ServerKeyJson::from(&self.server_key),
+                    label_root: Hex(self.label_root),
+                    label_r_sum: Hex(self.label_r_sum),
+                    tags: self.tags.iter().copied().map(Hex).collect(),
+                    labels: self.labels.clone(),
+                },
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for DisclosureKey {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let json = Versioned::<DisclosureKeyJson>::deserialize(deserializer)?;
+            check_version(json.version)?;
+            let key = DisclosureKey {
+                server_key: json.body.server_key.into_key()?,
+                label_root: json.body.label_root.0,
+                label_r_sum: json.body.label_r_sum.0,
+                tags: json.body.tags.into_iter().map(|x| x.0).collect(),
+                labels: json.body.labels,
+            };
+            key.check().map_err(D::Error::custom)?;
+            Ok(key)
+        }
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct DisclosureJson {
+        root: Hex<Fq>,
+        res_label: Hex<EdwardsAffine>,
+        proof: Hex<Proof<Bls12_381>>,
+    }
+
+    impl Serialize for Disclosure {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            Versioned {
+                version: WIRE_VERSION,
+                body: DisclosureJson {
+                    root: Hex(self.root),
+                    res_label: Hex(self.res_label),
+                    proof: Hex(self.proof.clone()),
+                },
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Disclosure {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let json = Versioned::<DisclosureJson>::deserialize(deserializer)?;
+            check_version(json.version)?;
+            Ok(Disclosure {
+                root: json.body.root.0,
+                res_label: json.body.res_label.0,
+                proof: json.body.proof.0,
+            })
+        }
+    }
+
+    #[derive(Serialize, Deserialize)]
+    enum DecisionJson {
+        Yes,
+        No,
+    }
+
+    impl From<ServerDecision> for DecisionJson {
+        fn from(decision: ServerDecision) -> Self {
+            match decision {
+                ServerDecision::Yes => DecisionJson::Yes,
+                ServerDecision::No => DecisionJson::No,
+            }
+        }
+    }
+
+    impl From<DecisionJson> for ServerDecision {
+        fn from(decision: DecisionJson) -> Self {
+            match decision {
+                DecisionJson::Yes => ServerDecision::Yes,
+                DecisionJson::No => ServerDecision::No,
+            }
+        }
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct DecisionBody {
+        decision: DecisionJson,
+    }
+
+    impl Serialize for ServerDecision {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            Versioned {
+                version: WIRE_VERSION,
+                body: DecisionBody {
+                    decision: (*self).into(),
+                },
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for ServerDecision {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let json = Versioned::<DecisionBody>::deserialize(deserializer)?;
+            check_version(json.version)?;
+            Ok(json.body.decision.into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::regime_a::disclosure::{client_submit_with_disclosure, DisclosureSetup};
+    use crate::regime_a::{client_submit, SubmissionHeader, TtpSetup};
+    use ark_std::rand::{rngs::StdRng, SeedableRng};
+    use std::sync::OnceLock;
+
+    fn fixture() -> &'static (TtpSetup, ClientSubmission) {
+        static FIXTURE: OnceLock<(TtpSetup, ClientSubmission)> = OnceLock::new();
+        FIXTURE.get_or_init(|| {
+            let params = RegimeAParams::new(4, 2, 2);
+            let db = vec![vec![0; 8], vec![1, 0, 1, 0, 1, 1, 0, 0]];
+            let setup = TtpSetup::setup_seeded(db, params, 21);
+            let header = SubmissionHeader {
+                client_id: ClientId([9; 32]),
+                msgid: 5,
+                issued_at: 1_700_000_000,
+            };
+            let submission = client_submit(
+                &setup.client_key,
+                vec![0; 8],
+                header,
+                &mut StdRng::seed_from_u64(1),
+            );
+            (setup, submission)
+        })
+    }
+
+    fn disclosure() -> &'static (DisclosureSetup, Disclosure) {
+        static DISCLOSURE: OnceLock<(DisclosureSetup, Disclosure)> = OnceLock::new();
+        DISCLOSURE.get_or_init(|| {
+            let (setup, submission) = fixture();
+            let disclosure = setup.disclosure_setup(
+                vec![b"zeros".to_vec(), b"pattern".to_vec()],
+                &mut StdRng::seed_from_u64(4),
+            );
+            let (_, proof) = client_submit_with_disclosure(
+                &setup.client_key,
+                &disclosure.tables,
+                vec![0; 8],
+                submission.header.clone(),
+                &mut StdRng::seed_from_u64(5),
+            );
+            (disclosure, proof)
+        })
+    }
+
+    fn delta() -> TableDelta {
+        let mut setup = fixture().0.clone();
+        setup
+            .add_entries(vec![vec![1; 8]], &mut StdRng::seed_from_u64(2))
+            .unwrap()
+    }
+
+    fn audit() -> (AuditKey, Receipt) {
+        let key = AuditKey::generate(&mut StdRng::seed_from_u64(3));
+        let submission = &fixture().1;
+        let mut receipt = Receipt {
+            seq: 4,
+            prev: [7; 32],
+            client_id: submission.header.client_id,
+            msgid: submission.header.msgid,
+            submission: submission.transcript_hash(),
+            epoch: submission.epoch,
+            decision: ServerDecision::Yes,
+            timestamp: 1_700_000_001,
+            signature: key.sign(&[0; 32]),
+        };
+        receipt.signature = key.sign(&receipt.digest());
+        (key, receipt)
+    }
+
+    fn roundtrip<T: Wire + PartialEq + std::fmt::Debug>(value: &T) {
+        let bytes = value.to_bytes();
+        assert_eq!(&T::from_bytes(&bytes).unwrap(), value);
+        assert!(T::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn binary_roundtrip() {
+        let (setup, submission) = fixture();
+        roundtrip(&setup.secret.params);
+        roundtrip(&setup.client_key);
+        roundtrip(&setup.server_key);
+        roundtrip(&setup.secret);
+        roundtrip(submission);
+        roundtrip(&delta());
+        roundtrip(&ServerDecision::Yes);
+        roundtrip(&ServerDecision::No);
+        let (key, receipt) = audit();
+        roundtrip(&key);
+        roundtrip(&key.public_key());
+        roundtrip(&receipt);
+        let (disclosure, proof) = disclosure();
+        roundtrip(&disclosure.tables);
+        roundtrip(&disclosure.key);
+        roundtrip(proof);
+    }
+
+    #[test]
+    fn binary_header_is_checked() {
+        let (setup, submission) = fixture();
+        let bytes = submission.to_bytes();
+
+        let mut bad_version = bytes.clone();
+        bad_version[WIRE_MAGIC.len()] = WIRE_VERSION + 1;
+        assert!(matches!(
+            ClientSubmission::from_bytes(&bad_version),
+            Err(WireError::UnsupportedVersion(_))
+        ));
+        assert!(matches!(
+            ServerKey::from_bytes(&bytes),
+            Err(WireError::WrongKind { .. })
+        ));
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(matches!(
+            ClientSubmission::from_bytes(&trailing),
+            Err(WireError::TrailingBytes(1))
+        ));
+        assert!(matches!(
+            ClientSubmission::from_bytes(b"junk"),
+            Err(WireError::BadMagic)
+        ));
+
+        // Parameters that `RegimeAParams::new` would reject never decode.
+        let params = setup.secret.params.to_bytes();
+        for (offset, value) in [(0, 0), (8, 0), (8, 21), (16, 5)] {
+            let mut bad_params = params.clone();
+            bad_params[WIRE_MAGIC.len() + 2 + offset] = value;
+            assert!(RegimeAParams::from_bytes(&bad_params).is_err());
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json_roundtrip() {
+        fn roundtrip<T>(value: &T)
+        where
+            T: serde::Serialize + serde::de::DeserializeOwned + PartialEq + std::fmt::Debug,
+        {
+            let json = serde_json::to_string(value).unwrap();
+            assert_eq!(&serde_json::from_str::<T>(&json).unwrap(), value);
+            let mut other_version = serde_json::from_str::<serde_json::Value>(&json).unwrap();
+            other_version["version"] = serde_json::json!(WIRE_VERSION + 1);
+            assert!(serde_json::from_value::<T>(other_version).is_err());
+        }
+
+        let (setup, submission) = fixture();
+        roundtrip(&setup.secret.params);
+        roundtrip(&setup.client_key);
+        roundtrip(&setup.server_key);
+        roundtrip(&setup.secret);
+        roundtrip(submission);
+        roundtrip(&delta());
+        roundtrip(&ServerDecision::Yes);
+        let (key, receipt) = audit();
+        roundtrip(&key);
+        roundtrip(&key.public_key());
+        roundtrip(&receipt);
+        let (disclosure, proof) = disclosure();
+        roundtrip(&disclosure.tables);
+        roundtrip(&disclosure.key);
+        roundtrip(proof);
+
+        let json = serde_json::to_value(&setup.secret.params).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "version": WIRE_VERSION,
+                "chunks": [{ "len": 4, "epsilon": 2 }, { "len": 4, "epsilon": 2 }],
+            })
+        );
+    }
+}
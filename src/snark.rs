@@ -4,20 +4,58 @@
 //! downsampled luminance buffer of an image. The prover supplies the image
 //! bytes and proves that they correspond to the public PDQ hash without
 //! revealing the image itself.
+//!
+//! ## Soundness
+//!
+//! The circuit works purely on the fixed-point DCT, so the public hash bits
+//! are a deterministic function of the witnessed 64x64 buffer:
+//!
+//! * Every pixel is range-checked to `LUMA_BITS` bits. With DCT coefficients
+//!   scaled by `DCT_FIXED_SCALE`, each row of the coefficient matrix has an
+//!   absolute sum below `2^24`, so every fixed-point DCT value `dct_i`
+//!   satisfies `|dct_i| < 2^72` as an integer; no field wrap-around occurs.
+//! * For each coefficient the prover supplies a slack `s_i < 2^SIGN_BITS`
+//!   with `s_i = dct_i - median - 1` when bit `i` is set and
+//!   `s_i = median - dct_i` otherwise. Because `2^SIGN_BITS` is far below the
+//!   field modulus, the two cases select disjoint integer ranges of
+//!   `dct_i - median`, so a set bit forces `dct_i > median` and a clear bit
+//!   forces `dct_i <= median`. The same constraints bound `median` to within
+//!   `2^SIGN_BITS` of every coefficient, so it needs no separate range check.
+//! * A tie flag `t_i` is `1` exactly when `dct_i == median` (checked with the
+//!   usual inverse trick). With `S` set bits and `T` ties the circuit enforces
+//!   `S <= 128` and `256 - S - T <= 127`. The only value with at most 127
+//!   coefficients strictly below it and at most 128 strictly above it is the
+//!   lower median of the 256 coefficients, which is also what the float
+//!   implementation's `torben_median` selects. The median, and therefore
+//!   every hash bit, is uniquely determined by the pixels.
+//!
+//! The hash proven is thus the PDQ hash of the fixed-point DCT. It agrees
+//! with the float hash unless quantisation reorders coefficients that sit
+//! within rounding distance of the median; [`PDQSnark::create_proof`]
+//! reports that case as an error rather than proving a different hash.
 
 use crate::dct;
-use crate::dwn_pdq::{compute_pdq_state, PDQ_HASH_LENGTH};
+use crate::dwn_pdq::{
+    compute_pdq_state, compute_pdq_state_from_buffer64, compute_pdq_state_from_luma,
+    PDQPrecomputed, PDQ_HASH_LENGTH,
+};
 use anyhow::{anyhow, Context};
 use ark_bls12_381::{Bls12_381, Fr as BlsFr};
-use ark_ff::{Field, PrimeField};
+use ark_ff::PrimeField;
 use ark_groth16::{Groth16, Proof, ProvingKey, VerifyingKey};
-use ark_r1cs_std::{
-    alloc::AllocVar, bits::uint64::UInt64, boolean::Boolean, fields::fp::FpVar, prelude::*,
+use ark_r1cs_std::{alloc::AllocVar, boolean::Boolean, fields::fp::FpVar, prelude::*};
+use ark_relations::r1cs::{
+    ConstraintSynthesizer, ConstraintSystem, ConstraintSystemRef, SynthesisError, SynthesisMode,
+};
+use ark_serialize::{
+    CanonicalDeserialize, CanonicalSerialize, Compress, Read, SerializationError, Valid, Validate,
+    Write,
 };
-use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError};
 use ark_snark::SNARK;
 use ark_std::{rand::CryptoRng, rand::RngCore, Zero};
+use std::fmt;
 use std::sync::OnceLock;
+use std::time::{Duration, Instant};
 
 /// The PDQ downsampled buffer is always 64x64.
 const BUFFER_EDGE: usize = 64;
@@ -27,12 +65,17 @@ const DCT_VALUE_COUNT: usize = DCT_EDGE * DCT_EDGE;
 const PDQ_HASH_BITS: usize = PDQ_HASH_LENGTH * 8;
 
 // Scaling factors used to keep arithmetic integral inside the circuit.
-const LUMA_FIXED_SCALE: i64 = 1 << 12;
-const DCT_FIXED_SCALE: i64 = 1 << 14;
-const FINAL_SCALE: i128 =
-    (LUMA_FIXED_SCALE as i128) * (DCT_FIXED_SCALE as i128) * (DCT_FIXED_SCALE as i128);
-const CORRECTION_BITS: usize = 46;
-const CORRECTION_TOLERANCE: u64 = 1u64 << CORRECTION_BITS;
+const LUMA_FIXED_SCALE: i64 = 1 << 16;
+const DCT_FIXED_SCALE: i64 = 1 << 20;
+/// Bit width of a quantised pixel (`255 * LUMA_FIXED_SCALE < 2^24`).
+const LUMA_BITS: usize = 24;
+/// Bit width of the per-coefficient sign slack (`|dct - median| < 2^73`).
+const SIGN_BITS: usize = 73;
+/// Rank of the median: at most this many coefficients may lie strictly above
+/// it, and strictly fewer strictly below it (mirrors `torben_median`).
+const MEDIAN_RANK: usize = DCT_VALUE_COUNT / 2;
+/// Bit width of the slack terms in the median counting constraints.
+const COUNT_SLACK_BITS: usize = 9;
 
 /// Convert a signed 64-bit integer into the prime field.
 fn field_from_i64<F: PrimeField>(value: i64) -> F {
@@ -43,6 +86,34 @@ fn field_from_i64<F: PrimeField>(value: i64) -> F {
     }
 }
 
+/// Convert a signed 128-bit integer into the prime field.
+fn field_from_i128<F: PrimeField>(value: i128) -> F {
+    if value >= 0 {
+        F::from(value as u128)
+    } else {
+        -F::from(value.unsigned_abs())
+    }
+}
+
+/// Enforce `0 <= value < 2^bits` by decomposing `native` into boolean witnesses
+/// whose weighted sum must equal `value`.
+fn enforce_bit_length<F: PrimeField>(
+    cs: ConstraintSystemRef<F>,
+    value: &FpVar<F>,
+    native: u128,
+    bits: usize,
+) -> Result<(), SynthesisError> {
+    let mut recomposed = FpVar::<F>::zero();
+    let mut coeff = F::one();
+    for i in 0..bits {
+        let bit = Boolean::new_witness(cs.clone(), || Ok((native >> i) & 1 == 1))?;
+        let bit_fp: FpVar<F> = bit.into();
+        recomposed += bit_fp * coeff;
+        coeff = coeff + coeff;
+    }
+    recomposed.enforce_equal(value)
+}
+
 /// Lazily construct the scaled DCT matrix coefficients.
 fn dct_coefficients() -> &'static [[i64; BUFFER_EDGE]; DCT_EDGE] {
     static TABLE: OnceLock<[[i64; BUFFER_EDGE]; DCT_EDGE]> = OnceLock::new();
@@ -58,20 +129,21 @@ fn dct_coefficients() -> &'static [[i64; BUFFER_EDGE]; DCT_EDGE] {
     })
 }
 
-/// Quantise the filtered 64x64 buffer into fixed-point integers.
+/// Quantise the filtered 64x64 buffer into fixed-point integers, clamped to
+/// the `LUMA_BITS` range the circuit accepts.
 fn quantize_buffer(buffer: &[[f32; BUFFER_EDGE]; BUFFER_EDGE]) -> Vec<i64> {
     let mut out = Vec::with_capacity(BUFFER_EDGE * BUFFER_EDGE);
     for row in buffer.iter() {
         for &value in row.iter() {
-            let scaled = (value as f64 * LUMA_FIXED_SCALE as f64).round();
-            out.push(scaled as i64);
+            let scaled = (value as f64 * LUMA_FIXED_SCALE as f64).round() as i64;
+            out.push(scaled.clamp(0, (1 << LUMA_BITS) - 1));
         }
     }
     out
 }
 
 /// Compute the fixed-point DCT used inside the circuit.
-fn compute_dct_fixed(pixels: &[i64]) -> Vec<i64> {
+fn compute_dct_fixed(pixels: &[i64]) -> Vec<i128> {
     let coeffs = dct_coefficients();
 
     let mut intermediate = vec![0i128; DCT_EDGE * BUFFER_EDGE];
@@ -87,7 +159,7 @@ fn compute_dct_fixed(pixels: &[i64]) -> Vec<i64> {
         }
     }
 
-    let mut output = vec![0i64; DCT_VALUE_COUNT];
+    let mut output = vec![0i128; DCT_VALUE_COUNT];
     for row in 0..DCT_EDGE {
         for col in 0..DCT_EDGE {
             let mut acc = 0i128;
@@ -96,56 +168,103 @@ fn compute_dct_fixed(pixels: &[i64]) -> Vec<i64> {
                 let value = intermediate[row * BUFFER_EDGE + k];
                 acc += value * coeff;
             }
-            output[row * DCT_EDGE + col] = acc as i64;
+            output[row * DCT_EDGE + col] = acc;
         }
     }
     output
 }
 
+/// Lower median of the fixed-point DCT, the value `torben_median` would pick.
+fn lower_median(values: &[i128]) -> i128 {
+    let mut sorted = values.to_vec();
+    sorted.sort_unstable();
+    sorted[(sorted.len() - 1) / 2]
+}
+
+/// Pack `dct > median` into hash bytes using the PDQ bit ordering.
+fn threshold_bits(dct_values: &[i128], median: i128) -> [u8; PDQ_HASH_LENGTH] {
+    let mut hash = [0u8; PDQ_HASH_LENGTH];
+    for (idx, &value) in dct_values.iter().enumerate() {
+        if value > median {
+            hash[PDQ_HASH_LENGTH - 1 - idx / 8] |= 1 << (idx % 8);
+        }
+    }
+    hash
+}
+
 /// Field-based Groth16 circuit verifying the PDQ hash computation.
 #[derive(Clone, Debug)]
 pub struct PDQHashCircuit<F: PrimeField> {
     /// Downsampled luminance buffer flattened in row-major order.
     pub pixels: Option<Vec<i64>>,
     /// Fixed-point median of the DCT coefficients.
-    pub median: Option<i64>,
+    pub median: Option<i128>,
     /// Public PDQ hash bytes.
     pub hash: Option<[u8; PDQ_HASH_LENGTH]>,
-    /// Positive parts of `dct - median` used to assert bit assignments.
-    pub pos_diffs: Option<Vec<i64>>,
-    /// Negative parts of `dct - median` used to assert bit assignments.
-    pub neg_diffs: Option<Vec<i64>>,
-    /// Field inverses for each coefficient difference (0 when the diff is zero).
+    /// Per-coefficient sign slack: `dct - median - 1` when the hash bit is set,
+    /// `median - dct` otherwise.
+    pub sign_slack: Option<Vec<u128>>,
+    /// Field inverses for each `dct - median` (0 when the diff is zero).
     pub diff_inverses: Option<Vec<F>>,
-    /// Scaled floating-point differences between DCT coefficients and the median.
-    pub float_diffs: Option<Vec<i64>>,
-    /// Positive rounding slack to reconcile integer and float differences.
-    pub corr_pos: Option<Vec<i64>>,
-    /// Negative rounding slack to reconcile integer and float differences.
-    pub corr_neg: Option<Vec<i64>>,
+    /// Whether each coefficient ties with the median (its difference is zero).
+    pub ties: Option<Vec<bool>>,
 }
 
-impl<F: PrimeField> ConstraintSynthesizer<F> for PDQHashCircuit<F> {
-    fn generate_constraints(self, cs: ConstraintSystemRef<F>) -> Result<(), SynthesisError> {
+impl<F: PrimeField> PDQHashCircuit<F> {
+    /// Derive every witness from a quantised 64x64 buffer, thresholding the
+    /// fixed-point DCT at `median`.
+    fn assign_with_median(pixels: Vec<i64>, median: i128) -> Self {
+        let dct_values = compute_dct_fixed(&pixels);
+        let hash = threshold_bits(&dct_values, median);
+
+        let mut sign_slack = Vec::with_capacity(DCT_VALUE_COUNT);
+        let mut inverses = Vec::with_capacity(DCT_VALUE_COUNT);
+        let mut ties = Vec::with_capacity(DCT_VALUE_COUNT);
+        for &value in &dct_values {
+            let diff = value - median;
+            sign_slack.push(if diff > 0 { diff - 1 } else { -diff } as u128);
+            let diff_field = field_from_i128::<F>(diff);
+            inverses.push(diff_field.inverse().unwrap_or_else(F::zero));
+            ties.push(diff == 0);
+        }
+
+        Self {
+            pixels: Some(pixels),
+            median: Some(median),
+            hash: Some(hash),
+            sign_slack: Some(sign_slack),
+            diff_inverses: Some(inverses),
+            ties: Some(ties),
+        }
+    }
+
+    /// Derive every witness from a quantised 64x64 buffer.
+    fn assign(pixels: Vec<i64>) -> Self {
+        let median = lower_median(&compute_dct_fixed(&pixels));
+        Self::assign_with_median(pixels, median)
+    }
+}
+
+impl<F: PrimeField> PDQHashCircuit<F> {
+    /// Synthesize the circuit, calling `checkpoint` with the name of each
+    /// phase as soon as its constraints have been emitted.
+    fn synthesize(
+        self,
+        cs: ConstraintSystemRef<F>,
+        mut checkpoint: impl FnMut(&'static str),
+    ) -> Result<(), SynthesisError> {
         let hash_bytes = self.hash.unwrap_or([0u8; PDQ_HASH_LENGTH]);
         let pixel_values = self
             .pixels
             .unwrap_or_else(|| vec![0i64; BUFFER_EDGE * BUFFER_EDGE]);
         let median_value = self.median.unwrap_or(0);
-        let pos_values = self
-            .pos_diffs
-            .unwrap_or_else(|| vec![0i64; DCT_VALUE_COUNT]);
-        let neg_values = self
-            .neg_diffs
-            .unwrap_or_else(|| vec![0i64; DCT_VALUE_COUNT]);
+        let slack_values = self
+            .sign_slack
+            .unwrap_or_else(|| vec![0u128; DCT_VALUE_COUNT]);
         let inverse_values = self
             .diff_inverses
             .unwrap_or_else(|| vec![F::zero(); DCT_VALUE_COUNT]);
-        let float_diff_values = self
-            .float_diffs
-            .unwrap_or_else(|| vec![0i64; DCT_VALUE_COUNT]);
-        let corr_pos_values = self.corr_pos.unwrap_or_else(|| vec![0i64; DCT_VALUE_COUNT]);
-        let corr_neg_values = self.corr_neg.unwrap_or_else(|| vec![0i64; DCT_VALUE_COUNT]);
+        let tie_values = self.ties.unwrap_or_else(|| vec![false; DCT_VALUE_COUNT]);
 
         let mut hash_bits = Vec::with_capacity(DCT_VALUE_COUNT);
         for idx in 0..DCT_VALUE_COUNT {
@@ -153,15 +272,15 @@ impl<F: PrimeField> ConstraintSynthesizer<F> for PDQHashCircuit<F> {
             let bit_value = ((byte >> (idx % 8)) & 1) == 1;
             hash_bits.push(Boolean::new_input(cs.clone(), || Ok(bit_value))?);
         }
-
-        let median_var = FpVar::new_witness(cs.clone(), || Ok(field_from_i64::<F>(median_value)))?;
+        checkpoint(PHASE_HASH_BITS);
 
         let mut pixel_vars = Vec::with_capacity(pixel_values.len());
         for value in pixel_values {
-            pixel_vars.push(FpVar::new_witness(cs.clone(), || {
-                Ok(field_from_i64::<F>(value))
-            })?);
+            let pixel = FpVar::new_witness(cs.clone(), || Ok(field_from_i64::<F>(value)))?;
+            enforce_bit_length(cs.clone(), &pixel, value as u128, LUMA_BITS)?;
+            pixel_vars.push(pixel);
         }
+        checkpoint(PHASE_PIXELS);
 
         let coeffs = dct_coefficients();
         let mut intermediate = vec![FpVar::<F>::zero(); DCT_EDGE * BUFFER_EDGE];
@@ -176,6 +295,7 @@ impl<F: PrimeField> ConstraintSynthesizer<F> for PDQHashCircuit<F> {
                 intermediate[row * BUFFER_EDGE + col] = acc;
             }
         }
+        checkpoint(PHASE_DCT_ROWS);
 
         let mut dct_values = Vec::with_capacity(DCT_VALUE_COUNT);
         for row in 0..DCT_EDGE {
@@ -189,63 +309,402 @@ impl<F: PrimeField> ConstraintSynthesizer<F> for PDQHashCircuit<F> {
                 dct_values.push(acc);
             }
         }
+        checkpoint(PHASE_DCT_COLUMNS);
 
-        // Reconstruct public hash bits from the bytes.
+        // Tie each public hash bit to the side of the median its coefficient lies on.
+        let median_var = FpVar::new_witness(cs.clone(), || Ok(field_from_i128::<F>(median_value)))?;
+        let mut set_count = FpVar::<F>::zero();
+        let mut tie_count = FpVar::<F>::zero();
         for (idx, dct) in dct_values.into_iter().enumerate() {
-            let pos = FpVar::new_witness(cs.clone(), || Ok(field_from_i64::<F>(pos_values[idx])))?;
-            let neg = FpVar::new_witness(cs.clone(), || Ok(field_from_i64::<F>(neg_values[idx])))?;
+            let diff = dct - median_var.clone();
+            let bit_fp: FpVar<F> = hash_bits[idx].clone().into();
+
+            // slack = bit ? diff - 1 : -diff, which must be a small non-negative integer.
+            let slack = bit_fp.clone() * (diff.double()? - FpVar::one()) - diff.clone();
+            enforce_bit_length(cs.clone(), &slack, slack_values[idx], SIGN_BITS)?;
+
+            // `tie` is 1 exactly when the difference is zero.
             let diff_inv = FpVar::new_witness(cs.clone(), || Ok(inverse_values[idx]))?;
-            let float_diff = FpVar::new_witness(cs.clone(), || {
-                Ok(field_from_i64::<F>(float_diff_values[idx]))
-            })?;
-
-            let corr_pos_u64 = UInt64::new_witness(cs.clone(), || Ok(corr_pos_values[idx] as u64))?;
-            let corr_neg_u64 = UInt64::new_witness(cs.clone(), || Ok(corr_neg_values[idx] as u64))?;
-            let corr_pos_bits = corr_pos_u64.to_bits_le();
-            let corr_neg_bits = corr_neg_u64.to_bits_le();
-            for bit in corr_pos_bits.iter().skip(CORRECTION_BITS) {
-                bit.enforce_equal(&Boolean::FALSE)?;
-            }
-            for bit in corr_neg_bits.iter().skip(CORRECTION_BITS) {
-                bit.enforce_equal(&Boolean::FALSE)?;
-            }
+            let tie = Boolean::new_witness(cs.clone(), || Ok(tie_values[idx]))?;
+            let tie_fp: FpVar<F> = tie.into();
+            (diff.clone() * tie_fp.clone()).enforce_equal(&FpVar::zero())?;
+            (diff * diff_inv).enforce_equal(&(FpVar::one() - tie_fp.clone()))?;
 
-            let mut corr_pos_fp = FpVar::<F>::zero();
-            let mut coeff = F::one();
-            for bit in &corr_pos_bits {
-                let bit_fp: FpVar<F> = bit.clone().into();
-                corr_pos_fp += bit_fp * coeff;
-                coeff = coeff + coeff;
-            }
+            set_count += bit_fp;
+            tie_count += tie_fp;
+        }
+        checkpoint(PHASE_MEDIAN_COMPARISON);
 
-            let mut corr_neg_fp = FpVar::<F>::zero();
-            coeff = F::one();
-            for bit in &corr_neg_bits {
-                let bit_fp: FpVar<F> = bit.clone().into();
-                corr_neg_fp += bit_fp * coeff;
-                coeff = coeff + coeff;
-            }
+        // Median property: at most MEDIAN_RANK coefficients lie strictly above
+        // the median (set bits) and at most MEDIAN_RANK - 1 strictly below it
+        // (neither set nor tied), which pins the median to the lower median.
+        let native_set = hash_bytes
+            .iter()
+            .map(|b| b.count_ones() as i64)
+            .sum::<i64>();
+        let native_ties = tie_values.iter().filter(|t| **t).count() as i64;
+        let above_slack = FpVar::constant(F::from(MEDIAN_RANK as u64)) - set_count.clone();
+        enforce_bit_length(
+            cs.clone(),
+            &above_slack,
+            (MEDIAN_RANK as i64 - native_set) as u128,
+            COUNT_SLACK_BITS,
+        )?;
+        let below_slack = set_count + tie_count - FpVar::constant(F::from(MEDIAN_RANK as u64 + 1));
+        enforce_bit_length(
+            cs,
+            &below_slack,
+            (native_set + native_ties - MEDIAN_RANK as i64 - 1) as u128,
+            COUNT_SLACK_BITS,
+        )?;
+        checkpoint(PHASE_MEDIAN_RANK);
 
-            let diff = dct.clone() - median_var.clone();
-            (diff.clone() - float_diff.clone())
-                .enforce_equal(&(corr_pos_fp.clone() - corr_neg_fp.clone()))?;
-            (corr_pos_fp.clone() * corr_neg_fp.clone()).enforce_equal(&FpVar::zero())?;
+        Ok(())
+    }
 
-            (pos.clone() - neg.clone()).enforce_equal(&float_diff)?;
-            (pos.clone() * neg.clone()).enforce_equal(&FpVar::zero())?;
+    /// Synthesize the circuit in setup mode and count what each phase adds.
+    pub fn constraint_profile() -> Result<CircuitProfile, SynthesisError> {
+        let cs = ConstraintSystem::<F>::new_ref();
+        cs.set_mode(SynthesisMode::Setup);
 
-            let bit_fp: FpVar<F> = hash_bits[idx].clone().into();
-            (bit_fp.clone() * neg.clone()).enforce_equal(&FpVar::zero())?;
-            ((FpVar::one() - bit_fp.clone()) * pos.clone()).enforce_equal(&FpVar::zero())?;
+        let mut phases = Vec::new();
+        let mut last = PhaseCounts::default();
+        let blank = Self {
+            pixels: None,
+            median: None,
+            hash: None,
+            sign_slack: None,
+            diff_inverses: None,
+            ties: None,
+        };
+        blank.synthesize(cs.clone(), |name| {
+            let now = PhaseCounts::snapshot(name, &cs);
+            phases.push(now.minus(&last));
+            last = now;
+        })?;
+
+        Ok(CircuitProfile { phases })
+    }
+}
+
+impl<F: PrimeField> ConstraintSynthesizer<F> for PDQHashCircuit<F> {
+    fn generate_constraints(self, cs: ConstraintSystemRef<F>) -> Result<(), SynthesisError> {
+        self.synthesize(cs, |_| {})
+    }
+}
 
-            let diff_product = float_diff.clone() * diff_inv.clone();
-            (bit_fp * (diff_product - FpVar::one())).enforce_equal(&FpVar::zero())?;
+/// Allocation of the 256 public hash bits.
+const PHASE_HASH_BITS: &str = "public hash bits";
+/// Witnessing and range-checking the 64x64 luminance buffer.
+const PHASE_PIXELS: &str = "pixel allocation";
+/// First DCT pass (16 coefficient rows against the pixel columns).
+const PHASE_DCT_ROWS: &str = "DCT rows";
+/// Second DCT pass producing the 16x16 coefficient block.
+const PHASE_DCT_COLUMNS: &str = "DCT columns";
+/// Per-coefficient sign slack and tie detection against the median. This
+/// replaces the rounding corrections of the earlier float-backed circuit.
+const PHASE_MEDIAN_COMPARISON: &str = "median comparison";
+/// Counting constraints pinning the witness median to the lower median.
+const PHASE_MEDIAN_RANK: &str = "median rank";
+
+/// Constraint-system sizes contributed by one synthesis phase.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PhaseCounts {
+    /// Phase name.
+    pub name: &'static str,
+    /// R1CS constraints.
+    pub constraints: usize,
+    /// Private witness variables.
+    pub witness_variables: usize,
+    /// Public input variables (excluding the constant `1`).
+    pub public_inputs: usize,
+    /// Symbolic linear combinations (free in R1CS, but they cost memory and
+    /// synthesis time).
+    pub linear_combinations: usize,
+}
+
+impl PhaseCounts {
+    fn snapshot<F: PrimeField>(name: &'static str, cs: &ConstraintSystemRef<F>) -> Self {
+        Self {
+            name,
+            constraints: cs.num_constraints(),
+            witness_variables: cs.num_witness_variables(),
+            // The constraint system always reserves one instance variable for `1`.
+            public_inputs: cs.num_instance_variables() - 1,
+            linear_combinations: cs.borrow().map_or(0, |inner| inner.num_linear_combinations),
+        }
+    }
+
+    fn minus(&self, earlier: &Self) -> Self {
+        Self {
+            name: self.name,
+            constraints: self.constraints - earlier.constraints,
+            witness_variables: self.witness_variables - earlier.witness_variables,
+            public_inputs: self.public_inputs - earlier.public_inputs,
+            linear_combinations: self.linear_combinations - earlier.linear_combinations,
+        }
+    }
+}
+
+/// Per-phase size breakdown of [`PDQHashCircuit`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CircuitProfile {
+    /// Phases in synthesis order.
+    pub phases: Vec<PhaseCounts>,
+}
+
+impl CircuitProfile {
+    /// Sum over all phases.
+    pub fn total(&self) -> PhaseCounts {
+        self.phases.iter().fold(
+            PhaseCounts {
+                name: "total",
+                ..PhaseCounts::default()
+            },
+            |acc, phase| PhaseCounts {
+                name: acc.name,
+                constraints: acc.constraints + phase.constraints,
+                witness_variables: acc.witness_variables + phase.witness_variables,
+                public_inputs: acc.public_inputs + phase.public_inputs,
+                linear_combinations: acc.linear_combinations + phase.linear_combinations,
+            },
+        )
+    }
+}
+
+impl fmt::Display for CircuitProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<20} {:>12} {:>12} {:>8} {:>12}",
+            "phase", "constraints", "witnesses", "inputs", "lin. combs"
+        )?;
+        for phase in self.phases.iter().chain(std::iter::once(&self.total())) {
+            writeln!(
+                f,
+                "{:<20} {:>12} {:>12} {:>8} {:>12}",
+                phase.name,
+                phase.constraints,
+                phase.witness_variables,
+                phase.public_inputs,
+                phase.linear_combinations
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Wall-clock time spent in each stage of producing and checking a proof.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ProverTimings {
+    /// Decoding the image, running PDQ and deriving the circuit witness.
+    pub witness_generation: Duration,
+    /// Synthesizing the constraint system with a full assignment.
+    pub synthesis: Duration,
+    /// Groth16 proving (synthesis again, the QAP reduction and the MSMs).
+    pub proving: Duration,
+    /// Groth16 verification.
+    pub verification: Duration,
+}
+
+impl fmt::Display for ProverTimings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:<20} {:>12}", "stage", "time")?;
+        for (name, time) in [
+            ("witness generation", self.witness_generation),
+            ("synthesis", self.synthesis),
+            ("proving", self.proving),
+            ("verification", self.verification),
+        ] {
+            writeln!(f, "{:<20} {:>12.3?}", name, time)?;
+        }
+        Ok(())
+    }
+}
+
+/// Private input to a PDQ proof: the quantised 64x64 luminance buffer.
+///
+/// A witness can be built from a decoded image, a raw luminance buffer or an
+/// already filtered 64x64 buffer, written to disk with
+/// [`CanonicalSerialize`] and proven later with [`PDQSnark::prove_witness`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PdqWitness {
+    pixels: Vec<u32>,
+    pdq_hash: [u8; PDQ_HASH_LENGTH],
+    hash: [u8; PDQ_HASH_LENGTH],
+}
+
+impl PdqWitness {
+    /// Build a witness from a decoded image, hashed at full size.
+    pub fn from_image(image: &image::DynamicImage) -> Self {
+        Self::from_state(&compute_pdq_state(image))
+    }
+
+    /// Build a witness from a row-major luminance buffer of `width * height`
+    /// values in `0.0..=255.0`.
+    pub fn from_luma(width: usize, height: usize, luma: &[f32]) -> anyhow::Result<Self> {
+        if width == 0 || height == 0 {
+            return Err(anyhow!("luminance buffer must not be empty"));
+        }
+        if luma.len() != width * height {
+            return Err(anyhow!(
+                "expected {} luminance values for a {}x{} buffer but received {}",
+                width * height,
+                width,
+                height,
+                luma.len()
+            ));
+        }
+        Self::check_luma(luma.iter())?;
+        Ok(Self::from_state(&compute_pdq_state_from_luma(
+            width,
+            height,
+            luma.to_vec(),
+        )))
+    }
+
+    /// Build a witness from a filtered and decimated 64x64 luminance buffer.
+    ///
+    /// Values are clamped into the circuit's pixel range, which absorbs the
+    /// rounding overshoot of the box filter.
+    pub fn from_buffer64(buffer: &[[f32; BUFFER_EDGE]; BUFFER_EDGE]) -> anyhow::Result<Self> {
+        if !buffer.iter().flatten().all(|v| v.is_finite()) {
+            return Err(anyhow!("luminance values must be finite"));
+        }
+        Ok(Self::from_state(&compute_pdq_state_from_buffer64(*buffer)))
+    }
+
+    fn check_luma<'a>(mut values: impl Iterator<Item = &'a f32>) -> anyhow::Result<()> {
+        if values.all(|v| (0.0..=255.0).contains(v)) {
+            Ok(())
+        } else {
+            Err(anyhow!("luminance values must lie in 0.0..=255.0"))
+        }
+    }
+
+    fn from_state(state: &PDQPrecomputed) -> Self {
+        let pixels = quantize_buffer(&state.buffer64)
+            .into_iter()
+            .map(|p| p as u32)
+            .collect::<Vec<_>>();
+        let hash = Self::fixed_point_hash(&pixels);
+        if hash != state.hash {
+            log::debug!(
+                "fixed-point hash {:02x?} differs from PDQ hash {:02x?}",
+                hash,
+                state.hash
+            );
+        }
+        Self {
+            pixels,
+            pdq_hash: state.hash,
+            hash,
         }
+    }
+
+    fn fixed_point_hash(pixels: &[u32]) -> [u8; PDQ_HASH_LENGTH] {
+        let pixels = pixels.iter().map(|p| *p as i64).collect::<Vec<_>>();
+        let dct_values = compute_dct_fixed(&pixels);
+        threshold_bits(&dct_values, lower_median(&dct_values))
+    }
+
+    /// Quantised luminance values in row-major order.
+    pub fn pixels(&self) -> &[u32] {
+        &self.pixels
+    }
+
+    /// The hash the circuit proves: PDQ over the fixed-point DCT.
+    pub fn hash(&self) -> [u8; PDQ_HASH_LENGTH] {
+        self.hash
+    }
+
+    /// The floating-point PDQ hash of the buffer the witness was built from.
+    pub fn pdq_hash(&self) -> [u8; PDQ_HASH_LENGTH] {
+        self.pdq_hash
+    }
+
+    /// Whether the proven hash agrees with the floating-point PDQ hash.
+    pub fn matches_pdq(&self) -> bool {
+        self.hash == self.pdq_hash
+    }
+
+    fn circuit(&self) -> PDQHashCircuit<BlsFr> {
+        PDQHashCircuit::assign(self.pixels.iter().map(|p| *p as i64).collect())
+    }
+}
+
+impl CanonicalSerialize for PdqWitness {
+    fn serialize_with_mode<W: Write>(
+        &self,
+        mut writer: W,
+        compress: Compress,
+    ) -> Result<(), SerializationError> {
+        self.pixels.serialize_with_mode(&mut writer, compress)?;
+        self.pdq_hash.serialize_with_mode(&mut writer, compress)?;
+        self.hash.serialize_with_mode(&mut writer, compress)
+    }
+
+    fn serialized_size(&self, compress: Compress) -> usize {
+        self.pixels.serialized_size(compress)
+            + self.pdq_hash.serialized_size(compress)
+            + self.hash.serialized_size(compress)
+    }
+}
 
+impl Valid for PdqWitness {
+    fn check(&self) -> Result<(), SerializationError> {
+        if self.pixels.len() != BUFFER_EDGE * BUFFER_EDGE
+            || self.pixels.iter().any(|p| *p >> LUMA_BITS != 0)
+            || Self::fixed_point_hash(&self.pixels) != self.hash
+        {
+            return Err(SerializationError::InvalidData);
+        }
         Ok(())
     }
 }
 
+impl CanonicalDeserialize for PdqWitness {
+    fn deserialize_with_mode<R: Read>(
+        mut reader: R,
+        compress: Compress,
+        validate: Validate,
+    ) -> Result<Self, SerializationError> {
+        // ark-serialize's `[T; N]` impl panics on short input, so the
+        // fixed-size hashes are read directly.
+        let pixels = Vec::deserialize_with_mode(&mut reader, compress, validate)?;
+        let mut pdq_hash = [0u8; PDQ_HASH_LENGTH];
+        reader.read_exact(&mut pdq_hash)?;
+        let mut hash = [0u8; PDQ_HASH_LENGTH];
+        reader.read_exact(&mut hash)?;
+        let witness = Self {
+            pixels,
+            pdq_hash,
+            hash,
+        };
+        if validate == Validate::Yes {
+            witness.check()?;
+        }
+        Ok(witness)
+    }
+}
+
+/// Public inputs for `hash`: its bits in circuit order.
+fn public_inputs_for(hash: &[u8; PDQ_HASH_LENGTH]) -> Vec<BlsFr> {
+    hash.iter()
+        .rev()
+        .flat_map(|byte| (0..8).map(move |bit| BlsFr::from(((byte >> bit) & 1) as u64)))
+        .collect()
+}
+
+/// BLAKE3 hash of a compressed verifying key, used to name key pairs.
+pub fn key_id(verifying_key: &VerifyingKey<Bls12_381>) -> [u8; 32] {
+    let mut bytes = Vec::new();
+    verifying_key
+        .serialize_compressed(&mut bytes)
+        .expect("serializing into a Vec cannot fail");
+    *blake3::hash(&bytes).as_bytes()
+}
+
 /// SNARK proving system for PDQ hashes.
 #[derive(Clone, Debug)]
 pub struct PDQSnark {
@@ -262,12 +721,9 @@ impl PDQSnark {
             pixels: Some(vec![0; BUFFER_EDGE * BUFFER_EDGE]),
             median: Some(0),
             hash: Some([0u8; PDQ_HASH_LENGTH]),
-            pos_diffs: Some(vec![0; DCT_VALUE_COUNT]),
-            neg_diffs: Some(vec![0; DCT_VALUE_COUNT]),
+            sign_slack: Some(vec![0; DCT_VALUE_COUNT]),
             diff_inverses: Some(vec![BlsFr::zero(); DCT_VALUE_COUNT]),
-            float_diffs: Some(vec![0; DCT_VALUE_COUNT]),
-            corr_pos: Some(vec![0; DCT_VALUE_COUNT]),
-            corr_neg: Some(vec![0; DCT_VALUE_COUNT]),
+            ties: Some(vec![false; DCT_VALUE_COUNT]),
         };
 
         let (pk, vk) = Groth16::<Bls12_381>::circuit_specific_setup(circuit, rng)?;
@@ -277,6 +733,21 @@ impl PDQSnark {
         })
     }
 
+    /// Rebuild the proving system from a stored proving key.
+    pub fn from_proving_key(proving_key: ProvingKey<Bls12_381>) -> Self {
+        let verifying_key = proving_key.vk.clone();
+        Self {
+            proving_key,
+            verifying_key,
+        }
+    }
+
+    /// Stable identifier of this key pair: the BLAKE3 hash of the compressed
+    /// verifying key.
+    pub fn key_id(&self) -> [u8; 32] {
+        key_id(&self.verifying_key)
+    }
+
     /// Create a Groth16 proof that the supplied image hashes to `target_hash`.
     pub fn create_proof<R: RngCore + CryptoRng>(
         &self,
@@ -286,86 +757,70 @@ impl PDQSnark {
     ) -> anyhow::Result<(Proof<Bls12_381>, Vec<BlsFr>)> {
         let image = image::load_from_memory(image_data)
             .context("failed to decode image bytes for SNARK proof")?;
-        let state = compute_pdq_state(&image);
-
-        let quantised = quantize_buffer(&state.buffer64);
-        let dct_values = compute_dct_fixed(&quantised);
-        let median = (state.median as f64 * FINAL_SCALE as f64).round() as i64;
-        let hash_bytes = state.hash;
-        println!("hash_bytes: {:?}", hash_bytes);
-        println!("target_hash: {:?}", target_hash);
-        if hash_bytes != target_hash {
+        let witness = PdqWitness::from_image(&image);
+        if witness.pdq_hash() != target_hash {
             return Err(anyhow!(
                 "provided target hash does not match computed PDQ hash"
             ));
         }
+        self.prove_witness(&witness, rng)
+    }
 
-        let mut pos = Vec::with_capacity(DCT_VALUE_COUNT);
-        let mut neg = Vec::with_capacity(DCT_VALUE_COUNT);
-        let mut inverses = Vec::with_capacity(DCT_VALUE_COUNT);
-        let mut float_diffs = Vec::with_capacity(DCT_VALUE_COUNT);
-        let mut corr_pos = Vec::with_capacity(DCT_VALUE_COUNT);
-        let mut corr_neg = Vec::with_capacity(DCT_VALUE_COUNT);
-
-        for (idx, &value) in dct_values.iter().enumerate() {
-            let diff = value - median;
-            let float_diff = state.dct16[idx] as f64 - state.median as f64;
-            let float_scaled = (float_diff * FINAL_SCALE as f64).round() as i64;
-            let delta = diff - float_scaled;
-
-            let (pos_corr, neg_corr) = if delta >= 0 {
-                (delta as u64, 0u64)
-            } else {
-                (0u64, (-delta) as u64)
-            };
-
-            if pos_corr > CORRECTION_TOLERANCE || neg_corr > CORRECTION_TOLERANCE {
-                return Err(anyhow!("rounding difference exceeded tolerance"));
-            }
-
-            float_diffs.push(float_scaled);
-            corr_pos.push(pos_corr as i64);
-            corr_neg.push(neg_corr as i64);
+    /// Create a Groth16 proof that `witness` hashes to [`PdqWitness::hash`].
+    ///
+    /// Fails if the fixed-point hash diverges from the floating-point PDQ
+    /// hash, so a proof always attests to the hash PDQ itself reports.
+    pub fn prove_witness<R: RngCore + CryptoRng>(
+        &self,
+        witness: &PdqWitness,
+        rng: &mut R,
+    ) -> anyhow::Result<(Proof<Bls12_381>, Vec<BlsFr>)> {
+        log::debug!("proving PDQ hash {:02x?}", witness.hash());
+        if !witness.matches_pdq() {
+            return Err(anyhow!(
+                "fixed-point PDQ hash diverges from the float hash near the median"
+            ));
+        }
 
-            if float_scaled > 0 {
-                pos.push(float_scaled);
-                neg.push(0);
-            } else {
-                pos.push(0);
-                neg.push(-float_scaled);
-            }
+        let proof = Groth16::<Bls12_381>::prove(&self.proving_key, witness.circuit(), rng)?;
+        Ok((proof, public_inputs_for(&witness.hash())))
+    }
 
-            let diff_field = field_from_i64::<BlsFr>(float_scaled);
-            let inverse = if diff_field.is_zero() {
-                BlsFr::zero()
-            } else {
-                diff_field
-                    .inverse()
-                    .ok_or_else(|| anyhow!("failed to compute inverse for non-zero diff"))?
-            };
-            inverses.push(inverse);
-        }
+    /// Prove and verify the hash of `image_data`, timing each stage.
+    pub fn profile_proof<R: RngCore + CryptoRng>(
+        &self,
+        image_data: &[u8],
+        rng: &mut R,
+    ) -> anyhow::Result<ProverTimings> {
+        let start = Instant::now();
+        let image = image::load_from_memory(image_data)
+            .context("failed to decode image bytes for SNARK proof")?;
+        let witness = PdqWitness::from_image(&image);
+        let circuit = witness.circuit();
+        let witness_generation = start.elapsed();
 
-        let circuit = PDQHashCircuit::<BlsFr> {
-            pixels: Some(quantised),
-            median: Some(median),
-            hash: Some(hash_bytes),
-            pos_diffs: Some(pos),
-            neg_diffs: Some(neg),
-            diff_inverses: Some(inverses),
-            float_diffs: Some(float_diffs),
-            corr_pos: Some(corr_pos),
-            corr_neg: Some(corr_neg),
-        };
+        let start = Instant::now();
+        let cs = ConstraintSystem::<BlsFr>::new_ref();
+        circuit.clone().generate_constraints(cs.clone())?;
+        cs.finalize();
+        let synthesis = start.elapsed();
 
+        let start = Instant::now();
         let proof = Groth16::<Bls12_381>::prove(&self.proving_key, circuit, rng)?;
-        let public_inputs = hash_bytes
-            .iter()
-            .rev()
-            .flat_map(|byte| (0..8).map(move |bit| BlsFr::from(((byte >> bit) & 1) as u64)))
-            .collect();
+        let proving = start.elapsed();
 
-        Ok((proof, public_inputs))
+        let start = Instant::now();
+        if !self.verify_proof(&proof, &public_inputs_for(&witness.hash()))? {
+            return Err(anyhow!("freshly generated proof failed to verify"));
+        }
+        let verification = start.elapsed();
+
+        Ok(ProverTimings {
+            witness_generation,
+            synthesis,
+            proving,
+            verification,
+        })
     }
 
     /// Verify a Groth16 proof for the PDQ hash circuit.
@@ -425,4 +880,211 @@ mod tests {
             .unwrap();
         assert!(snark.verify_proof(&proof, &public_inputs).unwrap());
     }
+
+    #[test]
+    fn witness_constructors_agree() {
+        let image_bytes = include_bytes!("test_data/bridge-1-original.jpg");
+        let gray = image::DynamicImage::ImageLuma8(
+            image::load_from_memory(image_bytes).unwrap().to_luma8(),
+        );
+        let from_image = PdqWitness::from_image(&gray);
+
+        let luma = gray
+            .as_luma8()
+            .unwrap()
+            .pixels()
+            .map(|p| p.0[0] as f32)
+            .collect::<Vec<_>>();
+        let from_luma =
+            PdqWitness::from_luma(gray.width() as usize, gray.height() as usize, &luma).unwrap();
+        assert_eq!(from_image, from_luma);
+
+        let state = compute_pdq_state(&gray);
+        let from_buffer = PdqWitness::from_buffer64(&state.buffer64).unwrap();
+        assert_eq!(from_image, from_buffer);
+        assert_eq!(from_image.pdq_hash(), state.hash);
+
+        assert!(PdqWitness::from_luma(2, 2, &luma[..3]).is_err());
+        assert!(PdqWitness::from_luma(1, 1, &[300.0]).is_err());
+    }
+
+    #[test]
+    fn witness_serialization_roundtrip() {
+        let image_bytes = include_bytes!("test_data/bridge-1-original.jpg");
+        let witness = PdqWitness::from_image(&image::load_from_memory(image_bytes).unwrap());
+        assert!(witness.matches_pdq());
+
+        let mut bytes = Vec::new();
+        witness.serialize_compressed(&mut bytes).unwrap();
+        let decoded = PdqWitness::deserialize_compressed(&*bytes).unwrap();
+        assert_eq!(decoded, witness);
+
+        let mut wide_pixel = witness.clone();
+        wide_pixel.pixels[0] = 1 << LUMA_BITS;
+        let mut wrong_hash = witness;
+        wrong_hash.hash[0] ^= 1;
+        for tampered in [wide_pixel, wrong_hash] {
+            let mut bytes = Vec::new();
+            tampered.serialize_compressed(&mut bytes).unwrap();
+            assert!(PdqWitness::deserialize_compressed(&*bytes).is_err());
+        }
+    }
+
+    fn is_satisfied(circuit: PDQHashCircuit<BlsFr>) -> bool {
+        let cs = ConstraintSystem::<BlsFr>::new_ref();
+        circuit.generate_constraints(cs.clone()).unwrap();
+        cs.is_satisfied().unwrap()
+    }
+
+    fn bridge_state() -> PDQPrecomputed {
+        let image_bytes = include_bytes!("test_data/bridge-1-original.jpg");
+        compute_pdq_state(&image::load_from_memory(image_bytes).unwrap())
+    }
+
+    fn honest_circuit() -> PDQHashCircuit<BlsFr> {
+        PDQHashCircuit::assign(quantize_buffer(&bridge_state().buffer64))
+    }
+
+    /// Index of the coefficient closest to the median on the requested side.
+    fn nearest_to_median(circuit: &PDQHashCircuit<BlsFr>, above: bool) -> usize {
+        let median = circuit.median.unwrap();
+        let dct_values = compute_dct_fixed(circuit.pixels.as_ref().unwrap());
+        (0..DCT_VALUE_COUNT)
+            .filter(|&idx| (dct_values[idx] > median) == above && dct_values[idx] != median)
+            .min_by_key(|&idx| (dct_values[idx] - median).abs())
+            .unwrap()
+    }
+
+    fn flip_bit(circuit: &mut PDQHashCircuit<BlsFr>, idx: usize) {
+        circuit.hash.as_mut().unwrap()[PDQ_HASH_LENGTH - 1 - idx / 8] ^= 1 << (idx % 8);
+    }
+
+    #[test]
+    fn constraint_profile_matches_synthesis() {
+        let profile = PDQHashCircuit::<BlsFr>::constraint_profile().unwrap();
+        let names: Vec<_> = profile.phases.iter().map(|p| p.name).collect();
+        assert_eq!(
+            names,
+            [
+                PHASE_HASH_BITS,
+                PHASE_PIXELS,
+                PHASE_DCT_ROWS,
+                PHASE_DCT_COLUMNS,
+                PHASE_MEDIAN_COMPARISON,
+                PHASE_MEDIAN_RANK
+            ]
+        );
+
+        let cs = ConstraintSystem::<BlsFr>::new_ref();
+        honest_circuit().generate_constraints(cs.clone()).unwrap();
+        let total = profile.total();
+        assert_eq!(total.constraints, cs.num_constraints());
+        assert_eq!(total.witness_variables, cs.num_witness_variables());
+        assert_eq!(total.public_inputs, PDQ_HASH_BITS);
+
+        // The DCT is linear, so it costs no constraints.
+        assert_eq!(profile.phases[2].constraints, 0);
+        assert_eq!(profile.phases[3].constraints, 0);
+    }
+
+    #[test]
+    fn honest_witness_satisfies_circuit() {
+        assert!(is_satisfied(honest_circuit()));
+    }
+
+    #[test]
+    fn fixed_point_hash_matches_float_pdq() {
+        let images: [&[u8]; 8] = [
+            include_bytes!("test_data/bridge-1-original.jpg"),
+            include_bytes!("test_data/bridge-2-rotate-90.jpg"),
+            include_bytes!("test_data/bridge-3-rotate-180.jpg"),
+            include_bytes!("test_data/bridge-4-rotate-270.jpg"),
+            include_bytes!("test_data/bridge-5-flipx.jpg"),
+            include_bytes!("test_data/bridge-6-flipy.jpg"),
+            include_bytes!("test_data/bridge-7-flip-plus-1.jpg"),
+            include_bytes!("test_data/bridge-8-flip-minus-1.jpg"),
+        ];
+        for bytes in images {
+            let state = compute_pdq_state(&image::load_from_memory(bytes).unwrap());
+            let circuit = PDQHashCircuit::<BlsFr>::assign(quantize_buffer(&state.buffer64));
+            assert_eq!(circuit.hash, Some(state.hash));
+        }
+    }
+
+    #[test]
+    fn forged_median_is_rejected() {
+        let pixels = quantize_buffer(&bridge_state().buffer64);
+        let mut sorted = compute_dct_fixed(&pixels);
+        sorted.sort_unstable();
+
+        // Thresholds well below or above the true median keep every
+        // per-coefficient sign witness consistent but miscount the bits.
+        for rank in [DCT_VALUE_COUNT / 4, 3 * DCT_VALUE_COUNT / 4] {
+            let forged = PDQHashCircuit::assign_with_median(pixels.clone(), sorted[rank]);
+            assert!(!is_satisfied(forged));
+        }
+
+        // The upper median also splits the coefficients in half, but only the
+        // lower median is accepted, so the hash is unique.
+        let upper = PDQHashCircuit::assign_with_median(pixels, sorted[MEDIAN_RANK]);
+        assert!(!is_satisfied(upper));
+    }
+
+    #[test]
+    fn flipped_bits_near_median_are_rejected() {
+        for above in [true, false] {
+            let mut circuit = honest_circuit();
+            let idx = nearest_to_median(&circuit, above);
+            flip_bit(&mut circuit, idx);
+            // The best a prover can do is keep the honest slack; the value it
+            // would need is negative and has no small representative.
+            assert!(!is_satisfied(circuit));
+        }
+
+        // Swapping the two coefficients adjacent to the median keeps the bit
+        // count intact, so only the sign constraints catch it.
+        let mut circuit = honest_circuit();
+        let above = nearest_to_median(&circuit, true);
+        let below = nearest_to_median(&circuit, false);
+        flip_bit(&mut circuit, above);
+        flip_bit(&mut circuit, below);
+        assert!(!is_satisfied(circuit));
+    }
+
+    #[test]
+    fn setting_a_tied_bit_is_rejected() {
+        let mut circuit = honest_circuit();
+        let idx = circuit
+            .ties
+            .as_ref()
+            .unwrap()
+            .iter()
+            .position(|t| *t)
+            .unwrap();
+        flip_bit(&mut circuit, idx);
+        assert!(!is_satisfied(circuit));
+    }
+
+    #[test]
+    fn out_of_range_sign_slack_is_rejected() {
+        // A clear bit far above the median would need a slack of -diff, which
+        // wraps to a value wider than SIGN_BITS.
+        let mut circuit = honest_circuit();
+        let idx = nearest_to_median(&circuit, true);
+        flip_bit(&mut circuit, idx);
+        let median = circuit.median.unwrap();
+        let diff = compute_dct_fixed(circuit.pixels.as_ref().unwrap())[idx] - median;
+        circuit.sign_slack.as_mut().unwrap()[idx] = (-diff) as u128;
+        assert!(!is_satisfied(circuit));
+    }
+
+    #[test]
+    fn out_of_range_pixels_are_rejected() {
+        let mut pixels = quantize_buffer(&bridge_state().buffer64);
+        pixels[0] = 1 << LUMA_BITS;
+        assert!(!is_satisfied(PDQHashCircuit::assign(pixels.clone())));
+
+        pixels[0] = -1;
+        assert!(!is_satisfied(PDQHashCircuit::assign(pixels)));
+    }
 }
@@ -0,0 +1,129 @@
+//! End-to-end run of the `pdqhash regime-a` service over localhost TCP, with
+//! an audit log of its decisions.
+#![cfg(all(feature = "regime-a", feature = "cli"))]
+
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Output, Stdio};
+
+fn test_image(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("src/test_data")
+        .join(name)
+}
+
+fn pdqhash(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_pdqhash"))
+        .args(args)
+        .output()
+        .expect("failed to run pdqhash")
+}
+
+/// Kills the server when the test ends, even on failure.
+struct Server(Child);
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+#[test]
+fn serve_and_submit_over_tcp() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = |name: &str| dir.path().join(name).to_str().unwrap().to_owned();
+
+    let original = image::open(test_image("bridge-1-original.jpg")).unwrap();
+    let (hash, _) = pdqhash::generate_pdq(&original).unwrap();
+    std::fs::write(path("hashes.txt"), format!("{}\n", hex::encode(hash))).unwrap();
+    let setup = pdqhash(&[
+        "regime-a",
+        "setup",
+        "--hashes",
+        &path("hashes.txt"),
+        "--client-key",
+        &path("client.key"),
+        "--server-key",
+        &path("server.key"),
+    ]);
+    assert!(setup.status.success(), "{:?}", setup);
+    let keygen = pdqhash(&[
+        "regime-a",
+        "audit-keygen",
+        "--key",
+        &path("audit.key"),
+        "--public-key",
+        &path("audit.pub"),
+    ]);
+    assert!(keygen.status.success(), "{:?}", keygen);
+
+    let mut server = Server(
+        Command::new(env!("CARGO_BIN_EXE_pdqhash"))
+            .args([
+                "regime-a",
+                "serve",
+                "--server-key",
+                &path("server.key"),
+                "--tcp",
+                "127.0.0.1:0",
+                "--audit-log",
+                &path("audit.log"),
+                "--audit-key",
+                &path("audit.key"),
+            ])
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap(),
+    );
+    let mut line = String::new();
+    BufReader::new(server.0.stdout.take().unwrap())
+        .read_line(&mut line)
+        .unwrap();
+    let addr = line
+        .trim()
+        .strip_prefix("listening on ")
+        .expect("server announces its address")
+        .to_owned();
+
+    // Both images go over one connection, so the client key is loaded once.
+    let submit = pdqhash(&[
+        "regime-a",
+        "submit",
+        "--client-key",
+        &path("client.key"),
+        "--image",
+        test_image("bridge-1-original.jpg").to_str().unwrap(),
+        "--image",
+        test_image("emma.jpeg").to_str().unwrap(),
+        "--tcp",
+        &addr,
+        "--msgid",
+        "1",
+    ]);
+    assert!(submit.status.success(), "{:?}", submit);
+    let decisions = String::from_utf8_lossy(&submit.stdout);
+    assert_eq!(decisions.lines().collect::<Vec<_>>(), ["Yes", "No"]);
+
+    let audit = pdqhash(&[
+        "regime-a",
+        "audit-verify",
+        "--log",
+        &path("audit.log"),
+        "--public-key",
+        &path("audit.pub"),
+        "--receipts",
+    ]);
+    assert!(audit.status.success(), "{:?}", audit);
+    let lines = String::from_utf8_lossy(&audit.stdout)
+        .lines()
+        .map(str::to_owned)
+        .collect::<Vec<_>>();
+    assert_eq!(lines.len(), 3);
+    for (line, decision) in lines.iter().zip(["Yes", "No"]) {
+        let receipt: serde_json::Value = serde_json::from_str(line).unwrap();
+        assert_eq!(receipt["decision"], decision);
+        assert_eq!(receipt["epoch"], 0);
+    }
+    assert!(lines[2].starts_with("2 receipts verified"));
+}